@@ -192,18 +192,42 @@ lazy_static! {
     static ref REGISTRY: Registry = {
         let mut r = Registry::new();
 
+        r.register_encoder(encoder::csv::provider());
         r.register_encoder(encoder::debug::provider());
-
+        r.register_encoder(encoder::gelf::provider());
+        r.register_encoder(encoder::json::provider());
+        r.register_encoder(encoder::json_lines::provider());
+        r.register_encoder(encoder::logfmt::provider());
+        r.register_encoder(encoder::msgpack::provider());
+        r.register_encoder(encoder::template::provider());
+
+        r.register_event_decoder(decoder::event::access_log::provider());
+        r.register_event_decoder(decoder::event::csv::provider());
+        r.register_event_decoder(decoder::event::gelf::provider());
+        r.register_event_decoder(decoder::event::json::provider());
+        r.register_event_decoder(decoder::event::kv::provider());
+        r.register_event_decoder(decoder::event::msgpack::provider());
         r.register_event_decoder(decoder::event::text::provider());
 
         r.register_filter(filter::regex::provider());
 
+        r.register_frame_decoder(decoder::frame::csv::provider());
         r.register_frame_decoder(decoder::frame::delimited::provider());
+        r.register_frame_decoder(decoder::frame::length_prefixed::provider());
+        r.register_frame_decoder(decoder::frame::msgpack::provider());
 
+        r.register_input(input::dead_letter::provider());
         r.register_input(input::file::provider());
 
+        r.register_output(output::elasticsearch::provider());
+        r.register_output(output::exec::provider());
+        r.register_output(output::file::provider());
+        r.register_output(output::http::provider());
         r.register_output(output::null::provider());
         r.register_output(output::stdout::provider());
+        r.register_output(output::syslog::provider());
+        r.register_output(output::tcp::provider());
+        r.register_output(output::udp::provider());
 
         r.register_stream_decoder(decoder::stream::gzip::provider());
         r.register_stream_decoder(decoder::stream::plain::provider());
@@ -214,4 +238,21 @@ lazy_static! {
 
 pub fn registry() -> &'static Registry {
     &*REGISTRY
+}
+
+/// Splits nested component config into the component name and the component's own config.
+/// The nested config is either a string with the component name or a map with the `type` key
+/// holding the component name and the rest of keys being the component's config.
+pub fn split_component_config(mut value: Spanned<Value>)
+    -> Result<(Spanned<String>, Spanned<Value>)>
+{
+    if value.kind() == ValueKind::String {
+        let span = value.span.clone();
+        let name = Spanned { value: value.into_string()?, span: span.clone() };
+        return Ok((name, Spanned { value: Value::Map(Map::new()), span }));
+    }
+    let name = value.remove("type")?;
+    let span = name.span.clone();
+    let name = Spanned { value: name.into_string()?, span };
+    Ok((name, value))
 }
\ No newline at end of file
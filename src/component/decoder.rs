@@ -8,6 +8,10 @@ use std::cmp;
 use crate::error::*;
 use crate::event::*;
 
+/// Min free space in `Buf` after `ensure_writeable()`. Must be enough for any decoder to make
+/// progress, see `stream::charset::MIN_OUT_LEN`.
+const MIN_WRITEABLE: usize = 64;
+
 pub struct Buf {
     buf: Vec<u8>,
     read_pos: usize,
@@ -60,12 +64,16 @@ impl Buf {
         self.resize(new_len);
     }
 
+    /// Makes sure there's at least `MIN_WRITEABLE` bytes to write to.
     pub fn ensure_writeable(&mut self) {
-        if self.write().is_empty() {
-            if self.read_pos > 0 && self.len() / self.read_pos <= 2 {
+        if self.write().len() < MIN_WRITEABLE {
+            if self.read_pos > 0 {
                 self.compact();
             }
-            self.grow();
+            // Grow only if the unread data takes most of the buffer.
+            if self.write().len() < MIN_WRITEABLE || self.write().len() < self.len() / 2 {
+                self.grow();
+            }
         }
     }
 
@@ -78,7 +86,7 @@ impl Buf {
     }
 
     pub fn compact(&mut self) {
-        self.buf.drain(..self.read_pos);
+        self.buf.copy_within(self.read_pos..self.write_pos, 0);
         self.write_pos -= self.read_pos;
         self.read_pos = 0;
     }
@@ -95,10 +103,28 @@ struct Stream {
     buf: Buf,
 }
 
+/// Location of the event's frame start in the decoded byte stream.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Pos {
+    /// Byte offset in the decoded (e.g. decompressed or transcoded to UTF-8) stream.
+    pub offset: u64,
+
+    /// 1-based number of the LF-delimited line.
+    pub line: u64,
+}
+
 pub struct BufDecoder {
     stream: Stream,
     frame_event: Box<frame_event::Decoder>,
     buf: Buf,
+
+    /// Number of decoded bytes consumed by the frame-event decoder so far.
+    consumed: u64,
+
+    /// Number of LF line endings in the consumed bytes.
+    consumed_lines: u64,
+
+    offsets: Vec<usize>,
 }
 
 impl BufDecoder {
@@ -110,6 +136,9 @@ impl BufDecoder {
             },
             frame_event,
             buf: Buf::new(),
+            consumed: 0,
+            consumed_lines: 0,
+            offsets: Vec::new(),
         }
     }
 
@@ -121,6 +150,8 @@ impl BufDecoder {
     pub fn clear(&mut self) {
         self.stream.buf.clear();
         self.buf.clear();
+        self.consumed = 0;
+        self.consumed_lines = 0;
     }
 
     pub fn writeable_buf(&mut self) -> &mut Buf {
@@ -128,15 +159,19 @@ impl BufDecoder {
         &mut self.buf
     }
 
-    pub fn decode(&mut self, out: &mut Vec<Event>) -> Result<usize> {
-        self.decode0(out, false)
+    /// Decodes events into `out`. For every event written to `out` its location is written
+    /// to `pos`.
+    pub fn decode(&mut self, out: &mut Vec<Event>, pos: &mut Vec<Pos>) -> Result<usize> {
+        self.decode0(out, pos, false)
     }
 
-    pub fn flush(&mut self, out: &mut Vec<Event>) -> Result<usize> {
-        self.decode0(out, true)
+    pub fn flush(&mut self, out: &mut Vec<Event>, pos: &mut Vec<Pos>) -> Result<usize> {
+        self.decode0(out, pos, true)
     }
 
-    fn decode0(&mut self, out: &mut Vec<Event>, flush: bool) -> Result<usize> {
+    fn decode0(&mut self, out: &mut Vec<Event>, pos: &mut Vec<Pos>, flush: bool)
+        -> Result<usize>
+    {
         let mut written = 0;
         loop {
             let needs_more_input = if self.buf.read().len() > 0 {
@@ -150,20 +185,133 @@ impl BufDecoder {
                 true
             };
 
-            let r = self.frame_event.decode(self.stream.buf.read(), out)?;
-            self.stream.buf.advance_read_pos(r.read);
+            let r = self.decode_frames(out, pos, false)?;
             written += r.written;
-            if flush {
-                let r = self.frame_event.flush(self.stream.buf.read(), out)?;
-                self.stream.buf.advance_read_pos(r.read);
-                written += r.written;
-                break;
-            }
 
-            if r.written > 0 || needs_more_input {
+            if flush {
+                // Drain all the buffered input through the stream decoder before flushing the
+                // trailing partial frame.
+                if needs_more_input {
+                    written += self.decode_frames(out, pos, true)?.written;
+                    break;
+                }
+            } else if r.written > 0 || needs_more_input {
                 break;
             }
         }
         Ok(written)
     }
-}
\ No newline at end of file
+
+    fn decode_frames(&mut self, out: &mut Vec<Event>, pos: &mut Vec<Pos>, flush: bool)
+        -> Result<frame_event::Decode>
+    {
+        let inp = self.stream.buf.read();
+        self.offsets.clear();
+        let r = if flush {
+            self.frame_event.flush(inp, out, &mut self.offsets)?
+        } else {
+            self.frame_event.decode(inp, out, &mut self.offsets)?
+        };
+        debug_assert_eq!(self.offsets.len(), r.written);
+
+        let mut lines = LineCounter::new(inp);
+        for &offset in &self.offsets {
+            pos.push(Pos {
+                offset: self.consumed + offset as u64,
+                line: self.consumed_lines + lines.count_to(offset) + 1,
+            });
+        }
+        self.consumed += r.read as u64;
+        self.consumed_lines += lines.count_to(r.read);
+
+        self.stream.buf.advance_read_pos(r.read);
+        Ok(r)
+    }
+}
+
+/// Counts LF line endings in prefixes of a buffer reusing the previous count when possible.
+struct LineCounter<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    count: u64,
+}
+
+impl<'a> LineCounter<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self {
+            buf,
+            pos: 0,
+            count: 0,
+        }
+    }
+
+    /// Returns number of LF line endings in `buf[..end]`.
+    pub fn count_to(&mut self, end: usize) -> u64 {
+        if end < self.pos {
+            self.pos = 0;
+            self.count = 0;
+        }
+        self.count += memchr::memchr_iter(b'\n', &self.buf[self.pos..end]).count() as u64;
+        self.pos = end;
+        self.count
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::component::registry;
+
+    fn new() -> BufDecoder {
+        let r = registry();
+        BufDecoder::new(
+            r.stream_decoder("plain").unwrap().new(Default::default()).unwrap().new(),
+            frame_event::composite::factory(
+                r.frame_decoder("delimited").unwrap().new(Default::default()).unwrap(),
+                r.event_decoder("text").unwrap().new(Default::default()).unwrap(),
+                None).new())
+    }
+
+    fn write(dec: &mut BufDecoder, data: &[u8]) {
+        let buf = dec.writeable_buf();
+        buf.write()[..data.len()].copy_from_slice(data);
+        buf.advance_write_pos(data.len());
+    }
+
+    fn messages(events: &[Event]) -> Vec<&str> {
+        events.iter().map(|e| e.fields()["message"].as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn pos() {
+        let mut dec = new();
+        let (ref mut events, ref mut pos) = (Vec::new(), Vec::new());
+
+        write(&mut dec, b"line1\nline2\r\nli");
+        while dec.decode(events, pos).unwrap() > 0 {}
+        write(&mut dec, b"ne3\n\nline5");
+        while dec.decode(events, pos).unwrap() > 0 {}
+        assert_eq!(messages(events), &["line1", "line2", "line3", ""]);
+
+        assert_eq!(dec.flush(events, pos).unwrap(), 1);
+        assert_eq!(messages(events), &["line1", "line2", "line3", "", "line5"]);
+        assert_eq!(&pos[..], &[
+            Pos { offset: 0, line: 1 },
+            Pos { offset: 6, line: 2 },
+            Pos { offset: 13, line: 3 },
+            Pos { offset: 19, line: 4 },
+            Pos { offset: 20, line: 5 },
+        ]);
+    }
+
+    #[test]
+    fn flush_empty() {
+        let mut dec = new();
+        let (ref mut events, ref mut pos) = (Vec::new(), Vec::new());
+
+        write(&mut dec, b"line1\n");
+        assert_eq!(dec.flush(events, pos).unwrap(), 1);
+        assert_eq!(dec.flush(events, pos).unwrap(), 0);
+        assert_eq!(messages(events), &["line1"]);
+    }
+}
@@ -1,5 +1,12 @@
+pub mod access_log;
+pub mod csv;
+pub mod gelf;
+pub mod json;
+pub mod kv;
+pub mod msgpack;
 pub mod text;
 
+use encoding_rs::Encoding;
 use std::sync::Arc;
 
 use crate::error::*;
@@ -17,10 +24,52 @@ pub trait Provider: 'static + super::super::Provider {
 
 pub trait Factory: 'static + Send + Sync {
     fn new(&self) -> Box<Decoder>;
+
+    /// Charset of the input if the decoder expects text. Such input must be transcoded to UTF-8
+    /// with `stream::charset` before splitting it into frames.
+    fn charset(&self) -> Option<&'static Encoding> {
+        None
+    }
 }
 
 pub trait Decoder: 'static + Send {
     fn decode(&mut self, inp: &[u8], out: &mut Vec<Event>) -> Result<usize>;
 
     fn flush(&mut self, out: &mut Vec<Event>) -> Result<usize>;
-}
\ No newline at end of file
+}
+
+/// Makes the event of the `inp` the decoder couldn't parse. Rather than fail the input, decoders
+/// emit such events with the `inp` in the `field` and the `error` message as the value of their
+/// parse failure `tag`, so the events can be told apart and routed further.
+pub fn parse_failure(inp: &[u8], field: &str, tag: &str, error: impl Into<String>) -> Event {
+    let mut event = Event::new();
+    event.fields_mut().insert(field.into(), Value::String(String::from_utf8_lossy(inp).into()));
+    event.tags_mut().insert(tag.into(), Value::String(error.into()));
+    event
+}
+#[cfg(test)]
+pub mod test {
+    //! Helpers shared by the tests of the event decoders.
+
+    use super::*;
+
+    pub fn try_new(provider: &Provider, config: Value) -> Result<Box<Decoder>> {
+        Ok(provider.new(New { config: config.into() })?.new())
+    }
+
+    pub fn new(provider: &Provider, config: Value) -> Box<Decoder> {
+        try_new(provider, config).unwrap()
+    }
+
+    /// Decodes the `inp` checking that the decoder counts the events right.
+    pub fn decode(dec: &mut Box<Decoder>, inp: impl AsRef<[u8]>) -> Vec<Event> {
+        let mut out = Vec::new();
+        let n = dec.decode(inp.as_ref(), &mut out).unwrap();
+        assert_eq!(n, out.len());
+        out
+    }
+
+    pub fn s(s: &str) -> Value {
+        Value::String(s.into())
+    }
+}
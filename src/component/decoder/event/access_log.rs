@@ -0,0 +1,533 @@
+use regex::Regex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::*;
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::event::*;
+use crate::value::*;
+
+pub const NAME: &'static str = "access_log";
+
+/// Tag of the events made with `parse_failure()`.
+pub const PARSE_FAILURE_TAG: &'static str = "access_log_parse_failure";
+
+const FORMAT: &'static str = "format";
+const SYNTAX: &'static str = "syntax";
+const SYNTAX_NGINX: &'static str = "nginx";
+const SYNTAX_APACHE: &'static str = "apache";
+
+const DEFAULT_FIELD: &'static str = "message";
+const TIMESTAMP_FIELD: &'static str = "timestamp";
+const METHOD_FIELD: &'static str = "method";
+const REQUEST_PATH_FIELD: &'static str = "request_path";
+const PROTOCOL_FIELD: &'static str = "protocol";
+
+/// Built-in formats in Apache `LogFormat` syntax.
+const BUILTIN_FORMATS: &[(&str, &str)] = &[
+    ("common", r#"%h %l %u %t "%r" %>s %b"#),
+    ("combined", r#"%h %l %u %t "%r" %>s %b "%{Referer}i" "%{User-agent}i""#),
+];
+const DEFAULT_FORMAT: &'static str = "combined";
+
+/// Unix time of 9999-12-31T23:59:59Z.
+const MAX_TIMESTAMP: u64 = 253402300799;
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::EventDecoder,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Arc<Factory>> {
+        let config = &ctx.config;
+
+        let syntax = if let Some(v) = config.get_opt(SYNTAX)? {
+            match v.as_str()? {
+                SYNTAX_NGINX => Some(Syntax::Nginx),
+                SYNTAX_APACHE => Some(Syntax::Apache),
+                _ => return Err(v.new_error(format!("`{}` must be one of [\"{}\", \"{}\"]",
+                    SYNTAX, SYNTAX_NGINX, SYNTAX_APACHE))),
+            }
+        } else {
+            None
+        };
+
+        let format = if let Some(v) = config.get_opt(FORMAT)? {
+            let s = v.as_str()?;
+            let r = if let Some(&(_, f)) = BUILTIN_FORMATS.iter().find(|&&(n, _)| n == s) {
+                if syntax.is_some() {
+                    return Err(v.new_error(format!("`{}` can't be used with built-in formats",
+                        SYNTAX)));
+                }
+                Format::parse(f, Syntax::Apache)
+            } else if let Some(syntax) = syntax {
+                Format::parse(s, syntax)
+            } else {
+                return Err(v.new_error(format!(
+                    "`{}` must be one of the built-in formats [{}] or `{}` must be specified",
+                    FORMAT,
+                    itertools::join(BUILTIN_FORMATS.iter().map(|(n, _)| format!("\"{}\"", n)),
+                        ", "),
+                    SYNTAX)));
+            };
+            r.map_err(|e| v.new_error(e))?
+        } else {
+            let (_, f) = BUILTIN_FORMATS.iter().find(|&&(n, _)| n == DEFAULT_FORMAT).unwrap();
+            Format::parse(f, Syntax::Apache).unwrap()
+        };
+
+        Ok(Arc::new(FactoryImpl {
+            format: Arc::new(format),
+        }))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Syntax {
+    /// Nginx `log_format`, e.g. `$remote_addr [$time_local]`.
+    Nginx,
+
+    /// Apache `LogFormat`, e.g. `%h %t`.
+    Apache,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum FieldKind {
+    String,
+    Int,
+    Float,
+
+    /// Time in `10/Oct/2000:13:55:36 -0700` format, also parsed into the `timestamp` field.
+    TimeLocal,
+
+    /// Time in ISO 8601 format, also parsed into the `timestamp` field.
+    TimeIso8601,
+
+    /// Request line, also split into the `method`, `request_path` and `protocol` fields.
+    Request,
+}
+
+#[derive(Debug)]
+struct Field {
+    name: String,
+    kind: FieldKind,
+}
+
+impl Field {
+    fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        let kind = match name.as_str() {
+            "status" | "body_bytes_sent" | "bytes_sent" | "bytes_received" | "request_length"
+                | "request_time_us" | "server_port" | "connection" | "pipe_id"
+                => FieldKind::Int,
+            "request_time" | "upstream_response_time" | "upstream_connect_time"
+                | "upstream_header_time" | "msec"
+                => FieldKind::Float,
+            "time_local" => FieldKind::TimeLocal,
+            "time_iso8601" => FieldKind::TimeIso8601,
+            "request" => FieldKind::Request,
+            _ => FieldKind::String,
+        };
+        Self {
+            name,
+            kind,
+        }
+    }
+
+    /// Pattern of the field value.
+    fn pattern(&self) -> &'static str {
+        match self.kind {
+            FieldKind::Int => r"(-|\d+)",
+            FieldKind::TimeLocal => r"(-|[^\]]+?)",
+            _ => r"(.*?)",
+        }
+    }
+}
+
+/// Log line format compiled into a regex. The capture group `i + 1` holds the value of
+/// `fields[i]`.
+#[derive(Debug)]
+struct Format {
+    regex: Regex,
+    fields: Vec<Field>,
+}
+
+impl Format {
+    fn parse(format: &str, syntax: Syntax) -> std::result::Result<Self, String> {
+        let mut regex = "^".to_owned();
+        let mut fields = Vec::new();
+        {
+            let mut add_field = |regex: &mut String, field: Field| {
+                regex.push_str(field.pattern());
+                fields.push(field);
+            };
+            match syntax {
+                Syntax::Nginx => parse_nginx(format, &mut regex, &mut add_field)?,
+                Syntax::Apache => parse_apache(format, &mut regex, &mut add_field)?,
+            }
+        }
+        regex.push('$');
+        if fields.is_empty() {
+            return Err("format has no variables".into());
+        }
+        let regex = Regex::new(&regex).map_err(|e| e.to_string())?;
+        Ok(Self {
+            regex,
+            fields,
+        })
+    }
+
+    fn decode(&self, line: &str) -> Option<Event> {
+        let caps = self.regex.captures(line)?;
+        let mut event = Event::new();
+        let fields = event.fields_mut();
+        for (field, value) in self.fields.iter().zip(caps.iter().skip(1)) {
+            let value = value.unwrap().as_str();
+            if value == "-" {
+                continue;
+            }
+            let v = match field.kind {
+                FieldKind::String => None,
+                FieldKind::Int => value.parse().ok().map(Value::Int),
+                FieldKind::Float => value.parse().ok().map(Value::Float),
+                FieldKind::TimeLocal | FieldKind::TimeIso8601 => {
+                    let t = if field.kind == FieldKind::TimeLocal {
+                        parse_time_local(value)
+                    } else {
+                        parse_time_iso8601(value)
+                    };
+                    if let Some(t) = t {
+                        fields.insert(TIMESTAMP_FIELD.into(),
+                            Value::String(humantime::format_rfc3339(t).to_string()));
+                    }
+                    None
+                }
+                FieldKind::Request => {
+                    let parts: Vec<_> = value.split(' ').collect();
+                    if let &[method, path, protocol] = &parts[..] {
+                        fields.insert(METHOD_FIELD.into(), Value::String(method.into()));
+                        fields.insert(REQUEST_PATH_FIELD.into(), Value::String(path.into()));
+                        fields.insert(PROTOCOL_FIELD.into(), Value::String(protocol.into()));
+                    }
+                    None
+                }
+            };
+            fields.insert(field.name.clone(), v.unwrap_or_else(|| Value::String(value.into())));
+        }
+        Some(event)
+    }
+}
+
+fn push_literal(regex: &mut String, s: &str) {
+    regex.push_str(&regex::escape(s));
+}
+
+fn parse_nginx(format: &str, regex: &mut String, add_field: &mut impl FnMut(&mut String, Field))
+    -> std::result::Result<(), String>
+{
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut s = format;
+    while let Some(i) = s.find('$') {
+        push_literal(regex, &s[..i]);
+        s = &s[i + 1..];
+        let name = if s.starts_with('{') {
+            let end = s.find('}').ok_or("unterminated `${`")?;
+            let name = &s[1..end];
+            s = &s[end + 1..];
+            name
+        } else {
+            let end = s.find(|c| !is_name_char(c)).unwrap_or(s.len());
+            let name = &s[..end];
+            s = &s[end..];
+            name
+        };
+        if name.is_empty() || !name.chars().all(is_name_char) {
+            return Err(format!("invalid variable name `{}`", name));
+        }
+        add_field(regex, Field::new(name));
+    }
+    push_literal(regex, s);
+    Ok(())
+}
+
+fn parse_apache(format: &str, regex: &mut String, add_field: &mut impl FnMut(&mut String, Field))
+    -> std::result::Result<(), String>
+{
+    let header_name = |prefix: &str, name: &str| {
+        format!("{}{}", prefix, name.to_ascii_lowercase().replace('-', "_"))
+    };
+    let mut s = format;
+    while let Some(i) = s.find('%') {
+        push_literal(regex, &s[..i]);
+        s = &s[i + 1..];
+
+        // Skip the modifiers: `<`, `>` and conditions like `!200,304`.
+        let end = s.find(|c: char| !(c == '<' || c == '>' || c == '!' || c == ','
+            || c.is_ascii_digit())).unwrap_or(s.len());
+        s = &s[end..];
+
+        let arg = if s.starts_with('{') {
+            let end = s.find('}').ok_or("unterminated `%{`")?;
+            let arg = &s[1..end];
+            s = &s[end + 1..];
+            Some(arg)
+        } else {
+            None
+        };
+
+        let directive = s.chars().next().ok_or("unterminated `%`")?;
+        s = &s[directive.len_utf8()..];
+
+        let name = match (directive, arg) {
+            ('%', None) => {
+                push_literal(regex, "%");
+                continue;
+            }
+            ('t', None) => {
+                push_literal(regex, "[");
+                add_field(regex, Field::new("time_local"));
+                push_literal(regex, "]");
+                continue;
+            }
+            ('t', Some(_)) => "time".into(),
+            ('i', Some(arg)) => header_name("http_", arg),
+            ('o', Some(arg)) => header_name("sent_http_", arg),
+            ('C', Some(arg)) => header_name("cookie_", arg),
+            ('e', Some(arg)) => header_name("env_", arg),
+            ('n', Some(arg)) => header_name("note_", arg),
+            (c, _) => match c {
+                'a' | 'h' => "remote_addr",
+                'A' => "server_addr",
+                'b' | 'B' => "body_bytes_sent",
+                'D' => "request_time_us",
+                'f' => "request_filename",
+                'H' => "protocol",
+                'I' => "bytes_received",
+                'k' => "keepalive_requests",
+                'l' => "remote_logname",
+                'L' => "log_id",
+                'm' => "method",
+                'O' => "bytes_sent",
+                'p' => "server_port",
+                'P' => "pid",
+                'q' => "query_string",
+                'r' => "request",
+                'R' => "handler",
+                's' => "status",
+                'T' => "request_time",
+                'u' => "remote_user",
+                'U' => REQUEST_PATH_FIELD,
+                'v' | 'V' => "server_name",
+                'X' => "connection_status",
+                _ => return Err(format!("unsupported directive `%{}`", c)),
+            }.into(),
+        };
+        add_field(regex, Field::new(name));
+    }
+    push_literal(regex, s);
+    Ok(())
+}
+
+fn month(s: &str) -> Option<u32> {
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep",
+        "Oct", "Nov", "Dec"];
+    MONTHS.iter().position(|&m| m == s).map(|i| i as u32 + 1)
+}
+
+/// Parses UTC offset in `+hhmm` or `+hh:mm` format into seconds.
+fn parse_offset(s: &str) -> Option<i64> {
+    let sign = match s.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let s = s[1..].replace(':', "");
+    if s.len() != 4 {
+        return None;
+    }
+    let h: i64 = s[..2].parse().ok()?;
+    let m: i64 = s[2..].parse().ok()?;
+    Some(sign * (h * 3600 + m * 60))
+}
+
+/// Converts local time in `YYYY-MM-DDTHH:MM:SS` format with UTC offset to `SystemTime`.
+/// Returns `None` if the time is outside of the range representable in RFC 3339: from
+/// 1970-01-01T00:00:00Z to 9999-12-31T23:59:59Z.
+fn to_system_time(local: &str, offset: i64) -> Option<SystemTime> {
+    let t = humantime::parse_rfc3339(&format!("{}Z", local)).ok()?;
+    let offset_dur = Duration::from_secs(offset.abs() as u64);
+    let t = if offset >= 0 {
+        t.checked_sub(offset_dur)?
+    } else {
+        t.checked_add(offset_dur)?
+    };
+    if t < UNIX_EPOCH || t > UNIX_EPOCH + Duration::from_secs(MAX_TIMESTAMP) {
+        return None;
+    }
+    Some(t)
+}
+
+/// Parses `10/Oct/2000:13:55:36 -0700`.
+fn parse_time_local(s: &str) -> Option<SystemTime> {
+    let mut parts = s.splitn(2, ' ');
+    let dt = parts.next()?;
+    let offset = parse_offset(parts.next()?)?;
+    if dt.len() != 20 {
+        return None;
+    }
+    let local = format!("{}-{:02}-{}T{}",
+        dt.get(7..11)?, month(dt.get(3..6)?)?, dt.get(..2)?, dt.get(12..)?);
+    to_system_time(&local, offset)
+}
+
+/// Parses `2000-10-10T13:55:36-07:00`.
+fn parse_time_iso8601(s: &str) -> Option<SystemTime> {
+    let local = s.get(..19)?;
+    let offset = match s.get(19..)? {
+        "Z" => 0,
+        offset => parse_offset(offset)?,
+    };
+    to_system_time(local, offset)
+}
+
+struct FactoryImpl {
+    format: Arc<Format>,
+}
+
+impl Factory for FactoryImpl {
+    fn new(&self) -> Box<Decoder> {
+        Box::new(DecoderImpl {
+            format: self.format.clone(),
+        })
+    }
+}
+
+struct DecoderImpl {
+    format: Arc<Format>,
+}
+
+impl Decoder for DecoderImpl {
+    fn decode(&mut self, inp: &[u8], out: &mut Vec<Event>) -> Result<usize> {
+        let line = String::from_utf8_lossy(inp);
+        if line.trim().is_empty() {
+            return Ok(0);
+        }
+        let event = self.format.decode(&line).unwrap_or_else(|| parse_failure(inp,
+            DEFAULT_FIELD, PARSE_FAILURE_TAG, "line doesn't match the format"));
+        out.push(event);
+        Ok(1)
+    }
+
+    fn flush(&mut self, _out: &mut Vec<Event>) -> Result<usize> {
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::component::decoder::event::test::*;
+
+    #[test]
+    fn combined() {
+        let mut dec = new(&ProviderImpl, value!{{}});
+        let e = &decode(&mut dec, concat!(r#"127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "#,
+            r#""GET /a.gif?x=1 HTTP/1.0" 200 2326 "http://example.com/" "Mozilla/4.08 "x"""#))[0];
+        assert!(e.tags().is_empty());
+        let f = e.fields();
+        assert_eq!(f.len(), 12);
+        assert_eq!(f["remote_addr"], s("127.0.0.1"));
+        assert_eq!(f["remote_user"], s("frank"));
+        assert_eq!(f["time_local"], s("10/Oct/2000:13:55:36 -0700"));
+        assert_eq!(f[TIMESTAMP_FIELD], s("2000-10-10T20:55:36Z"));
+        assert_eq!(f["request"], s("GET /a.gif?x=1 HTTP/1.0"));
+        assert_eq!(f[METHOD_FIELD], s("GET"));
+        assert_eq!(f[REQUEST_PATH_FIELD], s("/a.gif?x=1"));
+        assert_eq!(f[PROTOCOL_FIELD], s("HTTP/1.0"));
+        assert_eq!(f["status"], Value::Int(200));
+        assert_eq!(f["body_bytes_sent"], Value::Int(2326));
+        assert_eq!(f["http_referer"], s("http://example.com/"));
+        assert_eq!(f["http_user_agent"], s("Mozilla/4.08 \"x\""));
+    }
+
+    #[test]
+    fn common_missing_values() {
+        let mut dec = new(&ProviderImpl, value!{{ FORMAT => "common" }});
+        let e = &decode(&mut dec, r#"::1 - - [01/Jan/2019:00:00:00 +0000] "-" 408 -"#)[0];
+        assert_eq!(e.fields().len(), 4);
+        assert_eq!(e.fields()["remote_addr"], s("::1"));
+        assert_eq!(e.fields()[TIMESTAMP_FIELD], s("2019-01-01T00:00:00Z"));
+        assert_eq!(e.fields()["status"], Value::Int(408));
+    }
+
+    #[test]
+    fn nginx() {
+        let mut dec = new(&ProviderImpl, value!{{
+            SYNTAX => SYNTAX_NGINX,
+            FORMAT => "$remote_addr [$time_iso8601] \"$request\" $status ${request_time}s",
+        }});
+        let e = &decode(&mut dec,
+            r#"10.0.0.1 [2019-03-01T10:00:00+02:00] "POST /api HTTP/2.0" 201 0.015s"#)[0];
+        assert!(e.tags().is_empty());
+        assert_eq!(e.fields()[TIMESTAMP_FIELD], s("2019-03-01T08:00:00Z"));
+        assert_eq!(e.fields()[METHOD_FIELD], s("POST"));
+        assert_eq!(e.fields()["status"], Value::Int(201));
+        assert_eq!(e.fields()["request_time"], Value::Float(0.015));
+    }
+
+    #[test]
+    fn apache() {
+        let mut dec = new(&ProviderImpl, value!{{
+            SYNTAX => SYNTAX_APACHE,
+            FORMAT => "%v:%p %h %D %{X-Request-Id}i 100%%",
+        }});
+        let e = &decode(&mut dec, "example.com:443 10.0.0.1 1500 abc 100%")[0];
+        assert_eq!(e.fields()["server_name"], s("example.com"));
+        assert_eq!(e.fields()["server_port"], Value::Int(443));
+        assert_eq!(e.fields()["request_time_us"], Value::Int(1500));
+        assert_eq!(e.fields()["http_x_request_id"], s("abc"));
+    }
+
+    #[test]
+    fn time_out_of_range() {
+        let mut dec = new(&ProviderImpl, value!{{ SYNTAX => SYNTAX_APACHE, FORMAT => "%t" }});
+        for &(inp, exp) in &[
+            ("[01/Jan/1970:01:00:00 +0100]", Some("1970-01-01T00:00:00Z")),
+            ("[01/Jan/1970:00:30:00 +0100]", None),
+            ("[31/Dec/9999:23:59:59 +0000]", Some("9999-12-31T23:59:59Z")),
+            ("[31/Dec/9999:23:30:00 -0100]", None),
+        ] {
+            let e = &decode(&mut dec, inp)[0];
+            assert!(e.tags().is_empty());
+            assert_eq!(e.fields().get(TIMESTAMP_FIELD), exp.map(s).as_ref(), "{}", inp);
+        }
+    }
+
+    #[test]
+    fn no_match() {
+        let mut dec = new(&ProviderImpl, value!{{}});
+        let e = &decode(&mut dec, "garbage")[0];
+        assert_eq!(e.fields()[DEFAULT_FIELD], s("garbage"));
+        assert!(e.tags().contains_key(PARSE_FAILURE_TAG));
+    }
+
+    #[test]
+    fn invalid_config() {
+        let new = |config: Value| try_new(&ProviderImpl, config);
+        assert!(new(value!{{ FORMAT => "%h" }}).is_err());
+        assert!(new(value!{{ FORMAT => "common", SYNTAX => SYNTAX_NGINX }}).is_err());
+        assert!(new(value!{{ FORMAT => "%h %Z", SYNTAX => SYNTAX_APACHE }}).is_err());
+        assert!(new(value!{{ FORMAT => "${a", SYNTAX => SYNTAX_NGINX }}).is_err());
+        assert!(new(value!{{ FORMAT => "static", SYNTAX => SYNTAX_NGINX }}).is_err());
+    }
+}
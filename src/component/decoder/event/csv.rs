@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+
+use super::*;
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::event::*;
+use crate::util::csv::Dialect;
+use crate::value::*;
+
+pub const NAME: &'static str = "csv";
+
+/// Tag of the events made with `parse_failure()`.
+pub const PARSE_FAILURE_TAG: &'static str = "csv_parse_failure";
+
+const COLUMNS: &'static str = "columns";
+const HEADER: &'static str = "header";
+const CONVERT: &'static str = "convert";
+const DEFAULT_FIELD: &'static str = "message";
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::EventDecoder,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Arc<Factory>> {
+        let config = &ctx.config;
+
+        let dialect = Dialect::parse(config)?;
+
+        let columns = if let Some(v) = config.get_opt(COLUMNS)? {
+            let mut columns = Vec::new();
+            for v in v.as_list()? {
+                columns.push(v.as_str()?.to_owned());
+            }
+            columns
+        } else {
+            Vec::new()
+        };
+
+        let header = config.get_opt_bool(HEADER)?.cloned().unwrap_or(false);
+
+        let mut convert = HashMap::new();
+        if let Some(v) = config.get_opt(CONVERT)? {
+            for (column, ty) in v.as_map()? {
+                let ty = match ty.as_str()? {
+                    "int" => Type::Int,
+                    "float" => Type::Float,
+                    "bool" => Type::Bool,
+                    "string" => Type::String,
+                    _ => return Err(ty.new_error(
+                        "expected one of [\"int\", \"float\", \"bool\", \"string\"]")),
+                };
+                convert.insert(column.clone(), ty);
+            }
+        }
+
+        Ok(Arc::new(FactoryImpl {
+            config: Arc::new(Config {
+                dialect,
+                columns,
+                header,
+                convert,
+            }),
+        }))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Type {
+    Int,
+    Float,
+    Bool,
+    String,
+}
+
+impl Type {
+    fn convert(self, s: String) -> std::result::Result<Value, String> {
+        Ok(match self {
+            Type::Int => Value::Int(s.trim().parse()
+                .map_err(|_| format!("invalid int value: {:?}", s))?),
+            Type::Float => Value::Float(s.trim().parse()
+                .map_err(|_| format!("invalid float value: {:?}", s))?),
+            Type::Bool => Value::Bool(match s.trim() {
+                v if v.eq_ignore_ascii_case("true") => true,
+                v if v.eq_ignore_ascii_case("false") => false,
+                _ => return Err(format!("invalid bool value: {:?}", s)),
+            }),
+            Type::String => Value::String(s),
+        })
+    }
+}
+
+struct Config {
+    dialect: Dialect,
+
+    /// Column names. Columns without a name are named `columnN` where `N` is the 1-based
+    /// column number.
+    columns: Vec<String>,
+
+    /// Whether the first record holds the column names. The names override `columns`.
+    header: bool,
+
+    /// Column type conversions. Columns are strings by default.
+    convert: HashMap<String, Type>,
+}
+
+struct FactoryImpl {
+    config: Arc<Config>,
+}
+
+impl Factory for FactoryImpl {
+    fn new(&self) -> Box<Decoder> {
+        Box::new(DecoderImpl {
+            config: self.config.clone(),
+            header: None,
+            fields: Vec::new(),
+        })
+    }
+}
+
+struct DecoderImpl {
+    config: Arc<Config>,
+
+    /// Column names read from the header record.
+    header: Option<Vec<String>>,
+
+    fields: Vec<Vec<u8>>,
+}
+
+impl DecoderImpl {
+    fn column_name(&self, i: usize) -> String {
+        self.header.as_ref().unwrap_or(&self.config.columns).get(i).cloned()
+            .unwrap_or_else(|| format!("column{}", i + 1))
+    }
+
+    fn parse(&mut self, inp: &[u8]) -> std::result::Result<Event, String> {
+        self.fields.clear();
+        if !self.config.dialect.split(inp, &mut self.fields) {
+            return Err("unterminated quoted field".into());
+        }
+        let mut event = Event::new();
+        for (i, field) in self.fields.iter().enumerate() {
+            let name = self.column_name(i);
+            let value = String::from_utf8_lossy(field).into_owned();
+            let value = match self.config.convert.get(&name) {
+                // Empty values can't be converted and are omitted.
+                Some(_) if value.is_empty() => continue,
+                Some(ty) => ty.convert(value)?,
+                None => Value::String(value),
+            };
+            event.fields_mut().insert(name, value);
+        }
+        Ok(event)
+    }
+}
+
+impl Decoder for DecoderImpl {
+    fn decode(&mut self, inp: &[u8], out: &mut Vec<Event>) -> Result<usize> {
+        if inp.is_empty() {
+            return Ok(0);
+        }
+
+        if self.config.header && self.header.is_none() {
+            self.fields.clear();
+            self.config.dialect.split(inp, &mut self.fields);
+            self.header = Some(self.fields.drain(..)
+                .map(|f| String::from_utf8_lossy(&f).into_owned())
+                .collect());
+            return Ok(0);
+        }
+
+        let event = match self.parse(inp) {
+            Ok(event) => event,
+            Err(e) => parse_failure(inp, DEFAULT_FIELD, PARSE_FAILURE_TAG, e),
+        };
+        out.push(event);
+        Ok(1)
+    }
+
+    fn flush(&mut self, _out: &mut Vec<Event>) -> Result<usize> {
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::component::decoder::event::test::*;
+
+    #[test]
+    fn columns() {
+        let mut dec = new(&ProviderImpl, value!{{ COLUMNS => ["a", "b"] }});
+        let e = &decode(&mut dec, "1,\"2,3\",4")[0];
+        assert_eq!(e.fields().len(), 3);
+        assert_eq!(e.fields()["a"], s("1"));
+        assert_eq!(e.fields()["b"], s("2,3"));
+        assert_eq!(e.fields()["column3"], s("4"));
+    }
+
+    #[test]
+    fn header() {
+        let mut dec = new(&ProviderImpl, value!{{
+            HEADER => true,
+            "delimiter" => "\t",
+            CONVERT => {
+                "i" => "int",
+                "f" => "float",
+                "b" => "bool"
+            }
+        }});
+        assert!(decode(&mut dec, "i\tf\tb\ts").is_empty());
+
+        let e = &decode(&mut dec, "1\t1.5\tTrue\tx")[0];
+        assert!(e.tags().is_empty());
+        assert_eq!(e.fields()["i"], Value::Int(1));
+        assert_eq!(e.fields()["f"], Value::Float(1.5));
+        assert_eq!(e.fields()["b"], Value::Bool(true));
+        assert_eq!(e.fields()["s"], s("x"));
+
+        let e = &decode(&mut dec, "\t\t\t")[0];
+        assert_eq!(e.fields().len(), 1);
+        assert_eq!(e.fields()["s"], s(""));
+    }
+
+    #[test]
+    fn parse_failure() {
+        let mut dec = new(&ProviderImpl, value!{{ CONVERT => { "column1" => "int" } }});
+        for inp in &["x,y", "1,\"y"] {
+            let e = &decode(&mut dec, inp)[0];
+            assert_eq!(e.fields().len(), 1);
+            assert_eq!(e.fields()[DEFAULT_FIELD], s(inp));
+            assert!(e.tags().contains_key(PARSE_FAILURE_TAG));
+        }
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert!(try_new(&ProviderImpl, value!{{ "quote" => "''" }}).is_err());
+        assert!(try_new(&ProviderImpl, value!{{ CONVERT => { "a" => "date" } }}).is_err());
+    }
+}
@@ -0,0 +1,372 @@
+use flate2::read::{GzDecoder, ZlibDecoder};
+use log::*;
+use serde_json as json;
+use std::collections::HashMap;
+use std::io::Read;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use super::*;
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::event::*;
+use crate::util::json::from_json;
+use crate::value::*;
+
+pub const NAME: &'static str = "gelf";
+
+/// Tag of the events made with `parse_failure()`.
+pub const PARSE_FAILURE_TAG: &'static str = "gelf_parse_failure";
+
+const CHUNK_TIMEOUT: &'static str = "chunk_timeout";
+const MAX_MESSAGE_LENGTH: &'static str = "max_message_length";
+
+const DEFAULT_CHUNK_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_MAX_MESSAGE_LENGTH: usize = 8 * 1024 * 1024;
+
+const DEFAULT_FIELD: &'static str = "message";
+const TIMESTAMP_FIELD: &'static str = "timestamp";
+
+/// Unix time of 9999-12-31T23:59:59Z.
+const MAX_TIMESTAMP: u64 = 253402300799;
+
+const CHUNK_MAGIC: &[u8] = b"\x1e\x0f";
+const CHUNK_HEADER_LEN: usize = 12;
+const MAX_CHUNKS: usize = 128;
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::EventDecoder,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Arc<Factory>> {
+        let config = &ctx.config;
+
+        let chunk_timeout = config.get_opt_duration(CHUNK_TIMEOUT)?
+            .unwrap_or(DEFAULT_CHUNK_TIMEOUT);
+
+        let max_message_length = if let Some(v) = config.get_opt(MAX_MESSAGE_LENGTH)? {
+            let r = *v.as_int()?;
+            if r <= 0 {
+                return Err(v.new_error(format!("`{}` must be positive", MAX_MESSAGE_LENGTH)));
+            }
+            r as usize
+        } else {
+            DEFAULT_MAX_MESSAGE_LENGTH
+        };
+
+        Ok(Arc::new(FactoryImpl {
+            chunk_timeout,
+            max_message_length,
+        }))
+    }
+}
+
+struct FactoryImpl {
+    /// Max time to wait for all chunks of a chunked message to arrive.
+    chunk_timeout: Duration,
+
+    /// Max length of a decompressed or reassembled message.
+    max_message_length: usize,
+}
+
+impl Factory for FactoryImpl {
+    fn new(&self) -> Box<Decoder> {
+        Box::new(DecoderImpl {
+            chunk_timeout: self.chunk_timeout,
+            max_message_length: self.max_message_length,
+            chunked: HashMap::new(),
+        })
+    }
+}
+
+/// Chunked message being reassembled.
+struct Chunked {
+    chunks: Vec<Option<Vec<u8>>>,
+    received: usize,
+    len: usize,
+    started: Instant,
+}
+
+/// Decodes GELF messages. Each frame must hold a single message or a message chunk, optionally
+/// zlib or gzip compressed.
+struct DecoderImpl {
+    chunk_timeout: Duration,
+    max_message_length: usize,
+
+    /// Chunked messages being reassembled by message ID.
+    chunked: HashMap<[u8; 8], Chunked>,
+}
+
+impl DecoderImpl {
+    /// Adds the chunk and returns the reassembled message if all its chunks have arrived.
+    fn add_chunk(&mut self, chunk: &[u8]) -> std::result::Result<Option<Vec<u8>>, String> {
+        let now = Instant::now();
+        let chunk_timeout = self.chunk_timeout;
+        self.chunked.retain(|id, c| {
+            let expired = now.duration_since(c.started) >= chunk_timeout;
+            if expired {
+                debug!("dropping incomplete chunked GELF message {:x?}: got {} of {} chunks",
+                    id, c.received, c.chunks.len());
+            }
+            !expired
+        });
+
+        if chunk.len() < CHUNK_HEADER_LEN {
+            return Err("chunk is too short".into());
+        }
+        let mut id = [0; 8];
+        id.copy_from_slice(&chunk[2..10]);
+        let seq = chunk[10] as usize;
+        let count = chunk[11] as usize;
+        if count == 0 || count > MAX_CHUNKS || seq >= count {
+            return Err(format!("invalid chunk sequence number {} of {}", seq, count));
+        }
+        let data = &chunk[CHUNK_HEADER_LEN..];
+
+        let c = self.chunked.entry(id).or_insert_with(|| Chunked {
+            chunks: vec![None; count],
+            received: 0,
+            len: 0,
+            started: now,
+        });
+        if c.chunks.len() != count {
+            self.chunked.remove(&id);
+            return Err("chunk count mismatch".into());
+        }
+        if c.chunks[seq].is_none() {
+            c.len += data.len();
+            if c.len > self.max_message_length {
+                self.chunked.remove(&id);
+                return Err(format!("message exceeds the max length {}",
+                    self.max_message_length));
+            }
+            c.chunks[seq] = Some(data.to_vec());
+            c.received += 1;
+        }
+        if c.received < count {
+            return Ok(None);
+        }
+
+        let c = self.chunked.remove(&id).unwrap();
+        let mut r = Vec::with_capacity(c.len);
+        for chunk in c.chunks {
+            r.extend_from_slice(&chunk.unwrap());
+        }
+        Ok(Some(r))
+    }
+
+    fn decompress(&self, inp: &[u8]) -> std::result::Result<Option<Vec<u8>>, String> {
+        let mut r = Vec::new();
+        let max = self.max_message_length as u64 + 1;
+        let read = if inp.starts_with(b"\x1f\x8b") {
+            GzDecoder::new(inp).take(max).read_to_end(&mut r)
+        } else if inp.first().map(|&b| b & 0x0f == 8 && b >> 4 <= 7).unwrap_or(false)
+            && inp.len() >= 2 && ((inp[0] as u16) << 8 | inp[1] as u16) % 31 == 0
+        {
+            // zlib header: CM = 8, CINFO <= 7 and the header checksum.
+            ZlibDecoder::new(inp).take(max).read_to_end(&mut r)
+        } else {
+            return Ok(None);
+        };
+        read.map_err(|e| format!("couldn't decompress message: {}", e))?;
+        if r.len() > self.max_message_length {
+            return Err(format!("message exceeds the max length {}", self.max_message_length));
+        }
+        Ok(Some(r))
+    }
+
+    fn parse(&self, inp: &[u8]) -> std::result::Result<Event, String> {
+        let obj = match json::from_slice(inp).map_err(|e| e.to_string())? {
+            json::Value::Object(v) => v,
+            _ => return Err("JSON object expected".into()),
+        };
+        let mut event = Event::new();
+        let fields = event.fields_mut();
+        for (k, v) in obj {
+            let v = if let Some(v) = from_json(v) {
+                v
+            } else {
+                continue;
+            };
+            let (k, v) = match k.as_str() {
+                "short_message" => (DEFAULT_FIELD.to_owned(), v),
+                "timestamp" => (TIMESTAMP_FIELD.to_owned(), match v {
+                    Value::Int(t) => format_timestamp(t as f64).unwrap_or(Value::Int(t)),
+                    Value::Float(t) => format_timestamp(t).unwrap_or(Value::Float(t)),
+                    v => v,
+                }),
+                _ if k.starts_with('_') => (k[1..].to_owned(), v),
+                _ => (k, v),
+            };
+            fields.insert(k, v);
+        }
+        Ok(event)
+    }
+
+    fn decode_message(&mut self, inp: &[u8]) -> std::result::Result<Option<Event>, String> {
+        let chunked;
+        let inp = if inp.starts_with(CHUNK_MAGIC) {
+            if let Some(v) = self.add_chunk(inp)? {
+                chunked = v;
+                &chunked[..]
+            } else {
+                return Ok(None);
+            }
+        } else {
+            inp
+        };
+        let decompressed = self.decompress(inp)?;
+        let inp = decompressed.as_ref().map(|v| &v[..]).unwrap_or(inp);
+        self.parse(inp).map(Some)
+    }
+}
+
+/// Formats the Unix `t` in seconds as RFC 3339. Returns `None` if `t` is outside of the range
+/// representable in RFC 3339: from 1970-01-01T00:00:00Z to 9999-12-31T23:59:59Z.
+fn format_timestamp(t: f64) -> Option<Value> {
+    let micros = (t * 1e6).round();
+    if !(micros >= 0.0 && micros < (MAX_TIMESTAMP + 1) as f64 * 1e6) {
+        return None;
+    }
+    let t = UNIX_EPOCH.checked_add(Duration::from_micros(micros as u64))?;
+    Some(Value::String(humantime::format_rfc3339_nanos(t).to_string()))
+}
+
+impl Decoder for DecoderImpl {
+    fn decode(&mut self, inp: &[u8], out: &mut Vec<Event>) -> Result<usize> {
+        if inp.is_empty() {
+            return Ok(0);
+        }
+        let event = match self.decode_message(inp) {
+            Ok(Some(event)) => event,
+            Ok(None) => return Ok(0),
+            Err(e) => parse_failure(inp, DEFAULT_FIELD, PARSE_FAILURE_TAG, e),
+        };
+        out.push(event);
+        Ok(1)
+    }
+
+    fn flush(&mut self, _out: &mut Vec<Event>) -> Result<usize> {
+        // Incomplete chunked messages can't be decoded.
+        self.chunked.clear();
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use flate2::Compression;
+    use flate2::write::{GzEncoder, ZlibEncoder};
+    use std::io::Write;
+    use std::thread;
+
+    use super::*;
+    use crate::component::decoder::event::test::*;
+
+    const MSG: &[u8] = br#"{"version": "1.1", "host": "h", "short_message": "hi",
+        "timestamp": 1385053862.3072, "level": 1, "_user_id": 9001, "_x": null}"#;
+
+    fn check(e: &Event) {
+        assert!(e.tags().is_empty(), "{:?}", e);
+        let f = e.fields();
+        assert_eq!(f.len(), 6);
+        assert_eq!(f["version"], Value::String("1.1".into()));
+        assert_eq!(f["host"], Value::String("h".into()));
+        assert_eq!(f[DEFAULT_FIELD], Value::String("hi".into()));
+        assert_eq!(f[TIMESTAMP_FIELD], Value::String("2013-11-21T17:11:02.307200000Z".into()));
+        assert_eq!(f["level"], Value::Int(1));
+        assert_eq!(f["user_id"], Value::Int(9001));
+    }
+
+    fn chunk(id: u8, seq: u8, count: u8, data: &[u8]) -> Vec<u8> {
+        let mut r = CHUNK_MAGIC.to_vec();
+        r.extend_from_slice(&[id; 8]);
+        r.push(seq);
+        r.push(count);
+        r.extend_from_slice(data);
+        r
+    }
+
+    #[test]
+    fn plain() {
+        let mut dec = new(&ProviderImpl, value!{{}});
+        check(&decode(&mut dec, MSG)[0]);
+    }
+
+    #[test]
+    fn compressed() {
+        let mut dec = new(&ProviderImpl, value!{{}});
+
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(MSG).unwrap();
+        check(&decode(&mut dec, &gz.finish().unwrap())[0]);
+
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(MSG).unwrap();
+        check(&decode(&mut dec, &zlib.finish().unwrap())[0]);
+    }
+
+    #[test]
+    fn chunked() {
+        let mut dec = new(&ProviderImpl, value!{{}});
+
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(MSG).unwrap();
+        let msg = zlib.finish().unwrap();
+        let (a, b) = msg.split_at(msg.len() / 2);
+
+        assert!(decode(&mut dec, &chunk(1, 1, 2, b)).is_empty());
+        assert!(decode(&mut dec, &chunk(2, 0, 2, &MSG[..10])).is_empty());
+        assert!(decode(&mut dec, &chunk(1, 1, 2, b)).is_empty());
+        check(&decode(&mut dec, &chunk(1, 0, 2, a))[0]);
+    }
+
+    #[test]
+    fn chunk_timeout() {
+        let mut dec = new(&ProviderImpl, value!{{ CHUNK_TIMEOUT => "10ms" }});
+
+        let (a, b) = MSG.split_at(10);
+        assert!(decode(&mut dec, &chunk(1, 0, 2, a)).is_empty());
+        thread::sleep(Duration::from_millis(20));
+        assert!(decode(&mut dec, &chunk(1, 1, 2, b)).is_empty());
+        check(&decode(&mut dec, &chunk(1, 0, 2, a))[0]);
+    }
+
+    #[test]
+    fn invalid() {
+        let mut dec = new(&ProviderImpl, value!{{ MAX_MESSAGE_LENGTH => 10 }});
+        for inp in &[&b"{"[..], &chunk(1, 2, 2, b"")[..], &chunk(1, 0, 2, &[b'a'; 11])[..]] {
+            let e = &decode(&mut dec, inp)[0];
+            assert!(e.tags().contains_key(PARSE_FAILURE_TAG), "{:?}", e);
+        }
+    }
+
+    #[test]
+    fn timestamp_out_of_range() {
+        let mut dec = new(&ProviderImpl, value!{{}});
+        for (inp, exp) in vec![
+            ("0", Value::String("1970-01-01T00:00:00.000000000Z".into())),
+            ("253402300799", Value::String("9999-12-31T23:59:59.000000000Z".into())),
+            ("253402300799.9999999", Value::Float(253402300799.9999999)),
+            ("1385053862307", Value::Int(1385053862307)),
+            ("-1", Value::Int(-1)),
+            ("-0.5", Value::Float(-0.5)),
+            ("1e300", Value::Float(1e300)),
+        ] {
+            let msg = format!(r#"{{"short_message": "hi", "timestamp": {}}}"#, inp);
+            let e = &decode(&mut dec, msg.as_bytes())[0];
+            assert!(e.tags().is_empty(), "{:?}", e);
+            assert_eq!(e.fields()[TIMESTAMP_FIELD], exp, "{}", inp);
+        }
+    }
+}
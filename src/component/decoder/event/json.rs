@@ -0,0 +1,158 @@
+use serde_json as json;
+
+use super::*;
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::event::*;
+use crate::util::json::from_json;
+
+pub const NAME: &'static str = "json";
+
+/// Tag of the events made with `parse_failure()`.
+pub const PARSE_FAILURE_TAG: &'static str = "json_parse_failure";
+
+const TARGET: &'static str = "target";
+const DEFAULT_FIELD: &'static str = "message";
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::EventDecoder,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Arc<Factory>> {
+        let target = ctx.config.get_opt_str(TARGET)?.map(|s| s.to_owned());
+
+        Ok(Arc::new(FactoryImpl {
+            target,
+        }))
+    }
+}
+
+struct FactoryImpl {
+    /// Field to put the parsed value to. If not set the parsed object fields become the event
+    /// fields.
+    target: Option<String>,
+}
+
+impl Factory for FactoryImpl {
+    fn new(&self) -> Box<Decoder> {
+        Box::new(DecoderImpl {
+            target: self.target.clone(),
+        })
+    }
+}
+
+struct DecoderImpl {
+    target: Option<String>,
+}
+
+impl DecoderImpl {
+    fn parse(&self, inp: &[u8]) -> std::result::Result<Event, String> {
+        let value = json::from_slice(inp).map_err(|e| e.to_string())?;
+        let mut event = Event::new();
+        if let Some(target) = &self.target {
+            if let Some(value) = from_json(value) {
+                event.fields_mut().insert(target.clone(), value);
+            }
+        } else {
+            match value {
+                json::Value::Object(obj) => {
+                    event.fields_mut().extend(obj.into_iter()
+                        .filter_map(|(k, v)| from_json(v).map(|v| (k, v))));
+                }
+                _ => return Err("JSON object expected".into()),
+            }
+        }
+        Ok(event)
+    }
+}
+
+impl Decoder for DecoderImpl {
+    fn decode(&mut self, inp: &[u8], out: &mut Vec<Event>) -> Result<usize> {
+        if inp.iter().all(|b| b.is_ascii_whitespace()) {
+            return Ok(0);
+        }
+        let event = match self.parse(inp) {
+            Ok(event) => event,
+            Err(e) => parse_failure(inp, DEFAULT_FIELD, PARSE_FAILURE_TAG, e),
+        };
+        out.push(event);
+        Ok(1)
+    }
+
+    fn flush(&mut self, _out: &mut Vec<Event>) -> Result<usize> {
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::component::decoder::event::test::*;
+
+    #[test]
+    fn object() {
+        let mut dec = new(&ProviderImpl, value!{{}});
+        let events = decode(&mut dec,
+            r#"{"s": "str", "i": -1, "f": 1.5, "b": true, "n": null,
+                "l": [1, null, {"a": 2}], "m": {"a": {"b": 3}}}"#);
+        assert_eq!(events.len(), 1);
+        let e = &events[0];
+        assert!(e.tags().is_empty());
+        assert_eq!(e.fields().len(), 6);
+        assert_eq!(e.fields()["s"], s("str"));
+        assert_eq!(e.fields()["i"], Value::Int(-1));
+        assert_eq!(e.fields()["f"], Value::Float(1.5));
+        assert_eq!(e.fields()["b"], Value::Bool(true));
+        assert_eq!(e.fields()["l"], value!([1, {"a" => 2}]));
+        assert_eq!(e.fields()["m"], value!({"a" => {"b" => 3}}));
+    }
+
+    #[test]
+    fn big_int() {
+        let mut dec = new(&ProviderImpl, value!{{}});
+        let events = decode(&mut dec, r#"{"u": 18446744073709551615, "f": 1.0}"#);
+        assert_eq!(events[0].fields()["u"], Value::Float(18446744073709551615.0));
+        assert_eq!(events[0].fields()["f"], Value::Float(1.0));
+    }
+
+    #[test]
+    fn target() {
+        let mut dec = new(&ProviderImpl, value!{{ TARGET => "json" }});
+        let events = decode(&mut dec, r#"{"a": 1}"#);
+        assert_eq!(events[0].fields().len(), 1);
+        assert_eq!(events[0].fields()["json"], value!({"a" => 1}));
+
+        let events = decode(&mut dec, "[1, 2]");
+        assert_eq!(events[0].fields()["json"], value!([1, 2]));
+    }
+
+    #[test]
+    fn invalid() {
+        let mut dec = new(&ProviderImpl, value!{{}});
+        for inp in &[r#"{"a": 1"#, "[1, 2]", "text"] {
+            let events = decode(&mut dec, inp);
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].fields().len(), 1);
+            assert_eq!(events[0].fields()[DEFAULT_FIELD], s(inp));
+            assert!(events[0].tags().contains_key(PARSE_FAILURE_TAG));
+        }
+    }
+
+    #[test]
+    fn blank() {
+        let mut dec = new(&ProviderImpl, value!{{}});
+        assert!(decode(&mut dec, "").is_empty());
+        assert!(decode(&mut dec, " \t").is_empty());
+    }
+}
@@ -0,0 +1,313 @@
+use std::collections::HashSet;
+
+use super::*;
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::event::*;
+use crate::value::*;
+
+pub const NAME: &'static str = "kv";
+
+const FIELD_SPLIT: &'static str = "field_split";
+const VALUE_SPLIT: &'static str = "value_split";
+const INCLUDE_KEYS: &'static str = "include_keys";
+const EXCLUDE_KEYS: &'static str = "exclude_keys";
+const INFER_TYPES: &'static str = "infer_types";
+
+const DEFAULT_FIELD_SPLIT: &'static str = " ";
+const DEFAULT_VALUE_SPLIT: &'static str = "=";
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::EventDecoder,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Arc<Factory>> {
+        let config = &ctx.config;
+
+        fn separator(config: &Spanned<Value>, key: &str, default: &str) -> Result<String> {
+            if let Some(v) = config.get_opt(key)? {
+                let r = v.as_str()?;
+                if r.is_empty() {
+                    return Err(v.new_error(format!("`{}` must not be empty", key)));
+                }
+                Ok(r.to_owned())
+            } else {
+                Ok(default.to_owned())
+            }
+        }
+
+        fn keys(config: &Spanned<Value>, key: &str) -> Result<Option<HashSet<String>>> {
+            if let Some(v) = config.get_opt(key)? {
+                let mut r = HashSet::new();
+                for v in v.as_list()? {
+                    r.insert(v.as_str()?.to_owned());
+                }
+                Ok(Some(r))
+            } else {
+                Ok(None)
+            }
+        }
+
+        let field_split = separator(config, FIELD_SPLIT, DEFAULT_FIELD_SPLIT)?;
+        let value_split = separator(config, VALUE_SPLIT, DEFAULT_VALUE_SPLIT)?;
+        let include_keys = keys(config, INCLUDE_KEYS)?;
+        let exclude_keys = keys(config, EXCLUDE_KEYS)?.unwrap_or_default();
+        let infer_types = config.get_opt_bool(INFER_TYPES)?.cloned().unwrap_or(true);
+
+        Ok(Arc::new(FactoryImpl {
+            config: Arc::new(Config {
+                field_split,
+                value_split,
+                include_keys,
+                exclude_keys,
+                infer_types,
+            }),
+        }))
+    }
+}
+
+struct Config {
+    /// Separator of the key-value pairs.
+    field_split: String,
+
+    /// Separator of the key and the value.
+    value_split: String,
+
+    /// If set only these keys are added to the events.
+    include_keys: Option<HashSet<String>>,
+
+    exclude_keys: HashSet<String>,
+
+    /// Whether to convert unquoted numbers and booleans to `Int`, `Float` and `Bool` values.
+    infer_types: bool,
+}
+
+impl Config {
+    fn includes(&self, key: &str) -> bool {
+        self.include_keys.as_ref().map(|k| k.contains(key)).unwrap_or(true) &&
+            !self.exclude_keys.contains(key)
+    }
+}
+
+struct FactoryImpl {
+    config: Arc<Config>,
+}
+
+impl Factory for FactoryImpl {
+    fn new(&self) -> Box<Decoder> {
+        Box::new(DecoderImpl {
+            config: self.config.clone(),
+        })
+    }
+}
+
+struct DecoderImpl {
+    config: Arc<Config>,
+}
+
+impl Decoder for DecoderImpl {
+    fn decode(&mut self, inp: &[u8], out: &mut Vec<Event>) -> Result<usize> {
+        let inp = String::from_utf8_lossy(inp);
+        if inp.trim().is_empty() {
+            return Ok(0);
+        }
+
+        let mut event = Event::new();
+        for (key, value) in Pairs::new(&inp, &self.config) {
+            if key.is_empty() || !self.config.includes(key) {
+                continue;
+            }
+            let value = match value {
+                PairValue::None => Value::Bool(true),
+                PairValue::Quoted(v) => Value::String(v),
+                PairValue::Unquoted(v) => if self.config.infer_types {
+                    infer_type(v)
+                } else {
+                    Value::String(v.into())
+                }
+            };
+            event.fields_mut().insert(key.into(), value);
+        }
+        out.push(event);
+        Ok(1)
+    }
+
+    fn flush(&mut self, _out: &mut Vec<Event>) -> Result<usize> {
+        Ok(0)
+    }
+}
+
+fn infer_type(s: &str) -> Value {
+    match s {
+        "true" => return Value::Bool(true),
+        "false" => return Value::Bool(false),
+        _ => {}
+    }
+    if let Ok(v) = s.parse() {
+        return Value::Int(v);
+    }
+    // Don't treat things like `inf` or `NaN` as floats.
+    if s.bytes().all(|b| b.is_ascii_digit() || b == b'.' || b == b'-' || b == b'+' ||
+        b == b'e' || b == b'E')
+    {
+        if let Ok(v) = s.parse() {
+            return Value::Float(v);
+        }
+    }
+    Value::String(s.into())
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum PairValue<'a> {
+    /// Bare key, e.g. `key` in logfmt.
+    None,
+
+    /// Unescaped value of a double-quoted string.
+    Quoted(String),
+
+    Unquoted(&'a str),
+}
+
+/// Iterator over key-value pairs.
+struct Pairs<'a> {
+    s: &'a str,
+    field_split: &'a str,
+    value_split: &'a str,
+}
+
+impl<'a> Pairs<'a> {
+    fn new(s: &'a str, config: &'a Config) -> Self {
+        Self {
+            s,
+            field_split: &config.field_split,
+            value_split: &config.value_split,
+        }
+    }
+
+    /// Splits `s` at the first occurrence of `pat`.
+    fn split(&mut self, pat: &str) -> &'a str {
+        if let Some(i) = self.s.find(pat) {
+            let r = &self.s[..i];
+            self.s = &self.s[i + pat.len()..];
+            r
+        } else {
+            let r = self.s;
+            self.s = "";
+            r
+        }
+    }
+
+    fn quoted(&mut self) -> String {
+        let mut r = String::new();
+        let mut chars = self.s[1..].char_indices();
+        let mut end = self.s.len();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    end = i + 2;
+                    break;
+                }
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => r.push('\n'),
+                    Some((_, 'r')) => r.push('\r'),
+                    Some((_, 't')) => r.push('\t'),
+                    Some((_, c)) => r.push(c),
+                    None => r.push('\\'),
+                }
+                c => r.push(c),
+            }
+        }
+        self.s = &self.s[end..];
+        // Skip the garbage after the closing quote if any.
+        self.split(self.field_split);
+        r
+    }
+}
+
+impl<'a> Iterator for Pairs<'a> {
+    type Item = (&'a str, PairValue<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.s.starts_with(self.field_split) {
+            self.s = &self.s[self.field_split.len()..];
+        }
+        if self.s.is_empty() {
+            return None;
+        }
+
+        let key_end = self.s.find(self.value_split);
+        let field_end = self.s.find(self.field_split);
+        match (key_end, field_end) {
+            (Some(k), Some(f)) if k < f => {},
+            (Some(_), None) => {},
+            _ => return Some((self.split(self.field_split), PairValue::None)),
+        }
+        let key = self.split(self.value_split);
+
+        let value = if self.s.starts_with('"') {
+            PairValue::Quoted(self.quoted())
+        } else {
+            PairValue::Unquoted(self.split(self.field_split))
+        };
+        Some((key, value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::component::decoder::event::test::*;
+
+    #[test]
+    fn logfmt() {
+        let mut dec = new(&ProviderImpl, value!{{}});
+        let e = &decode(&mut dec, concat!(r#"level=info  msg="hello \"world\"\n" n=-12 f=1.5 "#,
+            r#"ok=true bare empty= s=inf q="1""#))[0];
+        assert_eq!(e.fields().len(), 9);
+        assert_eq!(e.fields()["level"], s("info"));
+        assert_eq!(e.fields()["msg"], s("hello \"world\"\n"));
+        assert_eq!(e.fields()["n"], Value::Int(-12));
+        assert_eq!(e.fields()["f"], Value::Float(1.5));
+        assert_eq!(e.fields()["ok"], Value::Bool(true));
+        assert_eq!(e.fields()["bare"], Value::Bool(true));
+        assert_eq!(e.fields()["empty"], s(""));
+        assert_eq!(e.fields()["s"], s("inf"));
+        assert_eq!(e.fields()["q"], s("1"));
+    }
+
+    #[test]
+    fn separators() {
+        let mut dec = new(&ProviderImpl, value!{{
+            FIELD_SPLIT => "&",
+            VALUE_SPLIT => ": ",
+            INFER_TYPES => false,
+        }});
+        let e = &decode(&mut dec, "a: 1&&b: x y&c: \"unterminated")[0];
+        assert_eq!(e.fields().len(), 3);
+        assert_eq!(e.fields()["a"], s("1"));
+        assert_eq!(e.fields()["b"], s("x y"));
+        assert_eq!(e.fields()["c"], s("unterminated"));
+    }
+
+    #[test]
+    fn include_exclude() {
+        let mut dec = new(&ProviderImpl, value!{{
+            INCLUDE_KEYS => ["a", "b"],
+            EXCLUDE_KEYS => ["b"],
+        }});
+        let e = &decode(&mut dec, "a=1 b=2 c=3")[0];
+        assert_eq!(e.fields().len(), 1);
+        assert_eq!(e.fields()["a"], Value::Int(1));
+    }
+}
@@ -0,0 +1,161 @@
+use rmpv::Value as MsgPack;
+
+use super::*;
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::event::*;
+use crate::util::msgpack::from_msgpack;
+use crate::value::*;
+
+pub const NAME: &'static str = "msgpack";
+
+/// Tag of the events made with `parse_failure()`.
+pub const PARSE_FAILURE_TAG: &'static str = "msgpack_parse_failure";
+
+const DEFAULT_FIELD: &'static str = "message";
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::EventDecoder,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, _ctx: New) -> Result<Arc<Factory>> {
+        Ok(Arc::new(FactoryImpl))
+    }
+}
+
+struct FactoryImpl;
+
+impl Factory for FactoryImpl {
+    fn new(&self) -> Box<Decoder> {
+        Box::new(DecoderImpl)
+    }
+}
+
+/// Decodes each MessagePack map in the frame into an event. A frame may contain a number of
+/// concatenated values.
+struct DecoderImpl;
+
+impl Decoder for DecoderImpl {
+    fn decode(&mut self, inp: &[u8], out: &mut Vec<Event>) -> Result<usize> {
+        let mut rd = inp;
+        let mut written = 0;
+        while !rd.is_empty() {
+            let start = inp.len() - rd.len();
+            let event = match rmpv::decode::read_value(&mut rd) {
+                Ok(v @ MsgPack::Map(_)) => {
+                    let mut event = Event::new();
+                    if let Some(Value::Map(fields)) = from_msgpack(v) {
+                        event.fields_mut().extend(fields.into_iter().map(|(k, v)| (k, v.value)));
+                    }
+                    event
+                }
+                r => {
+                    let (end, e) = match r {
+                        Ok(_) => (inp.len() - rd.len(), "MessagePack map expected".into()),
+                        // Can't find where the broken value ends so the rest of the frame is
+                        // consumed.
+                        Err(e) => (inp.len(), e.to_string()),
+                    };
+                    rd = &inp[end..];
+                    parse_failure(&inp[start..end], DEFAULT_FIELD, PARSE_FAILURE_TAG, e)
+                }
+            };
+            out.push(event);
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self, _out: &mut Vec<Event>) -> Result<usize> {
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::component::decoder::event::test::*;
+
+    fn encode(values: &[MsgPack]) -> Vec<u8> {
+        let mut r = Vec::new();
+        for v in values {
+            rmpv::encode::write_value(&mut r, v).unwrap();
+        }
+        r
+    }
+
+    #[test]
+    fn map() {
+        let inp = encode(&[MsgPack::Map(vec![
+            (MsgPack::from("s"), MsgPack::from("str")),
+            (MsgPack::from("i"), MsgPack::from(-1)),
+            (MsgPack::from("u"), MsgPack::from(u64::max_value())),
+            (MsgPack::from("f"), MsgPack::F32(1.5)),
+            (MsgPack::from("b"), MsgPack::from(true)),
+            (MsgPack::from("n"), MsgPack::Nil),
+            (MsgPack::from("bin"), MsgPack::Binary(vec![0, 0xff])),
+            (MsgPack::from("l"), MsgPack::Array(vec![MsgPack::from(1), MsgPack::Nil])),
+            (MsgPack::from(1), MsgPack::Map(vec![(MsgPack::from(2), MsgPack::from("a"))])),
+        ])]);
+        let mut dec = new(&ProviderImpl, value!{{}});
+        let events = decode(&mut dec, &inp);
+        assert_eq!(events.len(), 1);
+        let f = events[0].fields();
+        assert!(events[0].tags().is_empty());
+        assert_eq!(f.len(), 8);
+        assert_eq!(f["s"], Value::String("str".into()));
+        assert_eq!(f["i"], Value::Int(-1));
+        assert_eq!(f["u"], Value::Float(18446744073709551615.0));
+        assert_eq!(f["f"], Value::Float(1.5));
+        assert_eq!(f["b"], Value::Bool(true));
+        assert_eq!(f["bin"], Value::Bytes(vec![0, 0xff]));
+        assert_eq!(f["l"], value!([1]));
+        assert_eq!(f["1"], value!({"2" => "a"}));
+    }
+
+    #[test]
+    fn concatenated() {
+        let m = |i: i64| MsgPack::Map(vec![(MsgPack::from("i"), MsgPack::from(i))]);
+        let mut dec = new(&ProviderImpl, value!{{}});
+        let events = decode(&mut dec, &encode(&[m(1), m(2)]));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].fields()["i"], Value::Int(1));
+        assert_eq!(events[1].fields()["i"], Value::Int(2));
+    }
+
+    #[test]
+    fn invalid() {
+        let m = MsgPack::Map(vec![(MsgPack::from("i"), MsgPack::from(1))]);
+        let mut inp = encode(&[MsgPack::from("text"), m]);
+        inp.extend_from_slice(b"\x92\x01");
+        let mut dec = new(&ProviderImpl, value!{{}});
+        let events = decode(&mut dec, &inp);
+        assert_eq!(events.len(), 3);
+
+        assert_eq!(events[0].fields()[DEFAULT_FIELD], Value::String("\u{fffd}text".into()));
+        assert!(events[0].tags().contains_key(PARSE_FAILURE_TAG));
+
+        assert!(events[1].tags().is_empty());
+        assert_eq!(events[1].fields()["i"], Value::Int(1));
+
+        assert_eq!(events[2].fields()[DEFAULT_FIELD], Value::String("\u{fffd}\u{1}".into()));
+        assert!(events[2].tags().contains_key(PARSE_FAILURE_TAG));
+    }
+
+    #[test]
+    fn empty() {
+        let mut dec = new(&ProviderImpl, value!{{}});
+        assert!(decode(&mut dec, b"").is_empty());
+    }
+}
@@ -1,3 +1,5 @@
+use encoding_rs::{Encoding, UTF_8};
+
 use super::*;
 use crate::component::{ComponentKind, Metadata, Provider as CProvider};
 use crate::event::*;
@@ -6,7 +8,6 @@ use crate::value::*;
 pub const NAME: &'static str = "text";
 
 const CHARSET: &'static str = "charset";
-const DEFAULT_CHARSET: &'static str = "UTF-8";
 const DEFAULT_FIELD: &'static str = "message";
 
 pub fn provider() -> Box<Provider> {
@@ -26,27 +27,37 @@ impl CProvider for ProviderImpl {
 
 impl Provider for ProviderImpl {
     fn new(&self, ctx: New) -> Result<Arc<Factory>> {
-        let charset = ctx.config.get_opt_str(CHARSET)?.unwrap_or(DEFAULT_CHARSET);
-        if charset != DEFAULT_CHARSET {
-            unimplemented!();
-        }
+        let charset = if let Some(v) = ctx.config.get_opt(CHARSET)? {
+            let label = v.as_str()?;
+            Encoding::for_label(label.as_bytes())
+                .ok_or_else(|| v.new_error(format!("unknown charset `{}`", label)))?
+        } else {
+            UTF_8
+        };
 
         Ok(Arc::new(FactoryImpl {
+            charset,
         }))
     }
 }
 
-struct FactoryImpl {}
+struct FactoryImpl {
+    charset: &'static Encoding,
+}
 
 impl Factory for FactoryImpl {
     fn new(&self) -> Box<Decoder> {
-        Box::new(DecoderImpl {})
+        Box::new(DecoderImpl)
     }
-}
 
-struct DecoderImpl {
+    fn charset(&self) -> Option<&'static Encoding> {
+        Some(self.charset)
+    }
 }
 
+/// Decodes the frames already transcoded to UTF-8 from the configured charset.
+struct DecoderImpl;
+
 impl Decoder for DecoderImpl {
     fn decode(&mut self, inp: &[u8], out: &mut Vec<Event>) -> Result<usize> {
         let mut event = Event::new();
@@ -59,4 +70,70 @@ impl Decoder for DecoderImpl {
     fn flush(&mut self, _out: &mut Vec<Event>) -> Result<usize> {
         Ok(0)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::component::decoder::{frame_event, stream, BufDecoder};
+    use crate::component::registry;
+
+    /// Decodes the lines of text in the `charset` going through the whole decoder stack.
+    fn decode(charset: &str, inp: &[u8]) -> Vec<String> {
+        let r = registry();
+        let config = value!{{ CHARSET => charset }}.into();
+        let event = ProviderImpl.new(New { config }).unwrap();
+        let stream = stream::charset::factory(
+            r.stream_decoder("plain").unwrap().new(Default::default()).unwrap(),
+            event.charset().unwrap());
+        let mut dec = BufDecoder::new(stream.new(), frame_event::composite::factory(
+            r.frame_decoder("delimited").unwrap().new(Default::default()).unwrap(),
+            event,
+            None).new());
+        let buf = dec.writeable_buf();
+        buf.write()[..inp.len()].copy_from_slice(inp);
+        buf.advance_write_pos(inp.len());
+        let mut events = Vec::new();
+        dec.flush(&mut events, &mut Vec::new()).unwrap();
+        events.iter()
+            .map(|e| e.fields()[DEFAULT_FIELD].as_str().unwrap().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn charsets() {
+        let cases: &[(&str, &[u8], &str)] = &[
+            ("UTF-8", b"\xef\xbb\xbf\xd1\x82\xd0\xb5\xd1\x81\xd1\x82", "тест"),
+            ("utf-8", b"invalid \xff", "invalid \u{fffd}"),
+            ("UTF-16LE", b"\x31\x04\x3e\x04", "бо"),
+            ("UTF-16BE", b"\x04\x31\x04\x3e", "бо"),
+            ("UTF-16", b"\xfe\xff\x04\x31\x04\x3e", "бо"),
+            ("UTF-16", b"\xff\xfe\x31\x04\x3e\x04", "бо"),
+            ("ISO-8859-1", b"caf\xe9", "café"),
+            ("ISO-8859-5", b"\xd1\xde", "бо"),
+            ("windows-1251", b"\xe1\xee", "бо"),
+            ("Shift_JIS", b"\x82\xa0", "あ"),
+        ];
+        for &(charset, inp, exp) in cases {
+            assert_eq!(decode(charset, inp), vec![exp.to_owned()], "{}", charset);
+        }
+    }
+
+    #[test]
+    fn utf16_lines() {
+        // The BOM overrides the configured charset for the whole stream. `Ċ` (U+010A) contains
+        // the LF byte in UTF-16.
+        let mut inp = vec![0xfe, 0xff];
+        for c in "бо\nĊ\r\n\nx".encode_utf16() {
+            inp.extend_from_slice(&c.to_be_bytes());
+        }
+        assert_eq!(decode("UTF-16LE", &inp), vec!["бо", "Ċ", "", "x"]);
+    }
+
+    #[test]
+    fn unknown_charset() {
+        let config = value!{{ CHARSET => "UTF-9" }}.into();
+        let e = ProviderImpl.new(New { config }).err().unwrap();
+        assert_eq!(e.id(), &ErrorId::Parse);
+    }
+}
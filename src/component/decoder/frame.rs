@@ -1,13 +1,19 @@
+pub mod csv;
 pub mod delimited;
+pub mod length_prefixed;
+pub mod msgpack;
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::error::*;
+use crate::metric::Metrics;
 use crate::value::*;
 
 #[derive(Default)]
 pub struct New {
     pub config: Spanned<Value>,
+    pub metrics: Arc<Metrics>,
 }
 
 pub trait Provider: 'static + super::super::Provider {
@@ -16,6 +22,11 @@ pub trait Provider: 'static + super::super::Provider {
 
 pub trait Factory: 'static + Send + Sync {
     fn new(&self) -> Box<Decoder>;
+
+    /// Max time the decoders may hold a pending frame before they need to be flushed.
+    fn flush_timeout(&self) -> Option<Duration> {
+        None
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -27,8 +38,33 @@ pub struct Decode {
     pub written: usize,
 }
 
+/// Frame decoded from the input.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Frame<'a> {
+    /// Offset of the frame start in `inp`. The data may start after it if the decoder strips
+    /// the frame header.
+    pub offset: usize,
+
+    pub data: &'a [u8],
+}
+
 pub trait Decoder: 'static + Send {
-    fn decode<'a>(&mut self, inp: &'a [u8], out: &mut Vec<&'a [u8]>) -> Result<Decode>;
+    fn decode<'a>(&mut self, inp: &'a [u8], out: &mut Vec<Frame<'a>>) -> Result<Decode>;
+
+    fn flush<'a>(&mut self, inp: &'a [u8], out: &mut Vec<Frame<'a>>) -> Result<Decode>;
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    /// Returns the data of the frames.
+    pub fn data<'a>(frames: &[Frame<'a>]) -> Vec<&'a [u8]> {
+        frames.iter().map(|f| f.data).collect()
+    }
 
-    fn flush<'a>(&mut self, inp: &'a [u8], out: &mut Vec<&'a [u8]>) -> Result<Decode>;
+    /// Returns the offsets of the frames.
+    pub fn offsets(frames: &[Frame]) -> Vec<usize> {
+        frames.iter().map(|f| f.offset).collect()
+    }
 }
\ No newline at end of file
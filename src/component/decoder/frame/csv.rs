@@ -0,0 +1,191 @@
+use super::*;
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::util::csv::Dialect;
+
+pub const NAME: &'static str = "csv";
+
+const MAX_LENGTH: &'static str = "max_length";
+
+const DEFAULT_MAX_LENGTH: usize = 10 * 1024 * 1024;
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::FrameDecoder,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Arc<Factory>> {
+        let config = &ctx.config;
+
+        let dialect = Dialect::parse(config)?;
+
+        let max_length = if let Some(v) = config.get_opt(MAX_LENGTH)? {
+            let r = *v.as_int()?;
+            if r <= 0 {
+                return Err(v.new_error(format!("`{}` must be positive", MAX_LENGTH)));
+            }
+            r as usize
+        } else {
+            DEFAULT_MAX_LENGTH
+        };
+
+        Ok(Arc::new(FactoryImpl {
+            dialect,
+            max_length,
+        }))
+    }
+}
+
+struct FactoryImpl {
+    dialect: Dialect,
+
+    /// Length after which a record with an unterminated quoted field is ended at the next line
+    /// ending.
+    max_length: usize,
+}
+
+impl Factory for FactoryImpl {
+    fn new(&self) -> Box<Decoder> {
+        Box::new(DecoderImpl {
+            dialect: self.dialect,
+            max_length: self.max_length,
+            scanned: 0,
+            quoted: false,
+            escaped: false,
+        })
+    }
+}
+
+/// Splits input into records on line endings (`\n` or `\r\n`) that are not inside quoted
+/// fields.
+struct DecoderImpl {
+    dialect: Dialect,
+    max_length: usize,
+
+    /// Number of bytes of the pending record already scanned.
+    scanned: usize,
+
+    /// Whether the scanned part of the pending record ends inside a quoted field.
+    quoted: bool,
+
+    /// Whether the scanned part of the pending record ends with an escape character.
+    escaped: bool,
+}
+
+impl DecoderImpl {
+    /// Outputs the record at `offset` in `inp` ending at `end`.
+    fn push<'a>(inp: &'a [u8], offset: usize, end: usize, out: &mut Vec<Frame<'a>>) {
+        let data = &inp[offset..end];
+        let data = if data.ends_with(b"\r") {
+            &data[..data.len() - 1]
+        } else {
+            data
+        };
+        out.push(Frame { offset, data });
+    }
+}
+
+impl Decoder for DecoderImpl {
+    fn decode<'a>(&mut self, inp: &'a [u8], out: &mut Vec<Frame<'a>>) -> Result<Decode> {
+        let mut start = 0;
+        let mut written = 0;
+        for i in self.scanned..inp.len() {
+            let b = inp[i];
+            if self.quoted {
+                if self.escaped {
+                    self.escaped = false;
+                } else if Some(b) == self.dialect.escape {
+                    self.escaped = true;
+                } else if b == self.dialect.quote {
+                    self.quoted = false;
+                } else if b == b'\n' && i - start > self.max_length {
+                    // Most likely a stray quote, don't let it swallow the rest of the input.
+                    self.quoted = false;
+                }
+            } else if b == self.dialect.quote {
+                self.quoted = true;
+            }
+            if !self.quoted && b == b'\n' {
+                Self::push(inp, start, i, out);
+                written += 1;
+                start = i + 1;
+            }
+        }
+        self.scanned = inp.len() - start;
+        Ok(Decode {
+            read: start,
+            written,
+        })
+    }
+
+    fn flush<'a>(&mut self, inp: &'a [u8], out: &mut Vec<Frame<'a>>) -> Result<Decode> {
+        let mut r = self.decode(inp, out)?;
+        if r.read < inp.len() {
+            Self::push(inp, r.read, inp.len(), out);
+            r.read = inp.len();
+            r.written += 1;
+        }
+        self.scanned = 0;
+        self.quoted = false;
+        self.escaped = false;
+        Ok(r)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::component::decoder::frame::test::*;
+    use crate::value::*;
+
+    fn decode(read: usize, written: usize) -> Decode {
+        Decode {
+            read,
+            written,
+        }
+    }
+
+    fn new<'a>(config: Value) -> (Box<Decoder>, Vec<Frame<'a>>) {
+        let dec = ProviderImpl.new(New { config: config.into(), ..Default::default() })
+            .unwrap().new();
+        (dec, Vec::new())
+    }
+
+    #[test]
+    fn quoted_newlines() {
+        let (ref mut dec, ref mut frames) = new(value!{{}});
+
+        let inp = &b"a,b\r\n\"c\nd\",\"e\"\"\n\"\nf"[..];
+        assert_eq!(dec.decode(&inp[..8], frames).unwrap(), decode(5, 1));
+        assert_eq!(dec.decode(&inp[5..], frames).unwrap(), decode(13, 1));
+        assert_eq!(dec.flush(&inp[18..], frames).unwrap(), decode(1, 1));
+        assert_eq!(data(&frames[..]), &[&b"a,b"[..], &b"\"c\nd\",\"e\"\"\n\""[..], &b"f"[..]]);
+    }
+
+    #[test]
+    fn escape() {
+        let (ref mut dec, ref mut frames) = new(value!{{ "escape" => "\\" }});
+
+        assert_eq!(dec.decode(&b"\"a\\\"\n\"\nb\n"[..], frames).unwrap(), decode(9, 2));
+        assert_eq!(data(&frames[..]), &[&b"\"a\\\"\n\""[..], &b"b"[..]]);
+        assert_eq!(offsets(&frames[..]), &[0, 7]);
+    }
+
+    #[test]
+    fn max_length() {
+        let (ref mut dec, ref mut frames) = new(value!{{ MAX_LENGTH => 3 }});
+
+        assert_eq!(dec.decode(&b"\"a\nbcd\nef\n"[..], frames).unwrap(), decode(10, 2));
+        assert_eq!(data(&frames[..]), &[&b"\"a\nbcd"[..], &b"ef"[..]]);
+    }
+}
@@ -1,14 +1,20 @@
 use memchr::*;
 use regex::bytes::Regex;
+use std::cmp;
 use std::marker::PhantomData;
 use std::ops::Range;
+use std::time::Duration;
 
 use super::*;
 use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::metric::{self, Metrics};
 use crate::value::*;
 
 pub const NAME: &'static str = "delimited";
 
+/// Counter of frames that exceeded `max_length`.
+pub const OVERSIZED_METRIC: &'static str = "decoder.frame.delimited.oversized";
+
 const STRING: &'static str = "string";
 const LINE: &'static str = "line";
 const GLUE: &'static str = "glue";
@@ -16,6 +22,19 @@ const GLUE_ON: &'static str = "on";
 const GLUE_TO: &'static str = "to";
 const GLUE_TO_PREVIOUS: &'static str = "previous";
 const GLUE_TO_NEXT: &'static str = "next";
+const GLUE_NEGATE: &'static str = "negate";
+const GLUE_START: &'static str = "start";
+const GLUE_END: &'static str = "end";
+const GLUE_MAX_LINES: &'static str = "max_lines";
+const GLUE_MAX_BYTES: &'static str = "max_bytes";
+const GLUE_TIMEOUT: &'static str = "timeout";
+const MAX_LENGTH: &'static str = "max_length";
+const ON_OVERSIZE: &'static str = "on_oversize";
+const ON_OVERSIZE_TRUNCATE: &'static str = "truncate";
+const ON_OVERSIZE_SPLIT: &'static str = "split";
+const ON_OVERSIZE_SKIP: &'static str = "skip";
+
+const DEFAULT_MAX_LENGTH: usize = 10 * 1024 * 1024;
 
 pub fn provider() -> Box<Provider> {
     Box::new(ProviderImpl)
@@ -57,31 +76,43 @@ impl Provider for ProviderImpl {
         };
 
         let glue = if let Some(glue) = ctx.config.remove_opt(GLUE)? {
-            let on = glue.get(GLUE_ON)?;
-            let on = Regex::new(on.as_str()?)
-                .map_err(move |_| on.new_error("invalid regular expression"))?;
-
-            let to = glue.get(GLUE_TO)?;
-            let to = match to.as_str()? {
-                GLUE_TO_PREVIOUS => GlueTo::Previous,
-                GLUE_TO_NEXT => GlueTo::Next,
-                _ => return Err(ErrorDetails::new(
-                        format!("`{}` must be one of [\"{}\", \"{}\"]",
-                            to.as_str().unwrap(), GLUE_TO_PREVIOUS, GLUE_TO_NEXT),
-                        to.span.clone())
-                        .wrap_id(ErrorId::Parse)),
-            };
-            Some(Glue {
-                on,
-                to,
-            })
+            Some(Glue::parse(&glue)?)
         } else {
             None
         };
 
+        let max_length = if let Some(v) = ctx.config.remove_opt(MAX_LENGTH)? {
+            let r = *v.as_int()?;
+            if r <= 0 {
+                return Err(v.new_error(format!("`{}` must be positive", MAX_LENGTH)));
+            }
+            r as usize
+        } else {
+            DEFAULT_MAX_LENGTH
+        };
+
+        let on_oversize = if let Some(v) = ctx.config.remove_opt(ON_OVERSIZE)? {
+            match v.as_str()? {
+                ON_OVERSIZE_TRUNCATE => Oversize::Truncate,
+                ON_OVERSIZE_SPLIT => Oversize::Split,
+                ON_OVERSIZE_SKIP => Oversize::Skip,
+                _ => return Err(v.new_error(format!("`{}` must be one of [\"{}\", \"{}\", \"{}\"]",
+                    ON_OVERSIZE, ON_OVERSIZE_TRUNCATE, ON_OVERSIZE_SPLIT, ON_OVERSIZE_SKIP))),
+            }
+        } else {
+            Oversize::Truncate
+        };
+
+        ctx.metrics.set_default(OVERSIZED_METRIC.into(), metric::Value::Counter(0.into()));
+
         Ok(Arc::new(FactoryImpl {
             delimiter,
             glue,
+            max_length: MaxLength {
+                len: max_length,
+                on_oversize,
+            },
+            metrics: ctx.metrics,
         }))
     }
 }
@@ -89,15 +120,26 @@ impl Provider for ProviderImpl {
 struct FactoryImpl {
     delimiter: Delimiter,
     glue: Option<Glue>,
+    max_length: MaxLength,
+    metrics: Arc<Metrics>,
 }
 
 impl Factory for FactoryImpl {
     fn new(&self) -> Box<Decoder> {
         Box::new(DecoderImpl {
             delimiter: self.delimiter.clone(),
-            glue: self.glue.clone().map(|config| GlueState::new(config))
+            framer: Framer {
+                glue: self.glue.clone(),
+                max_length: self.max_length.clone(),
+                metrics: self.metrics.clone(),
+                state: State::default(),
+            },
         })
     }
+
+    fn flush_timeout(&self) -> Option<Duration> {
+        self.glue.as_ref().and_then(|g| g.timeout)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -106,175 +148,489 @@ enum Delimiter {
     String(String),
 }
 
+impl Delimiter {
+    /// Returns length of the delimiter `buf` ends with.
+    fn trailing_len(&self, buf: &[u8]) -> usize {
+        match self {
+            Delimiter::Line => if buf.ends_with(b"\r\n") {
+                2
+            } else if buf.ends_with(b"\r") || buf.ends_with(b"\n") {
+                1
+            } else {
+                0
+            }
+            Delimiter::String(s) => if buf.ends_with(s.as_bytes()) {
+                s.len()
+            } else {
+                0
+            }
+        }
+    }
+
+    /// Max number of trailing bytes of an undelimited input that can be a part of a delimiter.
+    fn max_partial_len(&self) -> usize {
+        match self {
+            Delimiter::Line => 1,
+            Delimiter::String(s) => s.len().saturating_sub(1),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 enum GlueTo {
     Previous,
     Next,
 }
 
+#[derive(Clone, Debug)]
+enum GlueRule {
+    /// Lines matching `on` (or not matching if `negate` is set) are glued to the previous or
+    /// the next line.
+    On {
+        on: Regex,
+        to: GlueTo,
+        negate: bool,
+    },
+
+    /// Lines matching `start` begin a new frame, lines matching `end` end the current frame.
+    StartEnd {
+        start: Option<Regex>,
+        end: Option<Regex>,
+    },
+}
+
 #[derive(Clone, Debug)]
 struct Glue {
-    on: Regex,
-    to: GlueTo,
+    rule: GlueRule,
+
+    /// Max number of lines in a glued frame. The line that would exceed the limit begins
+    /// a new frame.
+    max_lines: Option<usize>,
+
+    /// Max length of a glued frame. The line that would make the frame exceed the limit begins
+    /// a new frame. Unlike `max_length` this never splits lines.
+    max_bytes: Option<usize>,
+
+    /// Max time the pending frame is held waiting for more lines.
+    timeout: Option<Duration>,
+}
+
+impl Glue {
+    fn parse(config: &Spanned<Value>) -> Result<Self> {
+        fn regex(v: &Spanned<Value>) -> Result<Regex> {
+            Regex::new(v.as_str()?).map_err(|_| v.new_error("invalid regular expression"))
+        }
+
+        fn positive(v: &Spanned<Value>, key: &str) -> Result<usize> {
+            let r = *v.as_int()?;
+            if r <= 0 {
+                return Err(v.new_error(format!("`{}` must be positive", key)));
+            }
+            Ok(r as usize)
+        }
+
+        let on = config.get_opt(GLUE_ON)?;
+        let start = config.get_opt(GLUE_START)?;
+        let end = config.get_opt(GLUE_END)?;
+
+        let rule = if let Some(on) = on {
+            if let Some(v) = start.or(end) {
+                return Err(v.new_error(format!("`{}` can't be used along with `{}`",
+                    if start.is_some() { GLUE_START } else { GLUE_END }, GLUE_ON)));
+            }
+
+            let to = config.get(GLUE_TO)?;
+            let to = match to.as_str()? {
+                GLUE_TO_PREVIOUS => GlueTo::Previous,
+                GLUE_TO_NEXT => GlueTo::Next,
+                _ => return Err(ErrorDetails::new(
+                        format!("`{}` must be one of [\"{}\", \"{}\"]",
+                            to.as_str().unwrap(), GLUE_TO_PREVIOUS, GLUE_TO_NEXT),
+                        to.span.clone())
+                        .wrap_id(ErrorId::Parse)),
+            };
+
+            GlueRule::On {
+                on: regex(on)?,
+                to,
+                negate: config.get_opt_bool(GLUE_NEGATE)?.cloned().unwrap_or(false),
+            }
+        } else if start.is_some() || end.is_some() {
+            if let Some(v) = config.get_opt(GLUE_NEGATE)? {
+                return Err(v.new_error(format!("`{}` can only be used along with `{}`",
+                    GLUE_NEGATE, GLUE_ON)));
+            }
+            GlueRule::StartEnd {
+                start: start.map(regex).transpose()?,
+                end: end.map(regex).transpose()?,
+            }
+        } else {
+            return Err(config.new_error(format!("either `{}` or `{}`/`{}` must be specified",
+                GLUE_ON, GLUE_START, GLUE_END)));
+        };
+
+        let max_lines = config.get_opt(GLUE_MAX_LINES)?
+            .map(|v| positive(v, GLUE_MAX_LINES))
+            .transpose()?;
+
+        let max_bytes = config.get_opt(GLUE_MAX_BYTES)?
+            .map(|v| positive(v, GLUE_MAX_BYTES))
+            .transpose()?;
+
+        let timeout = config.get_opt_duration(GLUE_TIMEOUT)?;
+
+        Ok(Self {
+            rule,
+            max_lines,
+            max_bytes,
+            timeout,
+        })
+    }
+
+    /// Whether the `line` begins a new frame.
+    fn begins(&self, line: &[u8]) -> bool {
+        match &self.rule {
+            GlueRule::On { on, to: GlueTo::Previous, negate } => on.is_match(line) == *negate,
+            GlueRule::On { to: GlueTo::Next, .. } => false,
+            GlueRule::StartEnd { start, .. } => start.as_ref().map(|r| r.is_match(line))
+                .unwrap_or(false),
+        }
+    }
+
+    /// Whether the `line` ends the current frame.
+    fn ends(&self, line: &[u8]) -> bool {
+        match &self.rule {
+            GlueRule::On { to: GlueTo::Previous, .. } => false,
+            GlueRule::On { on, to: GlueTo::Next, negate } => on.is_match(line) == *negate,
+            GlueRule::StartEnd { end, .. } => end.as_ref().map(|r| r.is_match(line))
+                .unwrap_or(false),
+        }
+    }
+
+    /// Whether a frame of `lines` lines can't be extended with another line that would make
+    /// the frame `new_len` bytes long.
+    fn is_full(&self, lines: usize, new_len: usize) -> bool {
+        self.max_lines.map(|m| lines >= m).unwrap_or(false)
+            || self.max_bytes.map(|m| new_len > m).unwrap_or(false)
+    }
 }
 
-/// Indicates glued frame is ready to be flushed to the output buffer.
-#[derive(Debug)]
-struct Flush {
-    /// Frame location in the glue buffer.
-    /// The frame includes the delimiter of len `delim_len`.
-    slice: Range<usize>,
+/// What to do with a frame that exceeds the max length.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Oversize {
+    /// Output the first `max_length` bytes of the frame and drop the rest.
+    Truncate,
+
+    /// Output the frame in chunks of at most `max_length` bytes.
+    Split,
 
-    /// The length of delimiter included into `slice`.
-    delim_len: usize,
+    /// Drop the frame.
+    Skip,
 }
 
-#[derive(Debug)]
-enum GlueResult {
-    Flush(Flush),
-    Glue,
+#[derive(Clone, Debug)]
+struct MaxLength {
+    len: usize,
+    on_oversize: Oversize,
 }
 
-#[derive(Debug)]
-struct GlueState {
-    config: Glue,
+/// Frame assembly state. All positions are relative to the decoder input and are rebased
+/// after each `decode()` call.
+#[derive(Debug, Default)]
+struct State {
+    /// Pending frame location in the input. The frame doesn't include the delimiter of its
+    /// last line.
+    frame: Option<Range<usize>>,
+
+    /// Position where the next line begins.
+    line_start: usize,
 
-    /// Points to the start of active slice of the glue buffer.
-    /// Effectively it is located immediately after the last flushed slice.
-    start: usize,
+    /// The current frame exceeded the max length and was truncated or skipped. The rest of
+    /// the frame lines are dropped.
+    dropping_frame: bool,
 
-    /// Absolute position in the glue buffer. This is where new frame will begin.
-    pos: usize,
+    /// The rest of the current line is dropped.
+    dropping_line: bool,
 
-    /// Last matched delimiter len.
-    delim_len: usize,
+    /// The current incomplete line has already been added to the pending frame.
+    line_added: bool,
+
+    /// The current frame has been counted as oversized.
+    oversized: bool,
+
+    /// Number of lines in the pending frame.
+    lines: usize,
 }
 
-impl GlueState {
-    pub fn new(config: Glue) -> Self {
-        Self {
-            config,
-            start: 0,
-            pos: 0,
-            delim_len: 0,
-        }
-    }
-
-    /// `buf` is the current glue buffer which consists of the previous glued frames with new
-    /// frame at `self.pos`. The new frame includes the delimiter bytes of len `delim_len`.
-    pub fn update(&mut self, buf: &[u8], delim_len: usize) -> GlueResult {
-        let frame = &buf[self.pos..buf.len() - delim_len];
-        if self.config.on.is_match(frame) {
-            match self.config.to {
-                GlueTo::Previous => {
-                    self.pos = buf.len();
-                    self.delim_len = delim_len;
-                }
-                GlueTo::Next => {}
+/// Assembles frames from the delimited lines applying the glue rules and the max length
+/// limit.
+struct Framer {
+    glue: Option<Glue>,
+    max_length: MaxLength,
+    metrics: Arc<Metrics>,
+    state: State,
+}
+
+impl Framer {
+    fn decode<'a>(&mut self, inp: &'a [u8], out: &mut Vec<Frame<'a>>, delimiter: &Delimiter,
+        delimiters: impl Iterator<Item=(usize, usize)>, flush: bool) -> Decode
+    {
+        let mut written = 0;
+        for (i, delimiter_len) in delimiters {
+            if i < self.state.line_start {
+                // Overlaps with the previous delimiter.
+                continue;
             }
-            GlueResult::Glue
+            self.on_line(inp, i, out, &mut written);
+            self.state.line_start = i + delimiter_len;
+        }
+
+        if flush {
+            if self.state.line_start < inp.len() {
+                let end = inp.len() - delimiter.trailing_len(&inp[self.state.line_start..]);
+                self.on_line(inp, end, out, &mut written);
+            }
+            self.end_frame(inp, out, &mut written);
+            self.state = State::default();
+            return Decode {
+                read: inp.len(),
+                written,
+            };
+        }
+
+        self.on_partial_line(inp, out, &mut written, delimiter.max_partial_len());
+
+        let read = self.state.frame.as_ref()
+            .map(|f| f.start)
+            .unwrap_or(self.state.line_start);
+        if let Some(frame) = &mut self.state.frame {
+            frame.start -= read;
+            frame.end -= read;
+        }
+        self.state.line_start -= read;
+
+        Decode {
+            read,
+            written,
+        }
+    }
+
+    fn decode_undelimited<'a>(&mut self, inp: &'a [u8], out: &mut Vec<Frame<'a>>) -> Decode {
+        let mut written = 0;
+        if !inp.is_empty() {
+            self.state.frame = Some(0..inp.len());
+            self.check_length(inp, out, &mut written);
+            self.end_frame(inp, out, &mut written);
+        }
+        self.state = State::default();
+        Decode {
+            read: inp.len(),
+            written,
+        }
+    }
+
+    fn begins(&self, line: &[u8]) -> bool {
+        self.glue.as_ref().map(|g| g.begins(line)).unwrap_or(true)
+    }
+
+    fn ends(&self, line: &[u8]) -> bool {
+        self.glue.as_ref().map(|g| g.ends(line)).unwrap_or(true)
+    }
+
+    /// Whether the pending frame can't be extended with a line ending at `end`.
+    fn is_full(&self, end: usize) -> bool {
+        match (&self.glue, &self.state.frame) {
+            (Some(glue), Some(frame)) => glue.is_full(self.state.lines, end - frame.start),
+            _ => false,
+        }
+    }
+
+    /// Handles a complete line that starts at `state.line_start` and ends at `end`.
+    fn on_line<'a>(&mut self, inp: &'a [u8], end: usize, out: &mut Vec<Frame<'a>>,
+        written: &mut usize)
+    {
+        let line = &inp[self.state.line_start..end];
+
+        if self.state.dropping_line {
+            self.state.dropping_line = false;
+            if self.ends(line) {
+                self.end_frame(inp, out, written);
+            }
+            return;
+        }
+
+        if self.state.line_added {
+            self.state.line_added = false;
         } else {
-            let r = match self.config.to {
-                GlueTo::Previous => {
-                    let r = if self.pos == self.start {
-                        GlueResult::Glue
-                    } else {
-                        GlueResult::Flush(Flush {
-                            slice: self.start..self.pos,
-                            delim_len: self.delim_len,
-                        })
-                    };
-                    self.start = self.pos;
-                    r
+            if self.begins(line) || self.is_full(end) {
+                self.end_frame(inp, out, written);
+            }
+            if self.state.dropping_frame {
+                if self.ends(line) {
+                    self.end_frame(inp, out, written);
                 }
-                GlueTo::Next => {
-                    let r = GlueResult::Flush(Flush {
-                        slice: self.start..buf.len(),
-                        delim_len,
-                    });
-                    dbg!(&r);
-                    self.start = buf.len();
-                    r
+                return;
+            }
+            self.state.lines += 1;
+        }
+
+        let start = self.state.frame.as_ref().map(|f| f.start).unwrap_or(self.state.line_start);
+        self.state.frame = Some(start..end);
+        self.check_length(inp, out, written);
+
+        if self.ends(line) {
+            self.end_frame(inp, out, written);
+        }
+    }
+
+    /// Ensures the incomplete line at the end of the input doesn't make the pending frame
+    /// exceed the max length.
+    fn on_partial_line<'a>(&mut self, inp: &'a [u8], out: &mut Vec<Frame<'a>>,
+        written: &mut usize, max_partial_len: usize)
+    {
+        let line_start = self.state.line_start;
+        let drop_line_start = cmp::max(line_start, inp.len().saturating_sub(max_partial_len));
+
+        if self.state.dropping_line {
+            self.state.line_start = drop_line_start;
+            return;
+        }
+
+        let frame_len = |state: &State|
+            inp.len() - state.frame.as_ref().map(|f| f.start).unwrap_or(line_start);
+        if frame_len(&self.state) <= self.max_length.len {
+            return;
+        }
+
+        if !self.state.line_added {
+            let line = &inp[line_start..];
+            if self.begins(line) || self.is_full(inp.len()) {
+                self.end_frame(inp, out, written);
+                if frame_len(&self.state) <= self.max_length.len {
+                    return;
                 }
-            };
-            self.pos = buf.len();
-            self.delim_len = delim_len;
-            r
+            }
+            if self.state.dropping_frame {
+                self.state.dropping_line = true;
+                self.state.line_start = drop_line_start;
+                return;
+            }
+            self.state.line_added = true;
+            self.state.lines += 1;
+        }
+
+        let start = self.state.frame.as_ref().map(|f| f.start).unwrap_or(line_start);
+        self.state.frame = Some(start..inp.len());
+        self.check_length(inp, out, written);
+
+        if let Some(frame) = &self.state.frame {
+            // Split the frame, the line can't start before the rest of the frame.
+            self.state.line_start = cmp::max(line_start, frame.start);
+        } else {
+            self.state.line_added = false;
+            self.state.dropping_line = true;
+            self.state.line_start = drop_line_start;
         }
     }
 
-    pub fn reset(&mut self) {
-        self.start = 0;
-        self.pos = 0;
-        self.delim_len = 0;
+    /// Applies the max length limit to the pending frame.
+    fn check_length<'a>(&mut self, inp: &'a [u8], out: &mut Vec<Frame<'a>>,
+        written: &mut usize)
+    {
+        let frame = self.state.frame.clone().unwrap();
+        let max_len = self.max_length.len;
+        if frame.len() <= max_len {
+            return;
+        }
+
+        if !self.state.oversized {
+            self.state.oversized = true;
+            self.metrics.inc(OVERSIZED_METRIC, 1);
+        }
+
+        match self.max_length.on_oversize {
+            Oversize::Truncate => {
+                out.push(Frame {
+                    offset: frame.start,
+                    data: &inp[frame.start..frame.start + max_len],
+                });
+                *written += 1;
+                self.state.frame = None;
+                self.state.dropping_frame = true;
+            }
+            Oversize::Split => {
+                let mut start = frame.start;
+                while frame.end - start > max_len {
+                    out.push(Frame {
+                        offset: start,
+                        data: &inp[start..start + max_len],
+                    });
+                    *written += 1;
+                    start += max_len;
+                }
+                self.state.frame = Some(start..frame.end);
+            }
+            Oversize::Skip => {
+                self.state.frame = None;
+                self.state.dropping_frame = true;
+            }
+        }
     }
 
-    pub fn rebase(&mut self) {
-        self.pos -= self.start;
-        self.start = 0;
+    /// Outputs the pending frame (if any) and resets the per-frame state.
+    fn end_frame<'a>(&mut self, inp: &'a [u8], out: &mut Vec<Frame<'a>>, written: &mut usize) {
+        if let Some(frame) = self.state.frame.take() {
+            out.push(Frame {
+                offset: frame.start,
+                data: &inp[frame],
+            });
+            *written += 1;
+        }
+        self.state.dropping_frame = false;
+        self.state.oversized = false;
+        self.state.lines = 0;
     }
 }
 
 struct DecoderImpl {
     delimiter: Delimiter,
-    glue: Option<GlueState>,
+    framer: Framer,
 }
 
-impl Decoder for DecoderImpl {
-    fn decode<'a>(&mut self, inp: &'a [u8], out: &mut Vec<&'a [u8]>) -> Result<Decode> {
-        let start = self.glue.as_ref().map(|g| g.pos).unwrap_or(0);
-        Ok(match &self.delimiter {
-            Delimiter::Line => decode_line(inp, out, &mut self.glue, start),
+impl DecoderImpl {
+    fn decode<'a>(&mut self, inp: &'a [u8], out: &mut Vec<Frame<'a>>, flush: bool) -> Decode {
+        let start = self.framer.state.line_start;
+        let framer = &mut self.framer;
+        let delimiter = &self.delimiter;
+        match delimiter {
+            Delimiter::Line => framer.decode(inp, out, delimiter,
+                LineEndings::new(inp, start), flush),
             Delimiter::String(s) => {
                 let s = s.as_bytes();
                 match s.len() {
-                    0 => decode_undelimited(inp, out),
+                    0 => framer.decode_undelimited(inp, out),
 
-                    1 => decode_string(inp, out, &mut self.glue,
+                    1 => framer.decode(inp, out, delimiter,
                         new_memchr1(s[0], inp, start)
-                        .map(|i| (i, 1))),
-
-                    2 => decode_string(inp, out, &mut self.glue,
-                        new_memchr2(s[0], s[1], inp, start)
-                        .map(|i| (i, 2))),
-
-                    3 => decode_string(inp, out, &mut self.glue,
-                        new_memchr3(s[0], s[1], s[2], inp, start)
-                        .map(|i| (i, 3))),
-
-                    len => decode_string(inp, out, &mut self.glue,
-                        new_memchr3_overlapping(s[0], s[1], s[2], inp, start)
-                        .filter(|i| {
-                            let start = i + 3;
-                            let end = i + s.len();
-                            if inp.len() >= end {
-                                &inp[start..end] == &s[3..]
-                            } else {
-                                false
-                            }
-                        })
-                        .map(|i| (i, len))),
+                        .map(|i| (i, 1)), flush),
+
+                    len => framer.decode(inp, out, delimiter,
+                        new_memchr1(s[0], inp, start)
+                        .filter(|&i| inp[i..].starts_with(s))
+                        .map(|i| (i, len)), flush),
                 }
             }
-        })
-    }
-
-    fn flush<'a>(&mut self, inp: &'a [u8], out: &mut Vec<&'a [u8]>) -> Result<Decode> {
-        if let Some(glue) = &mut self.glue {
-            glue.reset();
         }
-        Ok(match &self.delimiter {
-            Delimiter::Line => flush_line(inp, out),
-            Delimiter::String(s) => flush_string(inp, out, s),
-        })
     }
 }
 
-fn decode_undelimited<'a>(inp: &'a [u8], out: &mut Vec<&'a [u8]>) -> Decode {
-    out.push(inp.into());
-    Decode {
-        read: inp.len(),
-        written: 1,
+impl Decoder for DecoderImpl {
+    fn decode<'a>(&mut self, inp: &'a [u8], out: &mut Vec<Frame<'a>>) -> Result<Decode> {
+        Ok(self.decode(inp, out, false))
+    }
+
+    fn flush<'a>(&mut self, inp: &'a [u8], out: &mut Vec<Frame<'a>>) -> Result<Decode> {
+        Ok(self.decode(inp, out, true))
     }
 }
 
@@ -339,92 +695,6 @@ impl Iterator for LineEndings<'_> {
     }
 }
 
-fn decode_line<'a>(inp: &'a [u8], out: &mut Vec<&'a [u8]>,
-    glue: &mut Option<GlueState>, start: usize) -> Decode
-{
-    decode_string(inp, out, glue, LineEndings::new(inp, start))
-}
-
-fn flush_line<'a>(inp: &'a [u8], out: &mut Vec<&'a [u8]>) -> Decode {
-    if inp.is_empty() {
-        return Decode {
-            read: 0,
-            written: 0,
-        };
-    }
-
-    let trailing_delim = if inp.len() > 1 &&
-        inp[inp.len() - 2] == b'\r' && inp[inp.len() - 1] == b'\n'
-    {
-        2
-    } else if inp[inp.len() - 1] == b'\r' || inp[inp.len() - 1] == b'\n' {
-        1
-    } else {
-        0
-    };
-    out.push(&inp[..inp.len() - trailing_delim]);
-    Decode {
-        read: inp.len(),
-        written: 1,
-    }
-}
-
-fn decode_string<'a>(inp: &'a [u8], out: &mut Vec<&'a [u8]>,
-    glue: &mut Option<GlueState>,
-    iter: impl Iterator<Item=(usize, usize)>) -> Decode
-{
-    let mut read = 0;
-    let mut written = 0;
-    if let Some(glue) = glue {
-        for (i, delimiter_len) in iter {
-            assert!(i >= glue.pos);
-            match glue.update(&inp[..i + delimiter_len], delimiter_len) {
-                GlueResult::Glue => continue,
-                GlueResult::Flush(flush) => {
-                    dbg!((i, delimiter_len));
-                    dbg!(&flush);
-                    dbg!(glue.delim_len);
-                    out.push(&inp[flush.slice.start..flush.slice.end - flush.delim_len]);
-                    read = flush.slice.end;
-                }
-            }
-            written += 1;
-        }
-        glue.rebase();
-        dbg!(&glue);
-    } else {
-        for (i, delimiter_len) in iter {
-            dbg!((i, delimiter_len));
-            out.push(&inp[read..i]);
-            read = i + delimiter_len;
-            written += 1;
-        }
-    }
-    Decode {
-        read,
-        written,
-    }
-}
-
-fn flush_string<'a>(inp: &'a [u8], out: &mut Vec<&'a [u8]>, s: &str) -> Decode {
-    if inp.is_empty() {
-        return Decode {
-            read: 0,
-            written: 0,
-        };
-    }
-    let delim_len = if inp.ends_with(s.as_bytes()) {
-        s.len()
-    } else {
-        0
-    };
-    out.push(&inp[..inp.len() - delim_len]);
-    Decode {
-        read: inp.len(),
-        written: 1,
-    }
-}
-
 trait Len {
     const LEN: usize;
 }
@@ -434,16 +704,6 @@ impl Len for Len1 {
     const LEN: usize = 1;
 }
 
-struct Len2;
-impl Len for Len2 {
-    const LEN: usize = 2;
-}
-
-struct Len3;
-impl Len for Len3 {
-    const LEN: usize = 3;
-}
-
 /// Iterator over non-overlapping `memchr`-based matches.
 struct Memchr<'a, F, L> {
     f: F,
@@ -470,21 +730,6 @@ fn new_memchr1<'a>(n: u8, hs: &'a [u8], start: usize) -> impl 'a + Iterator<Item
     new_memchr::<Len1, _>(hs, start, move |hs| memchr(n, hs))
 }
 
-#[inline]
-fn new_memchr2<'a>(n1: u8, n2: u8, hs: &'a [u8], start: usize) -> impl 'a + Iterator<Item=usize> {
-    new_memchr::<Len2, _>(hs, start, move |hs| memchr2(n1, n2, hs))
-}
-
-#[inline]
-fn new_memchr3<'a>(n1: u8, n2: u8, n3: u8, hs: &'a [u8], start: usize) -> impl 'a + Iterator<Item=usize> {
-    new_memchr::<Len3, _>(hs, start, move |hs| memchr3(n1, n2, n3, hs))
-}
-
-#[inline]
-fn new_memchr3_overlapping<'a>(n1: u8, n2: u8, n3: u8, hs: &'a [u8], start: usize) -> impl 'a + Iterator<Item=usize> {
-    new_memchr::<Len1, _>(hs, start, move |hs| memchr3(n1, n2, n3, hs))
-}
-
 impl<F, L> Iterator for Memchr<'_, F, L>
     where F: FnMut(&[u8]) -> Option<usize>,
           L: Len,
@@ -507,6 +752,7 @@ impl<F, L> Iterator for Memchr<'_, F, L>
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::component::decoder::frame::test::*;
 
     fn decode(read: usize, written: usize) -> Decode {
         Decode {
@@ -518,8 +764,9 @@ mod test {
     mod line_any {
         use super::*;
 
-        fn new<'a>() -> (Box<Decoder>, Vec<&'a [u8]>) {
-            let dec = ProviderImpl.new(New { config: value!{{}}.into() }).unwrap().new();
+        fn new<'a>() -> (Box<Decoder>, Vec<Frame<'a>>) {
+            let dec = ProviderImpl.new(New { config: value!{{}}.into(), ..Default::default() })
+                .unwrap().new();
             let frames = Vec::new();
             (dec, frames)
         }
@@ -541,7 +788,7 @@ mod test {
             assert_eq!(dec.decode(&b"test\x00"[..], frames).unwrap(), decode(0, 0));
             assert_eq!(frames.len(), 0);
             assert_eq!(dec.flush(&b"test\x00"[..], frames).unwrap(), decode(5, 1));
-            assert_eq!(&frames[..], &[&b"test\x00"[..]]);
+            assert_eq!(data(&frames[..]), &[&b"test\x00"[..]]);
         }
 
         #[test]
@@ -549,7 +796,7 @@ mod test {
             let (ref mut dec, ref mut frames) = new();
 
             assert_eq!(dec.decode(&b"\n"[..], frames).unwrap(), decode(1, 1));
-            assert_eq!(&frames[..], &[&b""[..]]);
+            assert_eq!(data(&frames[..]), &[&b""[..]]);
         }
 
         #[test]
@@ -559,7 +806,7 @@ mod test {
             assert_eq!(dec.decode(&b"\r"[..], frames).unwrap(), decode(0, 0));
             assert_eq!(frames.len(), 0);
             assert_eq!(dec.flush(&b"\r"[..], frames).unwrap(), decode(1, 1));
-            assert_eq!(&frames[..], &[&b""[..]]);
+            assert_eq!(data(&frames[..]), &[&b""[..]]);
         }
 
         #[test]
@@ -569,7 +816,7 @@ mod test {
             assert_eq!(dec.decode(&b"\r"[..], frames).unwrap(), decode(0, 0));
             assert_eq!(frames.len(), 0);
             assert_eq!(dec.flush(&b"\r\n"[..], frames).unwrap(), decode(2, 1));
-            assert_eq!(&frames[..], &[&b""[..]]);
+            assert_eq!(data(&frames[..]), &[&b""[..]]);
         }
 
         #[test]
@@ -577,20 +824,20 @@ mod test {
             let (ref mut dec, ref mut frames) = new();
 
             assert_eq!(dec.decode(&b"\n\n"[..], frames).unwrap(), decode(2, 2));
-            assert_eq!(&frames[..], &[&b""[..], &b""[..]]);
+            assert_eq!(data(&frames[..]), &[&b""[..], &b""[..]]);
 
             assert_eq!(dec.decode(&b"\r\n"[..], frames).unwrap(), decode(2, 1));
-            assert_eq!(&frames[2..], &[&b""[..]]);
+            assert_eq!(data(&frames[2..]), &[&b""[..]]);
 
             assert_eq!(dec.decode(&b"\r\n\r\n"[..], frames).unwrap(), decode(4, 2));
-            assert_eq!(&frames[3..], &[&b""[..], &b""[..]]);
+            assert_eq!(data(&frames[3..]), &[&b""[..], &b""[..]]);
 
             assert_eq!(dec.decode(&b"\r\n\n\r\r\n"[..], frames).unwrap(), decode(6, 4));
-            assert_eq!(&frames[5..], &[&b""[..], &b""[..],
+            assert_eq!(data(&frames[5..]), &[&b""[..], &b""[..],
                 &b""[..], &b""[..]]);
 
             assert_eq!(dec.decode(&b"\n\r\n"[..], frames).unwrap(), decode(3, 2));
-            assert_eq!(&frames[9..], &[&b""[..], &b""[..]]);
+            assert_eq!(data(&frames[9..]), &[&b""[..], &b""[..]]);
         }
 
         #[test]
@@ -602,33 +849,36 @@ mod test {
                    line 3\r\n\
                    line 4"[..];
             assert_eq!(dec.decode(&s[..22], frames).unwrap(), decode(15, 2));
-            assert_eq!(&frames[..], &[&b"line 1"[..], &b"line 2"[..]]);
+            assert_eq!(data(&frames[..]), &[&b"line 1"[..], &b"line 2"[..]]);
 
             assert_eq!(dec.decode(&s[15..], frames).unwrap(), decode(8, 1));
-            assert_eq!(&frames[2..], &[&b"line 3"[..]]);
+            assert_eq!(data(&frames[2..]), &[&b"line 3"[..]]);
 
             assert_eq!(dec.flush(&s[23..], frames).unwrap(), decode(6, 1));
-            assert_eq!(&frames[3..], &[&b"line 4"[..]]);
+            assert_eq!(data(&frames[3..]), &[&b"line 4"[..]]);
 
-            assert_eq!(&frames[..], &[
+            assert_eq!(data(&frames[..]), &[
                 &b"line 1"[..],
                 &b"line 2"[..],
                 &b"line 3"[..],
                 &b"line 4"[..],
             ]);
+            assert_eq!(offsets(&frames[..]), &[0, 8, 0, 0]);
         }
     }
 
     mod string {
         use super::*;
 
-        fn new<'a>() -> (Box<Decoder>, Vec<&'a [u8]>) {
+        fn new<'a>() -> (Box<Decoder>, Vec<Frame<'a>>) {
             new_with_str("~!~")
         }
 
-        fn new_with_str<'a>(s: &str) -> (Box<Decoder>, Vec<&'a [u8]>) {
-            let dec = ProviderImpl.new(New { config: value!{{ STRING => s }}.into() })
-                .unwrap().new();
+        fn new_with_str<'a>(s: &str) -> (Box<Decoder>, Vec<Frame<'a>>) {
+            let dec = ProviderImpl.new(New {
+                config: value!{{ STRING => s }}.into(),
+                ..Default::default()
+            }).unwrap().new();
             let frames = Vec::new();
             (dec, frames)
         }
@@ -648,7 +898,7 @@ mod test {
             let (ref mut dec, ref mut frames) = new();
 
             assert_eq!(dec.decode(&b"test\x00~!~"[..], frames).unwrap(), decode(8, 1));
-            assert_eq!(&frames[..], &[&b"test\x00"[..]]);
+            assert_eq!(data(&frames[..]), &[&b"test\x00"[..]]);
 
             frames.clear();
             assert_eq!(dec.flush(&b""[..], frames).unwrap(), decode(0, 0));
@@ -660,7 +910,7 @@ mod test {
             let (ref mut dec, ref mut frames) = new();
 
             assert_eq!(dec.decode(&b"test\x01~!~test\x02~!~"[..], frames).unwrap(), decode(16, 2));
-            assert_eq!(&frames[..], &[&b"test\x01"[..], &b"test\x02"[..]]);
+            assert_eq!(data(&frames[..]), &[&b"test\x01"[..], &b"test\x02"[..]]);
 
             frames.clear();
             assert_eq!(dec.flush(&b""[..], frames).unwrap(), decode(0, 0));
@@ -673,7 +923,7 @@ mod test {
 
             assert_eq!(dec.decode(&b"line1_dddddelim_line2_ddddelim"[..], frames).unwrap(),
                 decode(30, 2));
-            assert_eq!(&frames[..], &[&b"line1_d"[..], &b"_line2_"[..]]);
+            assert_eq!(data(&frames[..]), &[&b"line1_d"[..], &b"_line2_"[..]]);
         }
     }
 
@@ -690,13 +940,13 @@ mod test {
         mod line_any {
             use super::*;
 
-            fn new<'a>(on: &str, to: GlueTo) -> (Box<Decoder>, Vec<&'a [u8]>) {
+            fn new<'a>(on: &str, to: GlueTo) -> (Box<Decoder>, Vec<Frame<'a>>) {
                 let dec = ProviderImpl.new(New { config: value! {{
                     GLUE => {
                         GLUE_ON => on,
                         GLUE_TO => glue_to_str(to)
                     }
-                }}.into() }).unwrap().new();
+                }}.into(), ..Default::default() }).unwrap().new();
                 let frames = Vec::new();
                 (dec, frames)
             }
@@ -709,7 +959,7 @@ mod test {
                 assert_eq!(dec.flush(&b"line2\n"[..], frames).unwrap(), decode(6, 1));
                 assert_eq!(dec.decode(&b"line3\n\n"[..], frames).unwrap(), decode(6, 1));
 
-                assert_eq!(&frames[..], &[
+                assert_eq!(data(&frames[..]), &[
                     &b"line1"[..],
                     &b"line2"[..],
                     &b"line3"[..],
@@ -730,7 +980,7 @@ mod test {
                     [..];
 
                 assert_eq!(dec.decode(&inp[..13], frames).unwrap(), decode(7, 1));
-                assert_eq!(&frames[..], &[&b"line0"[..]]);
+                assert_eq!(data(&frames[..]), &[&b"line0"[..]]);
 
                 frames.clear();
                 assert_eq!(dec.decode(&inp[7..21], frames).unwrap(), decode(0, 0));
@@ -740,11 +990,11 @@ mod test {
                 assert_eq!(frames.len(), 0);
 
                 assert_eq!(dec.decode(&inp[7..38], frames).unwrap(), decode(25, 1));
-                assert_eq!(&frames[..], &[&b"line1\n line1.2\n! line1.3"[..]]);
+                assert_eq!(data(&frames[..]), &[&b"line1\n line1.2\n! line1.3"[..]]);
 
                 frames.clear();
                 assert_eq!(dec.flush(&inp[32..], frames).unwrap(), decode(15, 1));
-                assert_eq!(&frames[..], &[&b"line2\n\tline2.1"[..]]);
+                assert_eq!(data(&frames[..]), &[&b"line2\n\tline2.1"[..]]);
             }
 
             #[test]
@@ -760,32 +1010,32 @@ mod test {
                     [..];
 
                 assert_eq!(dec.decode(&inp[..10], frames).unwrap(), decode(6, 1));
-                assert_eq!(&frames[..], &[&b"line1"[..]]);
+                assert_eq!(data(&frames[..]), &[&b"line1"[..]]);
 
                 frames.clear();
                 assert_eq!(dec.decode(&inp[6..35], frames).unwrap(), decode(26, 1));
-                assert_eq!(&frames[..], &[&b"line2 ~\nline2.1 !\nline2.2"[..]]);
+                assert_eq!(data(&frames[..]), &[&b"line2 ~\nline2.1 !\nline2.2"[..]]);
 
                 frames.clear();
                 assert_eq!(dec.decode(&inp[32..], frames).unwrap(), decode(0, 0));
                 assert_eq!(frames.len(), 0);
 
                 assert_eq!(dec.flush(&inp[32..], frames).unwrap(), decode(16, 1));
-                assert_eq!(&frames[..], &[&b"line3!\rline3.1~"[..]]);
+                assert_eq!(data(&frames[..]), &[&b"line3!\rline3.1~"[..]]);
             }
         }
 
         mod string {
             use super::*;
 
-            fn new<'a>(delim: &str, on: &str, to: GlueTo) -> (Box<Decoder>, Vec<&'a [u8]>) {
+            fn new<'a>(delim: &str, on: &str, to: GlueTo) -> (Box<Decoder>, Vec<Frame<'a>>) {
                 let dec = ProviderImpl.new(New { config: value! {{
                     STRING => delim,
                     GLUE => {
                         GLUE_ON => on,
                         GLUE_TO => glue_to_str(to)
                     }
-                }}.into() }).unwrap().new();
+                }}.into(), ..Default::default() }).unwrap().new();
                 let frames = Vec::new();
                 (dec, frames)
             }
@@ -798,7 +1048,7 @@ mod test {
                 assert_eq!(dec.flush(&b"line2||"[..], frames).unwrap(), decode(7, 1));
                 assert_eq!(dec.decode(&b"line3||||"[..], frames).unwrap(), decode(7, 1));
 
-                assert_eq!(&frames[..], &[
+                assert_eq!(data(&frames[..]), &[
                     &b"line1"[..],
                     &b"line2"[..],
                     &b"line3"[..],
@@ -819,7 +1069,7 @@ mod test {
                     [..];
 
                 assert_eq!(dec.decode(&inp[..14], frames).unwrap(), decode(7, 1));
-                assert_eq!(&frames[..], &[&b"line0"[..]]);
+                assert_eq!(data(&frames[..]), &[&b"line0"[..]]);
 
                 frames.clear();
                 assert_eq!(dec.decode(&inp[7..21], frames).unwrap(), decode(0, 0));
@@ -829,12 +1079,12 @@ mod test {
                 assert_eq!(frames.len(), 0);
 
                 assert_eq!(dec.decode(&inp[7..42], frames).unwrap(), decode(28, 1));
-                dbg!(std::str::from_utf8(frames[0]).unwrap());
-                assert_eq!(&frames[..], &[&b"line1|| line1.2||! line1.3"[..]]);
+                dbg!(std::str::from_utf8(frames[0].data).unwrap());
+                assert_eq!(data(&frames[..]), &[&b"line1|| line1.2||! line1.3"[..]]);
 
                 frames.clear();
                 assert_eq!(dec.flush(&inp[35..], frames).unwrap(), decode(17, 1));
-                assert_eq!(&frames[..], &[&b"line2||\tline2.1"[..]]);
+                assert_eq!(data(&frames[..]), &[&b"line2||\tline2.1"[..]]);
             }
 
             #[test]
@@ -850,19 +1100,266 @@ mod test {
                     [..];
 
                 assert_eq!(dec.decode(&inp[..10], frames).unwrap(), decode(7, 1));
-                assert_eq!(&frames[..], &[&b"line1"[..]]);
+                assert_eq!(data(&frames[..]), &[&b"line1"[..]]);
 
                 frames.clear();
                 assert_eq!(dec.decode(&inp[7..36], frames).unwrap(), decode(29, 1));
-                assert_eq!(&frames[..], &[&b"line2 ~||line2.1 !||line2.2"[..]]);
+                assert_eq!(data(&frames[..]), &[&b"line2 ~||line2.1 !||line2.2"[..]]);
 
                 frames.clear();
                 assert_eq!(dec.decode(&inp[36..], frames).unwrap(), decode(0, 0));
                 assert_eq!(frames.len(), 0);
 
                 assert_eq!(dec.flush(&inp[36..], frames).unwrap(), decode(18, 1));
-                assert_eq!(&frames[..], &[&b"line3!||line3.1~"[..]]);
+                assert_eq!(data(&frames[..]), &[&b"line3!||line3.1~"[..]]);
+            }
+        }
+
+        mod rules {
+            use super::*;
+
+            fn new_factory(glue: Value) -> Result<Arc<Factory>> {
+                ProviderImpl.new(New { config: value!{{ GLUE => glue }}.into(),
+                    ..Default::default() })
             }
+
+            fn new<'a>(glue: Value) -> (Box<Decoder>, Vec<Frame<'a>>) {
+                (new_factory(glue).unwrap().new(), Vec::new())
+            }
+
+            #[test]
+            fn negate() {
+                let (ref mut dec, ref mut frames) = new(value!{{
+                    GLUE_ON => "^\\d{4}-",
+                    GLUE_TO => GLUE_TO_PREVIOUS,
+                    GLUE_NEGATE => true,
+                }});
+
+                let inp = &b"2020-01-01 a\n  at x\n  at y\n2020-01-02 b\n"[..];
+                assert_eq!(dec.decode(inp, frames).unwrap(), decode(27, 1));
+                assert_eq!(dec.flush(&inp[27..], frames).unwrap(), decode(13, 1));
+                assert_eq!(data(&frames[..]), &[&b"2020-01-01 a\n  at x\n  at y"[..],
+                    &b"2020-01-02 b"[..]]);
+            }
+
+            #[test]
+            fn start_end() {
+                let (ref mut dec, ref mut frames) = new(value!{{
+                    GLUE_START => "^BEGIN",
+                    GLUE_END => "^END",
+                }});
+
+                let inp = &b"x\nBEGIN\na\nEND\ny\n"[..];
+                assert_eq!(dec.decode(inp, frames).unwrap(), decode(14, 2));
+                assert_eq!(dec.flush(&inp[14..], frames).unwrap(), decode(2, 1));
+                assert_eq!(data(&frames[..]), &[&b"x"[..], &b"BEGIN\na\nEND"[..], &b"y"[..]]);
+            }
+
+            #[test]
+            fn end() {
+                let (ref mut dec, ref mut frames) = new(value!{{
+                    GLUE_END => ";$",
+                }});
+
+                assert_eq!(dec.decode(&b"a\nb;\nc;\nd"[..], frames).unwrap(), decode(8, 2));
+                assert_eq!(data(&frames[..]), &[&b"a\nb;"[..], &b"c;"[..]]);
+            }
+
+            #[test]
+            fn max_lines() {
+                let (ref mut dec, ref mut frames) = new(value!{{
+                    GLUE_ON => "^\\s",
+                    GLUE_TO => GLUE_TO_PREVIOUS,
+                    GLUE_MAX_LINES => 2,
+                }});
+
+                assert_eq!(dec.decode(&b"a\n 1\n 2\n 3\nb\n"[..], frames).unwrap(),
+                    decode(11, 2));
+                assert_eq!(data(&frames[..]), &[&b"a\n 1"[..], &b" 2\n 3"[..]]);
+            }
+
+            #[test]
+            fn unlimited_lines() {
+                let (ref mut dec, ref mut frames) = new(value!{{
+                    GLUE_ON => "^\\s",
+                    GLUE_TO => GLUE_TO_PREVIOUS,
+                }});
+
+                let frame = std::iter::once("a").chain(std::iter::repeat(" 1").take(1000))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let inp = format!("{}\nb\n", frame);
+                assert_eq!(dec.decode(inp.as_bytes(), frames).unwrap(),
+                    decode(frame.len() + 1, 1));
+                assert_eq!(data(&frames[..]), &[frame.as_bytes()]);
+            }
+
+            #[test]
+            fn max_bytes() {
+                let (ref mut dec, ref mut frames) = new(value!{{
+                    GLUE_ON => "^\\s",
+                    GLUE_TO => GLUE_TO_PREVIOUS,
+                    GLUE_MAX_BYTES => 6,
+                }});
+
+                assert_eq!(dec.decode(&b"a\n 1\n 2\n 3\nb\n"[..], frames).unwrap(),
+                    decode(11, 2));
+                assert_eq!(data(&frames[..]), &[&b"a\n 1"[..], &b" 2\n 3"[..]]);
+            }
+
+            #[test]
+            fn timeout() {
+                let f = new_factory(value!{{
+                    GLUE_END => ";$",
+                    GLUE_TIMEOUT => "1500ms",
+                }}).unwrap();
+                assert_eq!(f.flush_timeout(), Some(Duration::from_millis(1500)));
+
+                let f = ProviderImpl.new(Default::default()).unwrap();
+                assert_eq!(f.flush_timeout(), None);
+            }
+
+            #[test]
+            fn invalid_config() {
+                assert!(new_factory(value!{{}}).is_err());
+                assert!(new_factory(value!{{ GLUE_ON => "a" }}).is_err());
+                assert!(new_factory(value!{{
+                    GLUE_ON => "a",
+                    GLUE_TO => GLUE_TO_NEXT,
+                    GLUE_START => "b",
+                }}).is_err());
+                assert!(new_factory(value!{{ GLUE_START => "b", GLUE_NEGATE => true }}).is_err());
+                assert!(new_factory(value!{{ GLUE_END => "b", GLUE_MAX_LINES => 0 }}).is_err());
+                assert!(new_factory(value!{{ GLUE_END => "b", GLUE_TIMEOUT => "soon" }}).is_err());
+            }
+        }
+    }
+
+    mod max_length {
+        use super::*;
+
+        fn new<'a>(config: Spanned<Value>) -> (Box<Decoder>, Vec<Frame<'a>>, Arc<Metrics>) {
+            let metrics = Arc::new(Metrics::new());
+            let dec = ProviderImpl.new(New { config, metrics: metrics.clone() }).unwrap().new();
+            let frames = Vec::new();
+            (dec, frames, metrics)
+        }
+
+        fn oversized(metrics: &Metrics) -> metric::Value {
+            metrics.get(OVERSIZED_METRIC).unwrap()
+        }
+
+        fn new_line<'a>(max_length: i64, on_oversize: &str)
+            -> (Box<Decoder>, Vec<Frame<'a>>, Arc<Metrics>)
+        {
+            new(value!{{
+                MAX_LENGTH => max_length,
+                ON_OVERSIZE => on_oversize,
+            }}.into())
+        }
+
+        #[test]
+        fn truncate() {
+            let (ref mut dec, ref mut frames, metrics) = new_line(4, ON_OVERSIZE_TRUNCATE);
+
+            assert_eq!(dec.decode(&b"abcd\nabcdefg\nxy\n"[..], frames).unwrap(), decode(16, 3));
+            assert_eq!(data(&frames[..]), &[&b"abcd"[..], &b"abcd"[..], &b"xy"[..]]);
+            assert_eq!(oversized(&metrics), metric::Value::Counter(1.into()));
+        }
+
+        #[test]
+        fn split() {
+            let (ref mut dec, ref mut frames, metrics) = new_line(4, ON_OVERSIZE_SPLIT);
+
+            assert_eq!(dec.decode(&b"abcdefghij\nxy\n"[..], frames).unwrap(), decode(14, 4));
+            assert_eq!(data(&frames[..]), &[&b"abcd"[..], &b"efgh"[..], &b"ij"[..], &b"xy"[..]]);
+            assert_eq!(offsets(&frames[..]), &[0, 4, 8, 11]);
+            assert_eq!(oversized(&metrics), metric::Value::Counter(1.into()));
+        }
+
+        #[test]
+        fn skip() {
+            let (ref mut dec, ref mut frames, metrics) = new_line(4, ON_OVERSIZE_SKIP);
+
+            assert_eq!(dec.decode(&b"abcdefghij\nxy\nabcde"[..], frames).unwrap(),
+                decode(18, 1));
+            assert_eq!(data(&frames[..]), &[&b"xy"[..]]);
+            assert_eq!(oversized(&metrics), metric::Value::Counter(2.into()));
+
+            frames.clear();
+            assert_eq!(dec.flush(&b"e"[..], frames).unwrap(), decode(1, 0));
+            assert_eq!(frames.len(), 0);
+        }
+
+        #[test]
+        fn truncate_undelimited() {
+            let (ref mut dec, ref mut frames, metrics) = new_line(4, ON_OVERSIZE_TRUNCATE);
+
+            assert_eq!(dec.decode(&b"abcdefgh"[..], frames).unwrap(), decode(7, 1));
+            assert_eq!(data(&frames[..]), &[&b"abcd"[..]]);
+
+            assert_eq!(dec.decode(&b"hijklmn"[..], frames).unwrap(), decode(6, 0));
+            assert_eq!(dec.decode(&b"n\r"[..], frames).unwrap(), decode(1, 0));
+            assert_eq!(dec.decode(&b"\r\nxy\n"[..], frames).unwrap(), decode(5, 1));
+            assert_eq!(data(&frames[1..]), &[&b"xy"[..]]);
+            assert_eq!(oversized(&metrics), metric::Value::Counter(1.into()));
+        }
+
+        #[test]
+        fn split_undelimited() {
+            let (ref mut dec, ref mut frames, _) = new_line(4, ON_OVERSIZE_SPLIT);
+
+            assert_eq!(dec.decode(&b"abcdefghij"[..], frames).unwrap(), decode(8, 2));
+            assert_eq!(data(&frames[..]), &[&b"abcd"[..], &b"efgh"[..]]);
+
+            assert_eq!(dec.decode(&b"ij\nxy\n"[..], frames).unwrap(), decode(6, 2));
+            assert_eq!(data(&frames[2..]), &[&b"ij"[..], &b"xy"[..]]);
+        }
+
+        #[test]
+        fn string() {
+            let (ref mut dec, ref mut frames, _) = new(value!{{
+                STRING => "||",
+                MAX_LENGTH => 4,
+            }}.into());
+
+            assert_eq!(dec.decode(&b"abcdefg|"[..], frames).unwrap(), decode(7, 1));
+            assert_eq!(dec.decode(&b"||xy||"[..], frames).unwrap(), decode(6, 1));
+            assert_eq!(data(&frames[..]), &[&b"abcd"[..], &b"xy"[..]]);
+        }
+
+        #[test]
+        fn glue_truncate() {
+            let (ref mut dec, ref mut frames, metrics) = new(value!{{
+                GLUE => {
+                    GLUE_ON => "^\\s",
+                    GLUE_TO => GLUE_TO_PREVIOUS
+                },
+                MAX_LENGTH => 10,
+            }}.into());
+
+            assert_eq!(dec.decode(&b"line1\n cont1\n cont2\nline2\n cnt\nline3\n"[..], frames)
+                .unwrap(), decode(31, 2));
+            assert_eq!(data(&frames[..]), &[&b"line1\n con"[..], &b"line2\n cnt"[..]]);
+            assert_eq!(oversized(&metrics), metric::Value::Counter(1.into()));
+        }
+
+        #[test]
+        fn glue_runaway() {
+            let (ref mut dec, ref mut frames, _) = new(value!{{
+                GLUE => {
+                    GLUE_ON => "^\\s",
+                    GLUE_TO => GLUE_TO_PREVIOUS
+                },
+                MAX_LENGTH => 8,
+                ON_OVERSIZE => ON_OVERSIZE_SKIP,
+            }}.into());
+
+            let inp = &b"line1\n cont1 cont11\n cont2\nline2\n"[..];
+            assert_eq!(dec.decode(&inp[..18], frames).unwrap(), decode(17, 0));
+            assert_eq!(dec.decode(&inp[17..], frames).unwrap(), decode(10, 0));
+            assert_eq!(dec.flush(&inp[27..], frames).unwrap(), decode(6, 1));
+            assert_eq!(data(&frames[..]), &[&b"line2"[..]]);
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,384 @@
+use std::cmp;
+
+use super::*;
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::metric::{self, Metrics};
+
+pub const NAME: &'static str = "length_prefixed";
+
+/// Counter of the frames skipped because their length exceeds the max allowed length.
+pub const OVERSIZED_METRIC: &'static str = "decoder.frame.length_prefixed.oversized";
+
+/// Counter of the frame headers skipped because the frame length they specify is negative.
+pub const INVALID_METRIC: &'static str = "decoder.frame.length_prefixed.invalid";
+
+const LENGTH_SIZE: &'static str = "length_size";
+const BYTE_ORDER: &'static str = "byte_order";
+const BYTE_ORDER_BIG: &'static str = "big";
+const BYTE_ORDER_LITTLE: &'static str = "little";
+const LENGTH_OFFSET: &'static str = "length_offset";
+const LENGTH_ADJUSTMENT: &'static str = "length_adjustment";
+const STRIP_HEADER: &'static str = "strip_header";
+const MAX_LENGTH: &'static str = "max_length";
+
+const DEFAULT_LENGTH_SIZE: usize = 4;
+const DEFAULT_MAX_LENGTH: u64 = 64 * 1024 * 1024;
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::FrameDecoder,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Arc<Factory>> {
+        let config = &ctx.config;
+
+        let length_size = if let Some(v) = config.get_opt(LENGTH_SIZE)? {
+            match *v.as_int()? {
+                1 => 1,
+                2 => 2,
+                4 => 4,
+                8 => 8,
+                _ => return Err(v.new_error(
+                    format!("`{}` must be one of [1, 2, 4, 8]", LENGTH_SIZE))),
+            }
+        } else {
+            DEFAULT_LENGTH_SIZE
+        };
+
+        let byte_order = if let Some(v) = config.get_opt(BYTE_ORDER)? {
+            match v.as_str()? {
+                BYTE_ORDER_BIG => ByteOrder::Big,
+                BYTE_ORDER_LITTLE => ByteOrder::Little,
+                _ => return Err(v.new_error(format!("`{}` must be one of [\"{}\", \"{}\"]",
+                    BYTE_ORDER, BYTE_ORDER_BIG, BYTE_ORDER_LITTLE))),
+            }
+        } else {
+            ByteOrder::Big
+        };
+
+        let length_offset = if let Some(v) = config.get_opt(LENGTH_OFFSET)? {
+            let r = *v.as_int()?;
+            if r < 0 {
+                return Err(v.new_error(format!("`{}` must not be negative", LENGTH_OFFSET)));
+            }
+            r as usize
+        } else {
+            0
+        };
+
+        let length_adjustment = config.get_opt_int(LENGTH_ADJUSTMENT)?.cloned().unwrap_or(0);
+
+        let strip_header = config.get_opt_bool(STRIP_HEADER)?.cloned().unwrap_or(true);
+
+        let max_length = if let Some(v) = config.get_opt(MAX_LENGTH)? {
+            let r = *v.as_int()?;
+            if r <= 0 {
+                return Err(v.new_error(format!("`{}` must be positive", MAX_LENGTH)));
+            }
+            r as u64
+        } else {
+            DEFAULT_MAX_LENGTH
+        };
+
+        ctx.metrics.set_default(OVERSIZED_METRIC.into(), metric::Value::Counter(0.into()));
+        ctx.metrics.set_default(INVALID_METRIC.into(), metric::Value::Counter(0.into()));
+
+        Ok(Arc::new(FactoryImpl {
+            config: Config {
+                length_size,
+                byte_order,
+                length_offset,
+                length_adjustment,
+                strip_header,
+                max_length,
+            },
+            metrics: ctx.metrics,
+        }))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ByteOrder {
+    Big,
+    Little,
+}
+
+#[derive(Clone, Debug)]
+struct Config {
+    /// Size of the length field in bytes.
+    length_size: usize,
+
+    byte_order: ByteOrder,
+
+    /// Number of header bytes preceding the length field.
+    length_offset: usize,
+
+    /// Value added to the length field value to get the length of the frame body, i.e. the part
+    /// of the frame after the length field.
+    length_adjustment: i64,
+
+    /// Whether to exclude the header (the length field and the bytes preceding it) from
+    /// the output frames.
+    strip_header: bool,
+
+    /// Max allowed length of the frame body.
+    max_length: u64,
+}
+
+impl Config {
+    fn header_len(&self) -> usize {
+        self.length_offset + self.length_size
+    }
+
+    fn read_length(&self, buf: &[u8]) -> u64 {
+        let buf = &buf[self.length_offset..self.header_len()];
+        let mut r = 0;
+        match self.byte_order {
+            ByteOrder::Big => for &b in buf {
+                r = (r << 8) | b as u64;
+            }
+            ByteOrder::Little => for &b in buf.iter().rev() {
+                r = (r << 8) | b as u64;
+            }
+        }
+        r
+    }
+}
+
+struct FactoryImpl {
+    config: Config,
+    metrics: Arc<Metrics>,
+}
+
+impl Factory for FactoryImpl {
+    fn new(&self) -> Box<Decoder> {
+        Box::new(DecoderImpl {
+            config: self.config.clone(),
+            metrics: self.metrics.clone(),
+            skip: 0,
+        })
+    }
+}
+
+/// Length of the frame body or the reason the frame can't be decoded.
+enum BodyLen {
+    Valid(u64),
+    Oversized(u64),
+    Negative,
+}
+
+/// Splits the input into frames. The frames longer than the max length are skipped as a whole.
+/// The headers with negative frame length are skipped alone since there's no way to tell where
+/// the next frame starts, whatever follows them is read as the next header.
+struct DecoderImpl {
+    config: Config,
+    metrics: Arc<Metrics>,
+
+    /// Number of bytes of the oversized frame left to skip.
+    skip: u64,
+}
+
+impl DecoderImpl {
+    fn body_len(&self, length: u64) -> BodyLen {
+        let len = (length as i128) + (self.config.length_adjustment as i128);
+        if len < 0 {
+            BodyLen::Negative
+        } else if len > self.config.max_length as i128 {
+            BodyLen::Oversized(cmp::min(len, u64::max_value() as i128) as u64)
+        } else {
+            BodyLen::Valid(len as u64)
+        }
+    }
+}
+
+impl Decoder for DecoderImpl {
+    fn decode<'a>(&mut self, inp: &'a [u8], out: &mut Vec<Frame<'a>>) -> Result<Decode> {
+        let header_len = self.config.header_len();
+        let mut read = 0;
+        let mut written = 0;
+        loop {
+            if self.skip > 0 {
+                let n = cmp::min(self.skip, (inp.len() - read) as u64);
+                read += n as usize;
+                self.skip -= n;
+                if self.skip > 0 {
+                    break;
+                }
+            }
+            let buf = &inp[read..];
+            if buf.len() < header_len {
+                break;
+            }
+            let body_len = match self.body_len(self.config.read_length(buf)) {
+                BodyLen::Valid(v) => v,
+                BodyLen::Oversized(v) => {
+                    self.metrics.inc(OVERSIZED_METRIC, 1);
+                    self.skip = v.saturating_add(header_len as u64);
+                    continue;
+                }
+                BodyLen::Negative => {
+                    self.metrics.inc(INVALID_METRIC, 1);
+                    read += header_len;
+                    continue;
+                }
+            };
+            if ((buf.len() - header_len) as u64) < body_len {
+                break;
+            }
+            let end = header_len + body_len as usize;
+            let start = if self.config.strip_header {
+                header_len
+            } else {
+                0
+            };
+            out.push(Frame {
+                offset: read,
+                data: &buf[start..end],
+            });
+            read += end;
+            written += 1;
+        }
+        Ok(Decode {
+            read,
+            written,
+        })
+    }
+
+    fn flush<'a>(&mut self, inp: &'a [u8], out: &mut Vec<Frame<'a>>) -> Result<Decode> {
+        // Partial frame can't be decoded so it's left in the input.
+        self.decode(inp, out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::component::decoder::frame::test::*;
+
+    fn decode(read: usize, written: usize) -> Decode {
+        Decode {
+            read,
+            written,
+        }
+    }
+
+    fn new<'a>(config: Spanned<Value>) -> (Box<Decoder>, Vec<Frame<'a>>) {
+        let dec = ProviderImpl.new(New { config, ..Default::default() }).unwrap().new();
+        let frames = Vec::new();
+        (dec, frames)
+    }
+
+    #[test]
+    fn default() {
+        let (ref mut dec, ref mut frames) = new(value!{{}}.into());
+
+        let inp = &b"\x00\x00\x00\x03abc\x00\x00\x00\x00\x00\x00\x00\x02d"[..];
+
+        assert_eq!(dec.decode(&inp[..3], frames).unwrap(), decode(0, 0));
+        assert_eq!(dec.decode(&inp[..6], frames).unwrap(), decode(0, 0));
+        assert_eq!(frames.len(), 0);
+
+        assert_eq!(dec.decode(inp, frames).unwrap(), decode(11, 2));
+        assert_eq!(data(&frames[..]), &[&b"abc"[..], &b""[..]]);
+        // The offsets include the stripped headers.
+        assert_eq!(offsets(&frames[..]), &[0, 7]);
+
+        frames.clear();
+        assert_eq!(dec.flush(&inp[11..], frames).unwrap(), decode(0, 0));
+        assert_eq!(frames.len(), 0);
+    }
+
+    #[test]
+    fn sizes_and_byte_orders() {
+        let cases: &[(i64, &str, &[u8])] = &[
+            (1, BYTE_ORDER_BIG, b"\x02ab"),
+            (1, BYTE_ORDER_LITTLE, b"\x02ab"),
+            (2, BYTE_ORDER_BIG, b"\x00\x02ab"),
+            (2, BYTE_ORDER_LITTLE, b"\x02\x00ab"),
+            (4, BYTE_ORDER_LITTLE, b"\x02\x00\x00\x00ab"),
+            (8, BYTE_ORDER_BIG, b"\x00\x00\x00\x00\x00\x00\x00\x02ab"),
+            (8, BYTE_ORDER_LITTLE, b"\x02\x00\x00\x00\x00\x00\x00\x00ab"),
+        ];
+        for &(size, order, inp) in cases {
+            let (ref mut dec, ref mut frames) = new(value!{{
+                LENGTH_SIZE => size,
+                BYTE_ORDER => order,
+            }}.into());
+            assert_eq!(dec.decode(inp, frames).unwrap(), decode(inp.len(), 1));
+            assert_eq!(data(&frames[..]), &[&b"ab"[..]]);
+        }
+    }
+
+    #[test]
+    fn offset_and_adjustment() {
+        // 2 bytes of magic, length that includes the whole frame.
+        let (ref mut dec, ref mut frames) = new(value!{{
+            LENGTH_SIZE => 2,
+            LENGTH_OFFSET => 2,
+            LENGTH_ADJUSTMENT => -4,
+            STRIP_HEADER => false,
+        }}.into());
+
+        let inp = &b"XY\x00\x07abcXY\x00\x04"[..];
+        assert_eq!(dec.decode(inp, frames).unwrap(), decode(11, 2));
+        assert_eq!(data(&frames[..]), &[&b"XY\x00\x07abc"[..], &b"XY\x00\x04"[..]]);
+    }
+
+    #[test]
+    fn max_length() {
+        let metrics = Arc::new(Metrics::new());
+        let mut dec = ProviderImpl.new(New {
+            config: value!{{
+                LENGTH_SIZE => 1,
+                MAX_LENGTH => 2,
+            }}.into(),
+            metrics: metrics.clone(),
+        }).unwrap().new();
+        let frames = &mut Vec::new();
+
+        // The oversized frame is skipped as it arrives.
+        assert_eq!(dec.decode(&b"\x02ab\x04ab"[..], frames).unwrap(), decode(6, 1));
+        assert_eq!(dec.decode(&b"cd\x01e"[..], frames).unwrap(), decode(4, 1));
+        assert_eq!(data(&frames[..]), &[&b"ab"[..], &b"e"[..]]);
+        assert_eq!(metrics.get(OVERSIZED_METRIC), Some(metric::Value::Counter(1.into())));
+    }
+
+    #[test]
+    fn negative_length() {
+        let metrics = Arc::new(Metrics::new());
+        let mut dec = ProviderImpl.new(New {
+            config: value!{{
+                LENGTH_SIZE => 1,
+                LENGTH_ADJUSTMENT => -2,
+            }}.into(),
+            metrics: metrics.clone(),
+        }).unwrap().new();
+        let frames = &mut Vec::new();
+
+        assert_eq!(dec.decode(&b"\x01\x03a\x01"[..], frames).unwrap(), decode(4, 1));
+        assert_eq!(data(&frames[..]), &[&b"a"[..]]);
+        assert_eq!(metrics.get(INVALID_METRIC), Some(metric::Value::Counter(2.into())));
+    }
+
+    #[test]
+    fn invalid_config() {
+        let new = |config: Value| ProviderImpl.new(New {
+            config: config.into(),
+            ..Default::default()
+        });
+        assert!(new(value!{{ LENGTH_SIZE => 3 }}).is_err());
+        assert!(new(value!{{ BYTE_ORDER => "middle" }}).is_err());
+        assert!(new(value!{{ MAX_LENGTH => 0 }}).is_err());
+    }
+}
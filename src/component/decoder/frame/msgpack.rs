@@ -0,0 +1,394 @@
+use std::cmp;
+
+use super::*;
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::metric::{self, Metrics};
+
+pub const NAME: &'static str = "msgpack";
+
+/// Counter of the values skipped because their length exceeds the max allowed length.
+pub const OVERSIZED_METRIC: &'static str = "decoder.frame.msgpack.oversized";
+
+/// Counter of the values skipped because of the invalid marker.
+pub const INVALID_METRIC: &'static str = "decoder.frame.msgpack.invalid";
+
+const MAX_LENGTH: &'static str = "max_length";
+
+const DEFAULT_MAX_LENGTH: u64 = 64 * 1024 * 1024;
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::FrameDecoder,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Arc<Factory>> {
+        let max_length = if let Some(v) = ctx.config.get_opt(MAX_LENGTH)? {
+            let r = *v.as_int()?;
+            if r <= 0 {
+                return Err(v.new_error(format!("`{}` must be positive", MAX_LENGTH)));
+            }
+            r as u64
+        } else {
+            DEFAULT_MAX_LENGTH
+        };
+
+        ctx.metrics.set_default(OVERSIZED_METRIC.into(), metric::Value::Counter(0.into()));
+        ctx.metrics.set_default(INVALID_METRIC.into(), metric::Value::Counter(0.into()));
+
+        Ok(Arc::new(FactoryImpl {
+            max_length,
+            metrics: ctx.metrics,
+        }))
+    }
+}
+
+struct FactoryImpl {
+    /// Max allowed length of the encoded value.
+    max_length: u64,
+
+    metrics: Arc<Metrics>,
+}
+
+impl Factory for FactoryImpl {
+    fn new(&self) -> Box<Decoder> {
+        Box::new(DecoderImpl {
+            max_length: self.max_length,
+            metrics: self.metrics.clone(),
+            scan: Scan::new(),
+            skipping: false,
+        })
+    }
+}
+
+/// Progress of scanning the partial top-level value.
+#[derive(Clone, Copy, Debug)]
+struct Scan {
+    /// Offset of the next value header from the start of the top-level value.
+    pos: u64,
+
+    /// Number of values remaining to complete the top-level value.
+    pending: u64,
+}
+
+impl Scan {
+    fn new() -> Self {
+        Self {
+            pos: 0,
+            pending: 1,
+        }
+    }
+}
+
+/// Outcome of scanning the value at the start of the input.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ScanResult {
+    /// The value is complete and has the specified length.
+    Complete(usize),
+
+    /// The rest of the value is yet to be received.
+    Partial,
+
+    /// The value length exceeds the max allowed length. The scan stops right after the value
+    /// header that made it exceed.
+    Oversized,
+
+    /// The value has the invalid marker. Specifies the length of the value up to and including
+    /// the marker.
+    Invalid(usize),
+}
+
+/// Splits a stream of concatenated MessagePack values into frames with one value each.
+/// The values longer than the max length are skipped as a whole as they arrive. The values with
+/// the invalid marker are skipped up to and including the marker, whatever follows it is read as
+/// the next value.
+struct DecoderImpl {
+    max_length: u64,
+    metrics: Arc<Metrics>,
+
+    /// Where to resume scanning the partial value the input starts with, so the value is scanned
+    /// only once however many calls it takes to receive it.
+    scan: Scan,
+
+    /// Whether the value being scanned is oversized and is to be skipped.
+    skipping: bool,
+}
+
+impl DecoderImpl {
+    /// Continues scanning the value at the start of `buf` from `self.scan`. The partial value is
+    /// expected to be passed again along with the rest of it, except for the skipped bytes.
+    fn scan_value(&mut self, buf: &[u8]) -> ScanResult {
+        let Scan { mut pos, mut pending } = self.scan;
+        while pending > 0 {
+            // Resume from here if the value header is incomplete.
+            self.scan = Scan { pos, pending };
+            pending -= 1;
+            let marker = if let Some(&b) = buf.get(pos as usize) {
+                b
+            } else {
+                return ScanResult::Partial;
+            };
+            pos += 1;
+            let (len_size, fixed_len, items) = match marker {
+                0x00..=0x7f | 0xe0..=0xff | 0xc0 | 0xc2 | 0xc3 => (0, 0, 0),
+                0x80..=0x8f => (0, 0, 2 * (marker & 0x0f) as u64),
+                0x90..=0x9f => (0, 0, (marker & 0x0f) as u64),
+                0xa0..=0xbf => (0, (marker & 0x1f) as u64, 0),
+                0xc4 | 0xd9 => (1, 0, 0),
+                0xc5 | 0xda => (2, 0, 0),
+                0xc6 | 0xdb => (4, 0, 0),
+                // Ext: length field, type byte and data.
+                0xc7 => (1, 1, 0),
+                0xc8 => (2, 1, 0),
+                0xc9 => (4, 1, 0),
+                0xca => (0, 4, 0),
+                0xcb => (0, 8, 0),
+                0xcc | 0xd0 => (0, 1, 0),
+                0xcd | 0xd1 => (0, 2, 0),
+                0xce | 0xd2 => (0, 4, 0),
+                0xcf | 0xd3 => (0, 8, 0),
+                // Fixext: type byte and 1, 2, 4, 8 or 16 bytes of data.
+                0xd4..=0xd8 => (0, 1 + (1 << (marker - 0xd4)), 0),
+                0xdc | 0xdd | 0xde | 0xdf => {
+                    let len_size = if marker & 1 == 0 { 2 } else { 4 };
+                    let n = if let Some(n) = read_len(buf, pos, len_size) {
+                        n
+                    } else {
+                        return ScanResult::Partial;
+                    };
+                    pos += len_size;
+                    let n = if marker >= 0xde { 2 * n } else { n };
+                    (0, 0, n)
+                }
+                0xc1 => return ScanResult::Invalid(pos as usize),
+            };
+            let mut len = fixed_len;
+            if len_size > 0 {
+                len += if let Some(n) = read_len(buf, pos, len_size) {
+                    n
+                } else {
+                    return ScanResult::Partial;
+                };
+                pos += len_size;
+            }
+            pos += len;
+            pending += items;
+            // Each pending value takes at least one byte.
+            if !self.skipping && pos + pending > self.max_length {
+                self.scan = Scan { pos, pending };
+                return ScanResult::Oversized;
+            }
+        }
+        self.scan = Scan { pos, pending };
+        if pos <= buf.len() as u64 {
+            ScanResult::Complete(pos as usize)
+        } else {
+            ScanResult::Partial
+        }
+    }
+}
+
+/// Reads big-endian length of `size` bytes at `pos`.
+fn read_len(buf: &[u8], pos: u64, size: u64) -> Option<u64> {
+    let pos = pos as usize;
+    let buf = buf.get(pos..pos + size as usize)?;
+    Some(buf.iter().fold(0, |r, &b| (r << 8) | b as u64))
+}
+
+impl Decoder for DecoderImpl {
+    fn decode<'a>(&mut self, inp: &'a [u8], out: &mut Vec<Frame<'a>>) -> Result<Decode> {
+        let mut read = 0;
+        let mut written = 0;
+        while read < inp.len() {
+            let buf = &inp[read..];
+            match self.scan_value(buf) {
+                ScanResult::Complete(len) => {
+                    if self.skipping {
+                        self.skipping = false;
+                    } else {
+                        out.push(Frame {
+                            offset: read,
+                            data: &buf[..len],
+                        });
+                        written += 1;
+                    }
+                    read += len;
+                    self.scan = Scan::new();
+                }
+                ScanResult::Partial => {
+                    if self.skipping {
+                        // Drop what's been scanned so far, the scan resumes relative to the rest.
+                        let n = cmp::min(self.scan.pos, buf.len() as u64);
+                        self.scan.pos -= n;
+                        read += n as usize;
+                    }
+                    break;
+                }
+                ScanResult::Oversized => {
+                    self.metrics.inc(OVERSIZED_METRIC, 1);
+                    self.skipping = true;
+                }
+                ScanResult::Invalid(len) => {
+                    self.metrics.inc(INVALID_METRIC, 1);
+                    self.skipping = false;
+                    read += len;
+                    self.scan = Scan::new();
+                }
+            }
+        }
+        Ok(Decode {
+            read,
+            written,
+        })
+    }
+
+    fn flush<'a>(&mut self, inp: &'a [u8], out: &mut Vec<Frame<'a>>) -> Result<Decode> {
+        // Partial value can't be decoded so it's left in the input.
+        self.decode(inp, out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::component::decoder::frame::test::*;
+
+    fn decode(read: usize, written: usize) -> Decode {
+        Decode {
+            read,
+            written,
+        }
+    }
+
+    fn new<'a>(config: Value) -> (Box<Decoder>, Vec<Frame<'a>>) {
+        let dec = ProviderImpl.new(New { config: config.into(), ..Default::default() })
+            .unwrap().new();
+        (dec, Vec::new())
+    }
+
+    fn encode(values: &[rmpv::Value]) -> Vec<u8> {
+        let mut r = Vec::new();
+        for v in values {
+            rmpv::encode::write_value(&mut r, v).unwrap();
+        }
+        r
+    }
+
+    #[test]
+    fn values() {
+        use rmpv::Value as V;
+
+        let values = vec![
+            V::Nil,
+            V::from(true),
+            V::from(-1),
+            V::from(200),
+            V::from(-200),
+            V::from(u64::max_value()),
+            V::F32(1.5),
+            V::F64(1.5),
+            V::from("a"),
+            V::from("a".repeat(40)),
+            V::from("a".repeat(300)),
+            V::Binary(vec![1, 2, 3]),
+            V::Binary(vec![0; 70000]),
+            V::Array(vec![V::from(1), V::Array(vec![]), V::from("x")]),
+            V::Array(vec![V::from(1); 20]),
+            V::Map(vec![(V::from("a"), V::Map(vec![(V::from("b"), V::from(1))]))]),
+            V::Map((0..20).map(|i| (V::from(i), V::Nil)).collect()),
+            V::Ext(1, vec![1]),
+            V::Ext(1, vec![1; 16]),
+            V::Ext(1, vec![1; 3]),
+        ];
+        for v in &values {
+            let (ref mut dec, _) = new(value!{{}});
+            let inp = encode(&[v.clone()]);
+            let frames = &mut Vec::new();
+            for i in 0..inp.len() {
+                assert_eq!(dec.decode(&inp[..i], frames).unwrap(), decode(0, 0), "{} {}", v, i);
+            }
+            assert_eq!(dec.decode(&inp, frames).unwrap(), decode(inp.len(), 1), "{}", v);
+        }
+
+        let (ref mut dec, _) = new(value!{{}});
+        let inp = encode(&values);
+        let frames = &mut Vec::new();
+        assert_eq!(dec.decode(&inp, frames).unwrap(), decode(inp.len(), values.len()));
+        for (f, v) in frames.iter().zip(&values) {
+            assert_eq!(&rmpv::decode::read_value(&mut &f.data[..]).unwrap(), v);
+        }
+    }
+
+    #[test]
+    fn partial() {
+        let inp = encode(&[rmpv::Value::from("abc"), rmpv::Value::from("de")]);
+        let (ref mut dec, ref mut frames) = new(value!{{}});
+        assert_eq!(dec.decode(&inp[..6], frames).unwrap(), decode(4, 1));
+        assert_eq!(dec.flush(&inp[4..], frames).unwrap(), decode(3, 1));
+        assert_eq!(data(&frames[..]), &[&inp[..4], &inp[4..]]);
+    }
+
+    #[test]
+    fn resume() {
+        let inp = encode(&[rmpv::Value::Array(vec![rmpv::Value::from("abc"); 3])]);
+        let mut dec = DecoderImpl {
+            max_length: DEFAULT_MAX_LENGTH,
+            metrics: Arc::new(Metrics::new()),
+            scan: Scan::new(),
+            skipping: false,
+        };
+        let frames = &mut Vec::new();
+        // The header of the third string is missing.
+        assert_eq!(dec.decode(&inp[..7], frames).unwrap(), decode(0, 0));
+        assert_eq!((dec.scan.pos, dec.scan.pending), (9, 1));
+        // All the headers are scanned but the data is incomplete.
+        assert_eq!(dec.decode(&inp[..10], frames).unwrap(), decode(0, 0));
+        assert_eq!((dec.scan.pos, dec.scan.pending), (13, 0));
+        assert_eq!(dec.decode(&inp, frames).unwrap(), decode(inp.len(), 1));
+        assert_eq!(data(&frames[..]), &[&inp[..]]);
+    }
+
+    #[test]
+    fn max_length() {
+        let metrics = Arc::new(Metrics::new());
+        let mut dec = ProviderImpl.new(New {
+            config: value!{{ MAX_LENGTH => 4 }}.into(),
+            metrics: metrics.clone(),
+        }).unwrap().new();
+        let frames = &mut Vec::new();
+
+        // The oversized values are skipped as they arrive.
+        assert_eq!(dec.decode(b"\xa3abc\xa5ab", frames).unwrap(), decode(7, 1));
+        assert_eq!(dec.decode(b"cde\x01", frames).unwrap(), decode(4, 1));
+        assert_eq!(dec.decode(b"\x93\xa3abc", frames).unwrap(), decode(5, 0));
+        assert_eq!(dec.decode(b"\x02\x03\x04", frames).unwrap(), decode(3, 1));
+        assert_eq!(dec.decode(b"\xdd\xff\xff\xff\xff\x01", frames).unwrap(), decode(6, 0));
+        assert_eq!(data(&frames[..]), &[&b"\xa3abc"[..], &b"\x01"[..], &b"\x04"[..]]);
+        assert_eq!(metrics.get(OVERSIZED_METRIC), Some(metric::Value::Counter(3.into())));
+    }
+
+    #[test]
+    fn invalid() {
+        let metrics = Arc::new(Metrics::new());
+        let mut dec = ProviderImpl.new(New {
+            config: value!{{}}.into(),
+            metrics: metrics.clone(),
+        }).unwrap().new();
+        let frames = &mut Vec::new();
+
+        assert_eq!(dec.decode(b"\x01\xc1\x02\x92\x03\xc1\x04", frames).unwrap(), decode(7, 3));
+        assert_eq!(data(&frames[..]), &[&b"\x01"[..], &b"\x02"[..], &b"\x04"[..]]);
+        assert_eq!(offsets(&frames[..]), &[0, 2, 6]);
+        assert_eq!(metrics.get(INVALID_METRIC), Some(metric::Value::Counter(2.into())));
+    }
+}
@@ -29,7 +29,11 @@ pub struct Decode {
 }
 
 pub trait Decoder: 'static + Send {
-    fn decode(&mut self, inp: &[u8], out: &mut Vec<Event>) -> Result<Decode>;
+    /// Decodes events from `inp` into `out`. For every event written to `out` the offset of the
+    /// event's frame start in `inp` is written to `offsets`.
+    fn decode(&mut self, inp: &[u8], out: &mut Vec<Event>, offsets: &mut Vec<usize>)
+        -> Result<Decode>;
 
-    fn flush(&mut self, inp: &[u8], out: &mut Vec<Event>) -> Result<Decode>;
+    fn flush(&mut self, inp: &[u8], out: &mut Vec<Event>, offsets: &mut Vec<usize>)
+        -> Result<Decode>;
 }
@@ -1,14 +1,22 @@
+use std::iter;
 use std::sync::Arc;
 
 use super::*;
 use super::super::{frame, event};
+use crate::dead_letter::DeadLetters;
 use crate::error::*;
 use crate::event::*;
 
-pub fn factory(frame: Arc<frame::Factory>, event: Arc<event::Factory>) -> Arc<Factory> {
+/// Creates the decoder factory that splits the input into frames with the `frame` decoder and
+/// decodes each frame with the `event` decoder. If `dead_letters` is set, the frames that fail
+/// to decode are sent there as is instead of failing the decoding.
+pub fn factory(frame: Arc<frame::Factory>, event: Arc<event::Factory>,
+    dead_letters: Option<DeadLetters>) -> Arc<Factory>
+{
     Arc::new(FactoryImpl {
         frame,
         event,
+        dead_letters,
     })
 }
 
@@ -16,6 +24,7 @@ pub fn factory(frame: Arc<frame::Factory>, event: Arc<event::Factory>) -> Arc<Fa
 struct FactoryImpl {
     frame: Arc<frame::Factory>,
     event: Arc<event::Factory>,
+    dead_letters: Option<DeadLetters>,
 }
 
 impl super::Factory for FactoryImpl {
@@ -23,6 +32,7 @@ impl super::Factory for FactoryImpl {
         Box::new(DecoderImpl {
             frame: self.frame.new(),
             event: self.event.new(),
+            dead_letters: self.dead_letters.clone(),
         })
     }
 }
@@ -30,6 +40,7 @@ impl super::Factory for FactoryImpl {
 struct DecoderImpl {
     frame: Box<frame::Decoder>,
     event: Box<event::Decoder>,
+    dead_letters: Option<DeadLetters>,
 }
 
 impl DecoderImpl {
@@ -37,10 +48,27 @@ impl DecoderImpl {
         Self {
             frame,
             event,
+            dead_letters: None,
         }
     }
 
-    fn decode(&mut self, inp: &[u8], out: &mut Vec<Event>, flush: bool) -> Result<Decode> {
+    fn decode_frame(&mut self, frame: &[u8], out: &mut Vec<Event>) -> Result<usize> {
+        let len = out.len();
+        match self.event.decode(frame, out) {
+            Ok(n) => Ok(n),
+            Err(e) => if let Some(dead_letters) = &self.dead_letters {
+                out.truncate(len);
+                dead_letters.push_frame(&e, frame);
+                Ok(0)
+            } else {
+                Err(e)
+            }
+        }
+    }
+
+    fn decode(&mut self, inp: &[u8], out: &mut Vec<Event>, offsets: &mut Vec<usize>,
+        flush: bool) -> Result<Decode>
+    {
         let mut frames = Vec::new();
         let read = if flush {
             self.frame.flush(inp, &mut frames)?
@@ -49,10 +77,14 @@ impl DecoderImpl {
         }.read;
         let mut written = 0;
         for frame in frames {
-            written += self.event.decode(&frame, out)?;
+            let n = self.decode_frame(frame.data, out)?;
+            offsets.extend(iter::repeat(frame.offset).take(n));
+            written += n;
         }
         if flush {
-            written += self.event.flush(out)?;
+            let n = self.event.flush(out)?;
+            offsets.extend(iter::repeat(read).take(n));
+            written += n;
         }
         Ok(Decode {
             read,
@@ -62,11 +94,85 @@ impl DecoderImpl {
 }
 
 impl Decoder for DecoderImpl {
-    fn decode(&mut self, inp: &[u8], out: &mut Vec<Event>) -> Result<Decode> {
-        self.decode(inp, out, false)
+    fn decode(&mut self, inp: &[u8], out: &mut Vec<Event>, offsets: &mut Vec<usize>)
+        -> Result<Decode>
+    {
+        self.decode(inp, out, offsets, false)
+    }
+
+    fn flush(&mut self, inp: &[u8], out: &mut Vec<Event>, offsets: &mut Vec<usize>)
+        -> Result<Decode>
+    {
+        self.decode(inp, out, offsets, true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::component::registry;
+    use crate::dead_letter;
+    use crate::util::test::TempDir;
+
+    /// Decodes the frame as the `message` field failing on the frames starting with `!`.
+    struct StrictFactory;
+
+    impl event::Factory for StrictFactory {
+        fn new(&self) -> Box<event::Decoder> {
+            Box::new(StrictDecoder)
+        }
+    }
+
+    struct StrictDecoder;
+
+    impl event::Decoder for StrictDecoder {
+        fn decode(&mut self, inp: &[u8], out: &mut Vec<Event>) -> Result<usize> {
+            if inp.starts_with(b"!") {
+                return Err(Error::new(ErrorId::Parse, "invalid frame"));
+            }
+            let mut event = Event::new();
+            event.fields_mut().insert("message".into(),
+                Value::String(String::from_utf8_lossy(inp).into_owned()));
+            out.push(event);
+            Ok(1)
+        }
+
+        fn flush(&mut self, _out: &mut Vec<Event>) -> Result<usize> {
+            Ok(0)
+        }
+    }
+
+    fn new(dead_letters: Option<DeadLetters>) -> Box<Decoder> {
+        factory(
+            registry().frame_decoder("delimited").unwrap().new(Default::default()).unwrap(),
+            Arc::new(StrictFactory),
+            dead_letters).new()
+    }
+
+    #[test]
+    fn dead_letters() {
+        let dir = TempDir::new("composite-decoder-dead-letters");
+        let (queue, writer) = dead_letter::test::open(&dir);
+        let mut dec = new(Some(DeadLetters::new(queue, "test")));
+
+        let (ref mut events, ref mut offsets) = (Vec::new(), Vec::new());
+        let d = dec.decode(b"foo\n!bar\nbaz\n", events, offsets).unwrap();
+        drop(dec);
+        let letters = dead_letter::test::read(&dir, writer);
+
+        assert_eq!(d.written, 2);
+        assert_eq!(events.iter().map(|e| e.fields()["message"].clone()).collect::<Vec<_>>(),
+            vec![Value::String("foo".into()), Value::String("baz".into())]);
+        assert_eq!(offsets, &[0, 9]);
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].component_id, "test");
+        assert_eq!(letters[0].error_id, ErrorId::Parse);
+        assert_eq!(letters[0].frame, Some(b"!bar".to_vec()));
     }
 
-    fn flush(&mut self, inp: &[u8], out: &mut Vec<Event>) -> Result<Decode> {
-        self.decode(inp, out, true)
+    #[test]
+    fn no_dead_letters() {
+        let (ref mut events, ref mut offsets) = (Vec::new(), Vec::new());
+        assert!(new(None).decode(b"foo\n!bar\n", events, offsets).is_err());
     }
-}
\ No newline at end of file
+}
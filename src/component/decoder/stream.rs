@@ -1,3 +1,4 @@
+pub mod charset;
 pub mod gzip;
 pub mod plain;
 
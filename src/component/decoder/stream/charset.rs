@@ -0,0 +1,113 @@
+use encoding_rs::Encoding;
+use std::cmp;
+
+use super::*;
+
+/// Min size of the output buffer the decoder can make progress with.
+pub const MIN_OUT_LEN: usize = 32;
+
+/// Creates the decoder factory that transcodes the output of the `inner` decoder from
+/// the `charset` to UTF-8. The BOM (if any) overrides the `charset` and is removed.
+/// An incomplete character at the end of the stream is dropped.
+pub fn factory(inner: Arc<Factory>, charset: &'static Encoding) -> Arc<Factory> {
+    Arc::new(FactoryImpl {
+        inner,
+        charset,
+    })
+}
+
+struct FactoryImpl {
+    inner: Arc<Factory>,
+    charset: &'static Encoding,
+}
+
+impl Factory for FactoryImpl {
+    fn new(&self) -> Box<Decoder> {
+        Box::new(DecoderImpl {
+            inner: self.inner.new(),
+            charset: self.charset.new_decoder(),
+            buf: Vec::new(),
+        })
+    }
+}
+
+struct DecoderImpl {
+    inner: Box<Decoder>,
+    charset: encoding_rs::Decoder,
+
+    /// Buffer for the output of the `inner` decoder.
+    buf: Vec<u8>,
+}
+
+impl DecoderImpl {
+    /// Returns the max number of bytes that can be transcoded into `out_len` bytes.
+    fn max_inner_len(&self, out_len: usize) -> usize {
+        let mut r = out_len / 3;
+        while r > 0 && self.charset.max_utf8_buffer_length(r)
+            .map(|l| l > out_len)
+            .unwrap_or(true)
+        {
+            r -= 1;
+        }
+        r
+    }
+}
+
+impl Decoder for DecoderImpl {
+    fn decode(&mut self, inp: &[u8], out: &mut [u8]) -> Result<Decode> {
+        debug_assert!(out.len() >= MIN_OUT_LEN);
+        // Only as much is taken from the inner decoder as is guaranteed to fit in `out` once
+        // transcoded so nothing has to be kept between the calls.
+        let len = self.max_inner_len(out.len());
+        self.buf.resize(cmp::max(self.buf.len(), len), 0);
+        let mut read = 0;
+        loop {
+            let r = self.inner.decode(&inp[read..], &mut self.buf[..len])?;
+            read += r.read;
+            let (_, _, written, _) = self.charset.decode_to_utf8(&self.buf[..r.written], out,
+                false);
+            // Retry if only a part of a character was decoded.
+            if written > 0 || r.needs_more_input() {
+                return Ok(Decode {
+                    read,
+                    written,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use encoding_rs::{UTF_16LE, UTF_8};
+
+    use super::*;
+    use crate::component::registry;
+
+    fn decode(charset: &'static Encoding, inp: &[u8], out_len: usize) -> Vec<u8> {
+        let plain = registry().stream_decoder("plain").unwrap().new(Default::default()).unwrap();
+        let mut dec = factory(plain, charset).new();
+        let mut r = Vec::new();
+        let mut out = vec![0; out_len];
+        let mut inp = inp;
+        loop {
+            let d = dec.decode(inp, &mut out).unwrap();
+            if d.needs_more_input() {
+                break;
+            }
+            inp = &inp[d.read..];
+            r.extend_from_slice(&out[..d.written]);
+        }
+        r
+    }
+
+    #[test]
+    fn transcode() {
+        let utf16 = b"\xff\xfe\x31\x04\x0a\x00\x3e\x04\x0a\x01";
+        for &out_len in &[MIN_OUT_LEN, MIN_OUT_LEN + 1, 1024] {
+            assert_eq!(decode(UTF_8, utf16, out_len), "б\nоĊ".as_bytes(), "{}", out_len);
+            assert_eq!(decode(UTF_16LE, &utf16[2..], out_len), "б\nоĊ".as_bytes());
+            assert_eq!(decode(UTF_8, b"\xef\xbb\xbfa\xff", out_len), "a\u{fffd}".as_bytes());
+        }
+    }
+}
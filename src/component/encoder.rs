@@ -1,4 +1,11 @@
+pub mod csv;
 pub mod debug;
+pub mod gelf;
+pub mod json;
+pub mod json_lines;
+pub mod logfmt;
+pub mod msgpack;
+pub mod template;
 
 use std::sync::Arc;
 
@@ -17,6 +24,18 @@ pub trait Provider: 'static + super::Provider {
 
 pub trait Factory: 'static + Send + Sync {
     fn new(&self) -> Box<Encoder>;
+
+    /// Creates encoder for the stream that already has data, such as the existing file appended
+    /// to. The encoder doesn't repeat what's written once at the start of the stream.
+    fn new_appending(&self) -> Box<Encoder> {
+        self.new()
+    }
+
+    /// Whether the encoder produces lines of text. Such encoders may separate the events with
+    /// newlines rather than terminate them, so the last line is left for the output to terminate.
+    fn line_oriented(&self) -> bool {
+        false
+    }
 }
 
 pub trait Encoder: 'static + Send {
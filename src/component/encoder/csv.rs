@@ -0,0 +1,187 @@
+use super::*;
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::event::*;
+use crate::util::csv::Dialect;
+use crate::util::json::to_text;
+
+pub const NAME: &'static str = "csv";
+
+const COLUMNS: &'static str = "columns";
+const HEADER: &'static str = "header";
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::Encoder,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Arc<Factory>> {
+        let config = &ctx.config;
+
+        let dialect = Dialect::parse(config)?;
+
+        let columns_value = config.get(COLUMNS)?;
+        let mut columns = Vec::new();
+        for v in columns_value.as_list()? {
+            columns.push(v.as_str()?.to_owned());
+        }
+        if columns.is_empty() {
+            return Err(columns_value.new_error(format!("`{}` must not be empty", COLUMNS)));
+        }
+
+        let header = config.get_opt_bool(HEADER)?.cloned().unwrap_or(true);
+
+        Ok(Arc::new(FactoryImpl {
+            config: Arc::new(Config {
+                dialect,
+                columns,
+                header,
+            }),
+        }))
+    }
+}
+
+struct Config {
+    dialect: Dialect,
+
+    /// Names of the fields written to the records in that order. Missing fields are written
+    /// as empty strings.
+    columns: Vec<String>,
+
+    /// Whether to write the column names as the first record.
+    header: bool,
+}
+
+struct FactoryImpl {
+    config: Arc<Config>,
+}
+
+impl Factory for FactoryImpl {
+    fn new(&self) -> Box<Encoder> {
+        Box::new(EncoderImpl {
+            config: self.config.clone(),
+            header_written: false,
+        })
+    }
+
+    fn new_appending(&self) -> Box<Encoder> {
+        Box::new(EncoderImpl {
+            config: self.config.clone(),
+            header_written: true,
+        })
+    }
+
+    fn line_oriented(&self) -> bool {
+        true
+    }
+}
+
+/// Encodes each event into a CSV record terminated with newline. Since a new encoder is created
+/// for each output stream the header is written once per stream. The encoder for the stream that
+/// already has data doesn't write the header.
+struct EncoderImpl {
+    config: Arc<Config>,
+    header_written: bool,
+}
+
+impl Encoder for EncoderImpl {
+    fn encode(&mut self, event: &Event, out: &mut Vec<u8>) -> Result<()> {
+        let config = &self.config;
+        if config.header && !self.header_written {
+            config.dialect.join(config.columns.iter().map(|c| c.as_bytes()), out);
+            out.push(b'\n');
+            self.header_written = true;
+        }
+        let fields: Vec<_> = config.columns.iter()
+            .map(|c| event.fields().get(c).map(to_text).unwrap_or_default())
+            .collect();
+        config.dialect.join(fields.iter().map(|f| f.as_bytes()), out);
+        out.push(b'\n');
+        Ok(())
+    }
+
+    fn flush(&mut self, _out: &mut Vec<u8>) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::component::registry;
+
+    fn encode(config: Value, events: &[Event]) -> String {
+        let mut enc = ProviderImpl.new(New { config: config.into() }).unwrap().new();
+        let mut out = Vec::new();
+        for e in events {
+            enc.encode(e, &mut out).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    fn event() -> Event {
+        let mut e = Event::new();
+        let f = e.fields_mut();
+        f.insert("s".into(), Value::String("a, \"b\"".into()));
+        f.insert("i".into(), Value::Int(1));
+        f.insert("l".into(), value!([1, 2]));
+        e
+    }
+
+    #[test]
+    fn default() {
+        assert_eq!(encode(value!{{ COLUMNS => ["i", "s", "missing", "l"] }},
+            &[event(), Event::new()]),
+            "i,s,missing,l\n1,\"a, \"\"b\"\"\",,\"[1,2]\"\n,,,\n");
+    }
+
+    #[test]
+    fn no_header_and_dialect() {
+        assert_eq!(encode(value!{{
+            COLUMNS => ["s", "i"],
+            HEADER => false,
+            "delimiter" => "\t",
+            "escape" => "\\",
+        }}, &[event()]), "\"a, \\\"b\\\"\"\t1\n");
+    }
+
+    #[test]
+    fn appending() {
+        let enc = ProviderImpl.new(New { config: value!{{ COLUMNS => ["i"] }}.into() }).unwrap();
+        let mut out = Vec::new();
+        enc.new_appending().encode(&event(), &mut out).unwrap();
+        assert_eq!(out, b"1\n");
+    }
+
+    #[test]
+    fn roundtrip() {
+        let config = value!{{ COLUMNS => ["s", "i"], HEADER => true }};
+        let inp = encode(config.clone(), &[event()]);
+        let mut dec = registry().event_decoder("csv").unwrap()
+            .new(crate::component::decoder::event::New { config: config.into() }).unwrap().new();
+        let mut events = Vec::new();
+        for line in inp.lines() {
+            dec.decode(line.as_bytes(), &mut events).unwrap();
+        }
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].fields()["s"], Value::String("a, \"b\"".into()));
+        assert_eq!(events[0].fields()["i"], Value::String("1".into()));
+    }
+
+    #[test]
+    fn invalid_config() {
+        let new = |config: Value| ProviderImpl.new(New { config: config.into() });
+        assert!(new(value!{{}}).is_err());
+        assert!(new(value!{{ COLUMNS => [] }}).is_err());
+    }
+}
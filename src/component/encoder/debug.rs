@@ -35,6 +35,10 @@ impl Factory for FactoryImpl {
             first: true,
         })
     }
+
+    fn line_oriented(&self) -> bool {
+        true
+    }
 }
 
 struct EncoderImpl {
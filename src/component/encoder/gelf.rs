@@ -0,0 +1,239 @@
+use flate2::Compression as Level;
+use flate2::write::{GzEncoder, ZlibEncoder};
+use serde_json as json;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::*;
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::event::*;
+use crate::util::json::to_json;
+use crate::util::sys::hostname;
+
+pub const NAME: &'static str = "gelf";
+
+const HOST: &'static str = "host";
+const COMPRESSION: &'static str = "compression";
+const COMPRESSION_NONE: &'static str = "none";
+const COMPRESSION_GZIP: &'static str = "gzip";
+const COMPRESSION_ZLIB: &'static str = "zlib";
+
+const MESSAGE_FIELD: &'static str = "message";
+const TIMESTAMP_FIELD: &'static str = "timestamp";
+
+/// Standard GELF fields that are taken from the event fields of the same name.
+const STANDARD_FIELDS: &[&str] = &["host", "full_message", "level", "facility", "line", "file"];
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::Encoder,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Arc<Factory>> {
+        let config = &ctx.config;
+
+        let host = if let Some(v) = config.get_opt_str(HOST)? {
+            v.to_owned()
+        } else {
+            hostname().unwrap_or_else(|| "localhost".into())
+        };
+
+        let compression = if let Some(v) = config.get_opt(COMPRESSION)? {
+            match v.as_str()? {
+                COMPRESSION_NONE => Compression::None,
+                COMPRESSION_GZIP => Compression::Gzip,
+                COMPRESSION_ZLIB => Compression::Zlib,
+                _ => return Err(v.new_error(format!(
+                    "`{}` must be one of [\"{}\", \"{}\", \"{}\"]",
+                    COMPRESSION, COMPRESSION_NONE, COMPRESSION_GZIP, COMPRESSION_ZLIB))),
+            }
+        } else {
+            Compression::None
+        };
+
+        Ok(Arc::new(FactoryImpl {
+            host,
+            compression,
+        }))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Compression {
+    None,
+    Gzip,
+    Zlib,
+}
+
+struct FactoryImpl {
+    /// Host used when the event has no `host` field.
+    host: String,
+    compression: Compression,
+}
+
+impl Factory for FactoryImpl {
+    fn new(&self) -> Box<Encoder> {
+        Box::new(EncoderImpl {
+            host: self.host.clone(),
+            compression: self.compression,
+        })
+    }
+}
+
+/// Encodes each event into a GELF 1.1 message. The `message` field becomes `short_message`,
+/// the fields other than the standard GELF fields become additional fields.
+struct EncoderImpl {
+    host: String,
+    compression: Compression,
+}
+
+impl EncoderImpl {
+    fn to_gelf(&self, event: &Event) -> json::Value {
+        let fields = event.fields();
+        let mut obj = json::Map::new();
+        obj.insert("version".into(), "1.1".into());
+        obj.insert("host".into(), self.host.clone().into());
+        obj.insert("short_message".into(), "".into());
+        obj.insert("timestamp".into(), timestamp(None));
+        for (k, v) in fields {
+            let k = k.as_str();
+            if k == MESSAGE_FIELD {
+                obj.insert("short_message".into(), to_string(v).into());
+            } else if k == TIMESTAMP_FIELD {
+                obj.insert("timestamp".into(), timestamp(Some(v)));
+            } else if STANDARD_FIELDS.contains(&k) {
+                obj.insert(k.into(), to_json(v));
+            } else {
+                let k = if k.starts_with('_') {
+                    k.to_owned()
+                } else {
+                    format!("_{}", k)
+                };
+                // `_id` is reserved.
+                if k == "_id" {
+                    continue;
+                }
+                let v = match v {
+                    Value::Int(_) | Value::Float(_) | Value::String(_) => to_json(v),
+                    _ => to_string(v).into(),
+                };
+                obj.insert(k, v);
+            }
+        }
+        json::Value::Object(obj)
+    }
+}
+
+/// Converts values that can't be GELF field values to strings.
+fn to_string(v: &Value) -> String {
+    match v {
+        Value::String(v) => v.clone(),
+        _ => to_json(v).to_string(),
+    }
+}
+
+/// Returns GELF timestamp from the timestamp field value or the current time.
+fn timestamp(v: Option<&Value>) -> json::Value {
+    let t = match v {
+        Some(Value::Int(v)) => return (*v).into(),
+        Some(Value::Float(v)) => return (*v).into(),
+        Some(Value::String(v)) => humantime::parse_rfc3339_weak(v).ok(),
+        _ => None,
+    }.unwrap_or_else(SystemTime::now);
+    let t = t.duration_since(UNIX_EPOCH).unwrap_or_default();
+    // Millisecond precision is what GELF producers normally use.
+    (t.as_secs() as f64 + (t.subsec_millis() as f64) / 1000.0).into()
+}
+
+impl Encoder for EncoderImpl {
+    fn encode(&mut self, event: &Event, out: &mut Vec<u8>) -> Result<()> {
+        let msg = self.to_gelf(event);
+        match self.compression {
+            Compression::None => json::to_writer(out, &msg).unwrap(),
+            Compression::Gzip => {
+                let mut w = GzEncoder::new(out, Level::default());
+                json::to_writer(&mut w, &msg).unwrap();
+                w.finish().unwrap();
+            }
+            Compression::Zlib => {
+                let mut w = ZlibEncoder::new(out, Level::default());
+                json::to_writer(&mut w, &msg).unwrap();
+                w.finish().unwrap();
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self, _out: &mut Vec<u8>) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::component::registry;
+
+    fn encode(config: Value, event: &Event) -> Vec<u8> {
+        let mut enc = ProviderImpl.new(New { config: config.into() }).unwrap().new();
+        let mut out = Vec::new();
+        enc.encode(event, &mut out).unwrap();
+        out
+    }
+
+    fn event() -> Event {
+        let mut e = Event::new();
+        let f = e.fields_mut();
+        f.insert("message".into(), Value::String("hi".into()));
+        f.insert("timestamp".into(), Value::String("2013-11-21T17:11:02.307Z".into()));
+        f.insert("level".into(), Value::Int(3));
+        f.insert("user_id".into(), Value::Int(9001));
+        f.insert("ok".into(), Value::Bool(true));
+        f.insert("id".into(), Value::Int(1));
+        f.insert("list".into(), value!([1, "a"]));
+        e
+    }
+
+    #[test]
+    fn fields() {
+        let out = encode(value!{{ HOST => "h" }}, &event());
+        let v: json::Value = json::from_slice(&out).unwrap();
+        assert_eq!(v, json::json!({
+            "version": "1.1",
+            "host": "h",
+            "short_message": "hi",
+            "timestamp": 1385053862.307,
+            "level": 3,
+            "_user_id": 9001,
+            "_ok": "true",
+            "_list": "[1,\"a\"]",
+        }));
+    }
+
+    #[test]
+    fn roundtrip() {
+        let dec = registry().event_decoder("gelf").unwrap()
+            .new(Default::default()).unwrap();
+        for &c in &[COMPRESSION_NONE, COMPRESSION_GZIP, COMPRESSION_ZLIB] {
+            let out = encode(value!{{ HOST => "h", COMPRESSION => c }}, &event());
+            let mut events = Vec::new();
+            dec.new().decode(&out, &mut events).unwrap();
+            let f = events[0].fields();
+            assert!(events[0].tags().is_empty());
+            assert_eq!(f["message"], Value::String("hi".into()));
+            assert_eq!(f["host"], Value::String("h".into()));
+            assert_eq!(f["user_id"], Value::Int(9001));
+            assert_eq!(f["timestamp"], Value::String("2013-11-21T17:11:02.307000000Z".into()));
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json as json;
+
+use super::*;
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::event::*;
+use crate::util::json::to_json;
+
+pub const NAME: &'static str = "json";
+
+const PRETTY: &'static str = "pretty";
+pub(super) const KEY_ORDER: &'static str = "key_order";
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::Encoder,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Arc<Factory>> {
+        let pretty = ctx.config.get_opt_bool(PRETTY)?.cloned().unwrap_or(false);
+        let key_order = key_order(&ctx.config)?;
+
+        Ok(Arc::new(FactoryImpl {
+            pretty,
+            key_order,
+        }))
+    }
+}
+
+/// Parses the `key_order` config option.
+pub(super) fn key_order(config: &Spanned<Value>) -> Result<Arc<Vec<String>>> {
+    let mut r = Vec::new();
+    if let Some(v) = config.get_opt(KEY_ORDER)? {
+        for v in v.as_list()? {
+            r.push(v.as_str()?.to_owned());
+        }
+    }
+    Ok(Arc::new(r))
+}
+
+struct FactoryImpl {
+    /// Whether to write each object on multiple indented lines.
+    pretty: bool,
+
+    key_order: Arc<Vec<String>>,
+}
+
+impl Factory for FactoryImpl {
+    fn new(&self) -> Box<Encoder> {
+        Box::new(EncoderImpl {
+            pretty: self.pretty,
+            key_order: self.key_order.clone(),
+            first: true,
+        })
+    }
+
+    fn line_oriented(&self) -> bool {
+        true
+    }
+}
+
+/// Encodes each event into a JSON object. The objects are separated with newlines.
+struct EncoderImpl {
+    pretty: bool,
+    key_order: Arc<Vec<String>>,
+    first: bool,
+}
+
+impl Encoder for EncoderImpl {
+    fn encode(&mut self, event: &Event, out: &mut Vec<u8>) -> Result<()> {
+        if !self.first {
+            out.push(b'\n');
+        } else {
+            self.first = false;
+        }
+        let fields = Fields::new(event, &self.key_order);
+        if self.pretty {
+            json::to_writer_pretty(out, &fields).unwrap();
+        } else {
+            json::to_writer(out, &fields).unwrap();
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self, _out: &mut Vec<u8>) -> Result<()> {
+        self.first = true;
+        Ok(())
+    }
+}
+
+/// Returns event fields in a stable order: first the fields listed in `key_order` in that order,
+/// then the rest of the fields sorted by name.
+pub(super) fn ordered_fields<'a>(event: &'a Event, key_order: &[String])
+    -> Vec<(&'a String, &'a Value)>
+{
+    let fields = event.fields();
+    let mut rest: Vec<_> = fields.iter()
+        .filter(|(k, _)| !key_order.contains(k))
+        .collect();
+    rest.sort_by(|a, b| a.0.cmp(b.0));
+    key_order.iter()
+        .filter_map(|k| fields.get_key_value(k))
+        .chain(rest)
+        .collect()
+}
+
+/// Serializes event fields as JSON object in the order of `ordered_fields()`. Keys of the nested
+/// maps are always sorted.
+pub(super) struct Fields<'a> {
+    event: &'a Event,
+    key_order: &'a [String],
+}
+
+impl<'a> Fields<'a> {
+    pub fn new(event: &'a Event, key_order: &'a [String]) -> Self {
+        Self {
+            event,
+            key_order,
+        }
+    }
+}
+
+impl<'a> Serialize for Fields<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let fields = ordered_fields(self.event, self.key_order);
+        let mut map = serializer.serialize_map(Some(fields.len()))?;
+        for (k, v) in fields {
+            map.serialize_entry(k, &to_json(v))?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn encode(config: Value, events: &[Event]) -> String {
+        let mut enc = ProviderImpl.new(New { config: config.into() }).unwrap().new();
+        let mut out = Vec::new();
+        for e in events {
+            enc.encode(e, &mut out).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    fn event() -> Event {
+        let mut e = Event::new();
+        let f = e.fields_mut();
+        f.insert("message".into(), Value::String("a \"quoted\"\n\u{1}é".into()));
+        f.insert("b".into(), Value::Bool(true));
+        f.insert("i".into(), Value::Int(-1));
+        f.insert("f".into(), Value::Float(1.0));
+        f.insert("nan".into(), Value::Float(std::f64::NAN));
+        f.insert("m".into(), value!({"z" => 1, "a" => [1.5, "x"]}));
+        e
+    }
+
+    #[test]
+    fn compact() {
+        assert_eq!(encode(value!{{}}, &[event(), Event::new()]), concat!(
+            r#"{"b":true,"f":1.0,"i":-1,"m":{"a":[1.5,"x"],"z":1},"#,
+            r#""message":"a \"quoted\"\n\u0001é","nan":null}"#, "\n{}"));
+    }
+
+    #[test]
+    fn key_order() {
+        assert_eq!(encode(value!{{ KEY_ORDER => ["message", "missing", "i"] }}, &[event()]),
+            concat!(r#"{"message":"a \"quoted\"\n\u0001é","i":-1,"b":true,"f":1.0,"#,
+                r#""m":{"a":[1.5,"x"],"z":1},"nan":null}"#));
+    }
+
+    #[test]
+    fn pretty() {
+        let mut e = Event::new();
+        e.fields_mut().insert("b".into(), Value::Int(1));
+        e.fields_mut().insert("a".into(), value!([true]));
+        let obj = "{\n  \"a\": [\n    true\n  ],\n  \"b\": 1\n}";
+        assert_eq!(encode(value!{{ PRETTY => true }}, &[e.clone(), e]),
+            format!("{}\n{}", obj, obj));
+    }
+}
@@ -0,0 +1,86 @@
+use serde_json as json;
+
+use super::*;
+use super::json::{Fields, key_order};
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::event::*;
+
+pub const NAME: &'static str = "json_lines";
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::Encoder,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Arc<Factory>> {
+        let key_order = key_order(&ctx.config)?;
+
+        Ok(Arc::new(FactoryImpl {
+            key_order,
+        }))
+    }
+}
+
+struct FactoryImpl {
+    key_order: Arc<Vec<String>>,
+}
+
+impl Factory for FactoryImpl {
+    fn new(&self) -> Box<Encoder> {
+        Box::new(EncoderImpl {
+            key_order: self.key_order.clone(),
+        })
+    }
+
+    fn line_oriented(&self) -> bool {
+        true
+    }
+}
+
+/// Encodes each event into a single-line JSON object terminated with newline
+/// (http://jsonlines.org).
+struct EncoderImpl {
+    key_order: Arc<Vec<String>>,
+}
+
+impl Encoder for EncoderImpl {
+    fn encode(&mut self, event: &Event, out: &mut Vec<u8>) -> Result<()> {
+        json::to_writer(&mut *out, &Fields::new(event, &self.key_order)).unwrap();
+        out.push(b'\n');
+        Ok(())
+    }
+
+    fn flush(&mut self, _out: &mut Vec<u8>) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::json::KEY_ORDER;
+
+    #[test]
+    fn lines() {
+        let mut enc = ProviderImpl.new(New { config: value!{{ KEY_ORDER => ["b"] }}.into() })
+            .unwrap().new();
+        let mut e = Event::new();
+        e.fields_mut().insert("a".into(), Value::String("x\ny".into()));
+        e.fields_mut().insert("b".into(), Value::Float(0.1));
+        let mut out = Vec::new();
+        enc.encode(&e, &mut out).unwrap();
+        enc.encode(&Event::new(), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "{\"b\":0.1,\"a\":\"x\\ny\"}\n{}\n");
+    }
+}
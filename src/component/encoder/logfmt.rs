@@ -0,0 +1,155 @@
+use super::*;
+use super::json::{key_order, ordered_fields};
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::event::*;
+use crate::util::json::to_text;
+
+pub const NAME: &'static str = "logfmt";
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::Encoder,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Arc<Factory>> {
+        let key_order = key_order(&ctx.config)?;
+
+        Ok(Arc::new(FactoryImpl {
+            key_order,
+        }))
+    }
+}
+
+struct FactoryImpl {
+    /// Fields written first in that order, the rest of the fields are sorted by name.
+    key_order: Arc<Vec<String>>,
+}
+
+impl Factory for FactoryImpl {
+    fn new(&self) -> Box<Encoder> {
+        Box::new(EncoderImpl {
+            key_order: self.key_order.clone(),
+        })
+    }
+
+    fn line_oriented(&self) -> bool {
+        true
+    }
+}
+
+/// Encodes each event into a line of space-separated `key=value` pairs terminated with newline.
+/// Values are quoted when needed, lists and maps are written as JSON.
+struct EncoderImpl {
+    key_order: Arc<Vec<String>>,
+}
+
+impl Encoder for EncoderImpl {
+    fn encode(&mut self, event: &Event, out: &mut Vec<u8>) -> Result<()> {
+        let mut line = String::new();
+        for (k, v) in ordered_fields(event, &self.key_order) {
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            write_key(k, &mut line);
+            line.push('=');
+            match v {
+                Value::Bool(_) | Value::Int(_) | Value::Float(_) => line.push_str(&to_text(v)),
+                _ => write_value(&to_text(v), &mut line),
+            }
+        }
+        out.extend_from_slice(line.as_bytes());
+        out.push(b'\n');
+        Ok(())
+    }
+
+    fn flush(&mut self, _out: &mut Vec<u8>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes the key replacing characters that can't appear in logfmt keys with `_`.
+fn write_key(k: &str, out: &mut String) {
+    if k.is_empty() {
+        out.push('_');
+    }
+    out.extend(k.chars().map(|c| if c <= ' ' || c == '=' || c == '"' { '_' } else { c }));
+}
+
+fn write_value(v: &str, out: &mut String) {
+    let quote = v.is_empty() || v.chars().any(|c| c <= ' ' || c == '=' || c == '"');
+    if !quote {
+        out.push_str(v);
+        return;
+    }
+    out.push('"');
+    for c in v.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::json::KEY_ORDER;
+    use crate::component::registry;
+
+    fn encode(config: Value, event: &Event) -> String {
+        let mut enc = ProviderImpl.new(New { config: config.into() }).unwrap().new();
+        let mut out = Vec::new();
+        enc.encode(event, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn event() -> Event {
+        let mut e = Event::new();
+        let f = e.fields_mut();
+        f.insert("level".into(), Value::String("info".into()));
+        f.insert("msg".into(), Value::String("hello \"world\"\n".into()));
+        f.insert("n".into(), Value::Int(-12));
+        f.insert("f".into(), Value::Float(1.5));
+        f.insert("ok".into(), Value::Bool(true));
+        f.insert("empty".into(), Value::String("".into()));
+        f.insert("a key".into(), value!(["x"]));
+        e
+    }
+
+    #[test]
+    fn fields() {
+        assert_eq!(encode(value!{{ KEY_ORDER => ["level", "msg"] }}, &event()), concat!(
+            r#"level=info msg="hello \"world\"\n" a_key="[\"x\"]" empty="" f=1.5 n=-12 ok=true"#,
+            "\n"));
+        assert_eq!(encode(value!{{}}, &Event::new()), "\n");
+    }
+
+    #[test]
+    fn roundtrip() {
+        let inp = encode(value!{{}}, &event());
+        let mut dec = registry().event_decoder("kv").unwrap()
+            .new(Default::default()).unwrap().new();
+        let mut events = Vec::new();
+        dec.decode(inp.trim_end().as_bytes(), &mut events).unwrap();
+        let mut exp = event();
+        let l = exp.fields_mut().remove("a key").unwrap();
+        exp.fields_mut().insert("a_key".into(), Value::String(to_text(&l).into()));
+        assert_eq!(events[0].fields(), exp.fields());
+    }
+}
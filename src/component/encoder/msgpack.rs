@@ -0,0 +1,142 @@
+use rmpv::Value as MsgPack;
+
+use super::*;
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::event::*;
+use crate::util::msgpack::to_msgpack;
+
+pub const NAME: &'static str = "msgpack";
+
+const LENGTH_PREFIXED: &'static str = "length_prefixed";
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::Encoder,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Arc<Factory>> {
+        let length_prefixed = ctx.config.get_opt_bool(LENGTH_PREFIXED)?.cloned().unwrap_or(false);
+
+        Ok(Arc::new(FactoryImpl {
+            length_prefixed,
+        }))
+    }
+}
+
+struct FactoryImpl {
+    /// Whether to prefix each value with its length as 4-byte big-endian integer, the framing
+    /// expected by the `length_prefixed` frame decoder with the default config.
+    length_prefixed: bool,
+}
+
+impl Factory for FactoryImpl {
+    fn new(&self) -> Box<Encoder> {
+        Box::new(EncoderImpl {
+            length_prefixed: self.length_prefixed,
+        })
+    }
+}
+
+/// Encodes each event into a MessagePack map of the event fields.
+struct EncoderImpl {
+    length_prefixed: bool,
+}
+
+impl Encoder for EncoderImpl {
+    fn encode(&mut self, event: &Event, out: &mut Vec<u8>) -> Result<()> {
+        let value = MsgPack::Map(event.fields().iter()
+            .map(|(k, v)| (MsgPack::from(k.as_str()), to_msgpack(v)))
+            .collect());
+        let start = out.len();
+        if self.length_prefixed {
+            out.extend_from_slice(&[0; 4]);
+        }
+        rmpv::encode::write_value(out, &value).unwrap();
+        if self.length_prefixed {
+            let len = (out.len() - start - 4) as u32;
+            for (i, b) in out[start..start + 4].iter_mut().enumerate() {
+                *b = (len >> (24 - i * 8)) as u8;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self, _out: &mut Vec<u8>) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::component::decoder::frame::{Decode, New as FrameNew};
+    use crate::component::registry;
+
+    fn encode(config: Value, events: &[Event]) -> Vec<u8> {
+        let mut enc = ProviderImpl.new(New { config: config.into() }).unwrap().new();
+        let mut out = Vec::new();
+        for e in events {
+            enc.encode(e, &mut out).unwrap();
+        }
+        out
+    }
+
+    fn event() -> Event {
+        let mut e = Event::new();
+        let f = e.fields_mut();
+        f.insert("s".into(), Value::String("str".into()));
+        f.insert("i".into(), Value::Int(-1));
+        f.insert("f".into(), Value::Float(1.5));
+        f.insert("b".into(), Value::Bool(true));
+        f.insert("bytes".into(), Value::Bytes(vec![0, 0xff]));
+        f.insert("l".into(), value!([1, "a", [true]]));
+        f.insert("m".into(), value!({"a" => {"b" => 2.5}}));
+        e
+    }
+
+    fn roundtrip(frame_decoder: &str, config: Value) {
+        let events = vec![event(), Event::new(), event()];
+        let inp = encode(config, &events);
+
+        let mut frame_dec = registry().frame_decoder(frame_decoder).unwrap()
+            .new(FrameNew::default()).unwrap().new();
+        let mut frames = Vec::new();
+        assert_eq!(frame_dec.decode(&inp, &mut frames).unwrap(), Decode {
+            read: inp.len(),
+            written: events.len(),
+        });
+
+        let mut dec = registry().event_decoder(NAME).unwrap()
+            .new(Default::default()).unwrap().new();
+        let mut decoded = Vec::new();
+        for f in frames {
+            assert_eq!(dec.decode(f.data, &mut decoded).unwrap(), 1);
+        }
+        assert_eq!(decoded.len(), events.len());
+        for (d, e) in decoded.iter().zip(&events) {
+            assert!(d.tags().is_empty());
+            assert_eq!(d.fields(), e.fields());
+        }
+    }
+
+    #[test]
+    fn roundtrip_streaming() {
+        roundtrip("msgpack", value!{{}});
+    }
+
+    #[test]
+    fn roundtrip_length_prefixed() {
+        roundtrip("length_prefixed", value!{{ LENGTH_PREFIXED => true }});
+    }
+}
@@ -0,0 +1,104 @@
+use super::*;
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::event::*;
+use crate::util::template::Template;
+
+pub const NAME: &'static str = "template";
+
+const FORMAT: &'static str = "format";
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::Encoder,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Arc<Factory>> {
+        let template = Template::parse(ctx.config.get(FORMAT)?)?;
+
+        Ok(Arc::new(FactoryImpl {
+            template: Arc::new(template),
+        }))
+    }
+}
+
+struct FactoryImpl {
+    template: Arc<Template>,
+}
+
+impl Factory for FactoryImpl {
+    fn new(&self) -> Box<Encoder> {
+        Box::new(EncoderImpl {
+            template: self.template.clone(),
+            buf: String::new(),
+            first: true,
+        })
+    }
+
+    fn line_oriented(&self) -> bool {
+        true
+    }
+}
+
+/// Renders the template for each event. The rendered events are separated with newlines.
+struct EncoderImpl {
+    template: Arc<Template>,
+    buf: String,
+    first: bool,
+}
+
+impl Encoder for EncoderImpl {
+    fn encode(&mut self, event: &Event, out: &mut Vec<u8>) -> Result<()> {
+        if !self.first {
+            out.push(b'\n');
+        } else {
+            self.first = false;
+        }
+        self.buf.clear();
+        self.template.render(event, &mut self.buf);
+        out.extend_from_slice(self.buf.as_bytes());
+        Ok(())
+    }
+
+    fn flush(&mut self, _out: &mut Vec<u8>) -> Result<()> {
+        self.first = true;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode() {
+        let mut enc = ProviderImpl.new(New {
+            config: value!{{ FORMAT => "%{timestamp} [%{level|upper}] %{message}" }}.into(),
+        }).unwrap().new();
+        let mut e = Event::new();
+        e.fields_mut().insert("timestamp".into(), Value::String("2019-01-02T03:04:05Z".into()));
+        e.fields_mut().insert("level".into(), Value::String("info".into()));
+        e.fields_mut().insert("message".into(), Value::String("hello".into()));
+        let mut out = Vec::new();
+        enc.encode(&e, &mut out).unwrap();
+        enc.encode(&Event::new(), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "2019-01-02T03:04:05Z [INFO] hello\n [] ");
+    }
+
+    #[test]
+    fn invalid_config() {
+        let new = |config: Value| ProviderImpl.new(New { config: config.into() });
+        assert!(new(value!{{}}).is_err());
+        assert!(new(value!{{ FORMAT => "%{a|bad}" }}).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 pub mod regex;
 
+use std::fmt;
 use std::sync::Arc;
 
 use super::*;
@@ -27,5 +28,20 @@ pub trait Starter: 'static + Send + Sync {
 }
 
 pub trait Filter: 'static + Send {
+    /// Filters the `event`. A filter failing on the event should hand it back as the
+    /// `FailedEvent` details of the error so it can be sent to the dead letter queue.
     fn filter(&mut self, event: Event) -> BoxStream<Event, Error>;
+}
+
+/// Error details of a filter failed on the `event`. Displayed as the `message`.
+#[derive(Debug)]
+pub struct FailedEvent {
+    pub message: String,
+    pub event: Event,
+}
+
+impl fmt::Display for FailedEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
 }
\ No newline at end of file
@@ -1,18 +1,34 @@
+pub mod dead_letter;
 pub mod file;
 
+use std::sync::Arc;
+
 use super::*;
+use crate::dead_letter::{DeadLetterQueue, DeadLetters};
 use crate::error::Error;
 use crate::event::*;
+use crate::metric::Metrics;
 use crate::util::futures::*;
 
 #[derive(Default)]
 pub struct CommonConfig {
     pub id: Option<String>,
+
+    /// Queue for the frames the input fails to decode.
+    pub dead_letter_queue: Option<Arc<DeadLetterQueue>>,
+}
+
+impl CommonConfig {
+    /// Returns the dead letter queue handle for the input with the `id`.
+    pub fn dead_letters(&self, id: &str) -> Option<DeadLetters> {
+        self.dead_letter_queue.as_ref().map(|q| DeadLetters::new(q.clone(), id))
+    }
 }
 
 pub struct New {
     pub config: Spanned<Value>,
     pub common_config: CommonConfig,
+    pub metrics: Arc<Metrics>,
 }
 
 pub struct Started {
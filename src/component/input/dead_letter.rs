@@ -0,0 +1,262 @@
+use futures::prelude::*;
+use futures::{future, stream, try_ready};
+use log::*;
+use stream_cancel::{StreamExt as ScStreamExt};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+
+use super::*;
+use crate::component::{registry, split_component_config, ComponentKind, Metadata,
+    Provider as CProvider};
+use crate::component::decoder;
+use crate::dead_letter::DeadLetter;
+use crate::error::*;
+use crate::event::*;
+use crate::util::futures::future::blocking;
+
+pub const NAME: &'static str = "dead_letter";
+
+const PATH: &'static str = "path";
+const COMPONENT_ID: &'static str = "component_id";
+const CODEC: &'static str = "codec";
+
+const DEFAULT_CODEC: &'static str = "text";
+
+/// Max number of dead letters read at once.
+const CHUNK_LEN: usize = 1000;
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::Input,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Box<Input>> {
+        let mut config = ctx.config;
+        let decoder = if let Some(v) = config.remove_opt(CODEC)? {
+            let (name, config) = split_component_config(v)?;
+            registry().event_decoder(&name)
+                .ok_or_else(|| name.new_error(format!("unknown event decoder `{}`", *name)))?
+                .new(decoder::event::New { config })?
+        } else {
+            registry().event_decoder(DEFAULT_CODEC).unwrap().new(Default::default())?
+        };
+        Ok(Box::new(DeadLetterInput {
+            config: Arc::new(Config {
+                id: ctx.common_config.id.unwrap_or_else(|| NAME.into()),
+                path: config.get(PATH)?.as_str()?.into(),
+                component_id: config.get_opt_str(COMPONENT_ID)?.map(|s| s.to_owned()),
+                decoder,
+            }),
+        }))
+    }
+}
+
+struct Config {
+    /// Used as the log context.
+    id: String,
+
+    /// Dead letter queue file.
+    path: PathBuf,
+
+    /// Replay only the events failed in the component with this id.
+    component_id: Option<String>,
+
+    /// Decodes the frames the inputs failed to decode. The frames of text were recorded in UTF-8,
+    /// so the charset of the decoder doesn't matter.
+    decoder: Arc<decoder::event::Factory>,
+}
+
+/// Replays the events from the dead letter queue file. The file is read once from the beginning
+/// and the input finishes at its end. The frames the inputs failed to decode are decoded again
+/// with the codec.
+struct DeadLetterInput {
+    config: Arc<Config>,
+}
+
+impl Input for DeadLetterInput {
+    fn start(&self) -> BoxFuture<Started, Error> {
+        let file = match File::open(&self.config.path) {
+            Ok(v) => v,
+            Err(e) => return Box::new(future::err(e.wrap_id(ErrorId::Io)
+                .with_context(format!("opening {:?}", self.config.path)))),
+        };
+        info!("[{}] replaying dead letters from {:?}", self.config.id, self.config.path);
+        let mut reader = Reader {
+            config: self.config.clone(),
+            decoder: self.config.decoder.new(),
+            file: BufReader::new(file),
+            line_num: 0,
+        };
+        let (shutdown_tx, shutdown_rx) = signal::signal();
+        let stream = stream::poll_fn(move || {
+                let r = try_ready!(blocking(|| reader.read_chunk()).poll()
+                    .map_err(|_| Error::new(ErrorId::Unknown, "blocking read failed")));
+                r.map(Async::Ready)
+            })
+            .map(stream::iter_ok)
+            .flatten()
+            .take_until(shutdown_rx);
+        Box::new(future::ok(Started {
+            stream: Box::new(stream),
+            shutdown: shutdown_tx,
+        }))
+    }
+}
+
+struct Reader {
+    config: Arc<Config>,
+    decoder: Box<decoder::event::Decoder>,
+    file: BufReader<File>,
+    line_num: usize,
+}
+
+impl Reader {
+    /// Reads the next chunk of the events. Returns `None` at the end of the file. The invalid
+    /// dead letters and the frames that fail to decode again are logged and skipped.
+    fn read_chunk(&mut self) -> Result<Option<Vec<Event>>> {
+        let mut r = Vec::new();
+        let mut line = Vec::new();
+        while r.len() < CHUNK_LEN {
+            line.clear();
+            if self.file.read_until(b'\n', &mut line).wrap_err_id(ErrorId::Io)? == 0 {
+                if r.is_empty() {
+                    info!("[{}] replayed all dead letters from {:?}", self.config.id,
+                        self.config.path);
+                    return Ok(None);
+                }
+                break;
+            }
+            self.line_num += 1;
+            if line.iter().all(|b| b.is_ascii_whitespace()) {
+                continue;
+            }
+            match DeadLetter::parse(&line) {
+                Ok(letter) => {
+                    let matches = self.config.component_id.as_ref()
+                        .map(|id| *id == letter.component_id)
+                        .unwrap_or(true);
+                    if !matches {
+                        continue;
+                    }
+                    if let Some(frame) = letter.frame {
+                        let len = r.len();
+                        if let Err(e) = self.decode(&frame, &mut r) {
+                            r.truncate(len);
+                            error!("[{}] skipping dead letter at {:?}:{}: {}",
+                                self.config.id, self.config.path, self.line_num, e);
+                        }
+                    } else {
+                        r.push(letter.event);
+                    }
+                }
+                Err(e) => error!("[{}] skipping invalid dead letter at {:?}:{}: {}",
+                    self.config.id, self.config.path, self.line_num, e),
+            }
+        }
+        Ok(Some(r))
+    }
+
+    fn decode(&mut self, frame: &[u8], out: &mut Vec<Event>) -> Result<()> {
+        self.decoder.decode(frame, out)?;
+        self.decoder.flush(out)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+    use crate::util::test::TempDir;
+
+    fn new(config: Value) -> Result<Box<Input>> {
+        ProviderImpl.new(New {
+            config: config.into(),
+            common_config: Default::default(),
+            metrics: Default::default(),
+        })
+    }
+
+    fn event(message: &str) -> Event {
+        let mut e = Event::new();
+        e.fields_mut().insert("message".into(), Value::String(message.into()));
+        e
+    }
+
+    fn replay(config: Value) -> Vec<Event> {
+        let input = new(config).unwrap();
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(input.start()
+            .and_then(|started| started.stream.collect()))
+            .unwrap()
+    }
+
+    #[test]
+    fn replay_events() {
+        let dir = TempDir::new("dead-letter-input");
+        let error = Error::new(ErrorId::Parse, "failed");
+        let mut letters = Vec::new();
+        for (component_id, message) in &[("foo", "0"), ("bar", "1"), ("foo", "2")] {
+            letters.extend(DeadLetter::new(*component_id, &error, event(message)).to_json());
+            letters.push(b'\n');
+            if *message == "1" {
+                letters.extend_from_slice(b"invalid\n\n");
+            }
+        }
+        fs::write(dir.path("dlq.log"), letters).unwrap();
+
+        let all = replay(value!{{ PATH => dir.path("dlq.log") }});
+        let foo = replay(value!{{ PATH => dir.path("dlq.log"), COMPONENT_ID => "foo" }});
+
+        assert_eq!(all, vec![event("0"), event("1"), event("2")]);
+        assert_eq!(foo, vec![event("0"), event("2")]);
+    }
+
+    #[test]
+    fn replay_frames() {
+        let dir = TempDir::new("dead-letter-input-frames");
+        let error = Error::new(ErrorId::Parse, "failed");
+        let mut letters = Vec::new();
+        for frame in &["{\"message\":\"0\"}", "invalid", "{\"message\":\"1\"}"] {
+            letters.extend(DeadLetter::from_frame("file", &error, frame.as_bytes().into())
+                .to_json());
+            letters.push(b'\n');
+        }
+        fs::write(dir.path("dlq.log"), letters).unwrap();
+
+        let text = replay(value!{{ PATH => dir.path("dlq.log") }});
+        let json = replay(value!{{ PATH => dir.path("dlq.log"), CODEC => "json" }});
+
+        assert_eq!(text, vec![event("{\"message\":\"0\"}"), event("invalid"),
+            event("{\"message\":\"1\"}")]);
+        assert_eq!(json.len(), 3);
+        assert_eq!((&json[0], &json[2]), (&event("0"), &event("1")));
+        assert!(json[1].tags().contains_key(decoder::event::json::PARSE_FAILURE_TAG));
+    }
+
+    #[test]
+    fn missing_file() {
+        let input = new(value!{{ PATH => "/nonexistent/dead-letters.log" }}).unwrap();
+        assert!(input.start().wait().is_err());
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert!(new(value!{{}}).is_err());
+        assert!(new(value!{{ PATH => 1 }}).is_err());
+        assert!(new(value!{{ PATH => "dlq.log", CODEC => "unknown" }}).is_err());
+    }
+}
@@ -12,21 +12,24 @@ use std::mem;
 use std::os::unix::fs::FileExt;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::executor;
 use tokio::timer::Interval;
 
 use super::*;
-use crate::component::{ComponentKind, Metadata, Provider as CProvider};
-use crate::component::decoder::BufDecoder;
+use crate::component::{split_component_config, ComponentKind, Metadata, Provider as CProvider};
+use crate::component::decoder::{BufDecoder, Pos};
 use crate::error::*;
 use crate::event::*;
+use crate::metric::Metrics;
 use crate::util::futures::{*, stream::StreamExt};
 use crate::util::futures::future::blocking;
 use crate::value::*;
 
 pub const NAME: &'static str = "file";
 
+const DEFAULT_FLUSH_IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub fn provider() -> Box<Provider> {
     Box::new(ProviderImpl)
 }
@@ -45,7 +48,7 @@ impl CProvider for ProviderImpl {
 impl Provider for ProviderImpl {
     fn new(&self, ctx: New) -> Result<Box<Input>> {
         Ok(Box::new(FileInput {
-            config: Config::parse(ctx.config, ctx.common_config)?,
+            config: Config::parse(ctx.config, ctx.common_config, &ctx.metrics)?,
         }))
     }
 }
@@ -56,16 +59,89 @@ enum StartFrom {
     End,
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Mode {
+    /// Files are expected to grow, partial frames at EOF are flushed after the idle timeout.
+    Tail,
+
+    /// Files are complete, partial frames at EOF are flushed immediately.
+    Read,
+}
+
+/// File metadata that can be added to the events.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum MetaField {
+    Path,
+
+    /// Offset of the event start in the decoded (e.g. decompressed) file contents. When reading
+    /// starts at the end of the file, the offset is counted from there since the decoded length
+    /// of the skipped contents is unknown.
+    Offset,
+
+    /// 1-based line number of the event start. When reading starts at the end of the file,
+    /// the lines are counted from there.
+    Line,
+
+    Device,
+    Inode,
+
+    /// File modification time in RFC 3339 format. Omitted for the times before 1970.
+    Mtime,
+
+    /// The path pattern that matched the file.
+    Pattern,
+}
+
+impl MetaField {
+    const ALL: &'static [MetaField] = &[
+        MetaField::Path,
+        MetaField::Offset,
+        MetaField::Line,
+        MetaField::Device,
+        MetaField::Inode,
+        MetaField::Mtime,
+        MetaField::Pattern,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            MetaField::Path => "path",
+            MetaField::Offset => "offset",
+            MetaField::Line => "line",
+            MetaField::Device => "device",
+            MetaField::Inode => "inode",
+            MetaField::Mtime => "mtime",
+            MetaField::Pattern => "pattern",
+        }
+    }
+
+    fn parse(value: &Spanned<Value>) -> Result<Self> {
+        let s = value.as_str()?;
+        Self::ALL.iter().cloned()
+            .find(|f| f.name() == s)
+            .ok_or_else(|| value.new_error(format!("expected one of [{}]",
+                itertools::join(Self::ALL.iter().map(|f| format!("\"{}\"", f.name())), ", "))))
+    }
+}
+
 #[derive(Clone)]
 struct Config {
     path_patterns: Vec<String>,
     start_from: StartFrom,
+    mode: Mode,
+    flush_idle_timeout: Duration,
+
+    /// Metadata fields to add to the events along with the target field names.
+    meta_fields: Vec<(MetaField, String)>,
+
     stream_decoder: Arc<decoder::stream::Factory>,
     frame_event_decoder: Arc<decoder::frame_event::Factory>,
 }
 
 impl Config {
-    fn parse(mut value: Spanned<Value>, _common: CommonConfig) -> Result<Self> {
+    fn parse(mut value: Spanned<Value>, common: CommonConfig, metrics: &Arc<Metrics>)
+        -> Result<Self>
+    {
         let path_pattern_strs = value.remove("path")?.into_list()?;
         let mut path_patterns = Vec::new();
         for p in path_pattern_strs {
@@ -84,15 +160,80 @@ impl Config {
             StartFrom::Beginning
         };
 
+        let mode = if let Some(s) = value.remove_opt("mode")? {
+            match s.as_str()? {
+                "tail" => Mode::Tail,
+                "read" => Mode::Read,
+                _ => return Err(ErrorDetails::new("expected one of [\"tail\", \"read\"]", s.span.clone()))
+                    .wrap_err_id(ErrorId::Parse),
+            }
+        } else {
+            Mode::Tail
+        };
+
+        let flush_idle_timeout = value.get_opt_duration("flush_idle_timeout")?
+            .unwrap_or(DEFAULT_FLUSH_IDLE_TIMEOUT);
+
+        let meta_field_prefix = value.remove_opt("metadata_prefix")?
+            .map(|v| v.into_string())
+            .transpose()?
+            .unwrap_or_default();
+        let meta_fields = if let Some(v) = value.remove_opt("metadata")? {
+            let mut meta_fields: Vec<(MetaField, String)> = Vec::new();
+            for v in v.into_list()? {
+                let field = MetaField::parse(&v)?;
+                if meta_fields.iter().any(|&(f, _)| f == field) {
+                    return Err(v.new_error("duplicate metadata field"));
+                }
+                meta_fields.push((field, format!("{}{}", meta_field_prefix, field.name())));
+            }
+            meta_fields
+        } else {
+            vec![(MetaField::Path, format!("{}{}", meta_field_prefix, MetaField::Path.name()))]
+        };
+
+        let frame_decoder = if let Some(v) = value.remove_opt("frame_decoder")? {
+            let (name, config) = split_component_config(v)?;
+            registry().frame_decoder(&name)
+                .ok_or_else(|| name.new_error(format!("unknown frame decoder `{}`", *name)))?
+                .new(decoder::frame::New { config, metrics: metrics.clone() })?
+        } else {
+            registry().frame_decoder("delimited").unwrap().new(decoder::frame::New {
+                config: Default::default(),
+                metrics: metrics.clone(),
+            })?
+        };
+        // Let the frame decoder flush its pending frames sooner if it wants to.
+        let flush_idle_timeout = frame_decoder.flush_timeout()
+            .map(|t| cmp::min(t, flush_idle_timeout))
+            .unwrap_or(flush_idle_timeout);
+
+        let event_decoder = if let Some(v) = value.remove_opt("event_decoder")? {
+            let (name, config) = split_component_config(v)?;
+            registry().event_decoder(&name)
+                .ok_or_else(|| name.new_error(format!("unknown event decoder `{}`", *name)))?
+                .new(decoder::event::New { config })?
+        } else {
+            registry().event_decoder("text").unwrap().new(Default::default())?
+        };
         let stream_decoder = registry().stream_decoder("gzip").unwrap().new(Default::default())?;
+        let stream_decoder = if let Some(charset) = event_decoder.charset() {
+            decoder::stream::charset::factory(stream_decoder, charset)
+        } else {
+            stream_decoder
+        };
+
+        let dead_letters = common.dead_letters(common.id.as_ref().map(|s| s.as_str())
+            .unwrap_or(NAME));
         let frame_event_decoder = decoder::frame_event::composite::factory(
-            registry().frame_decoder("delimited").unwrap().new(Default::default())?,
-            registry().event_decoder("text").unwrap().new(Default::default())?,
-        );
+            frame_decoder, event_decoder, dead_letters);
 
         Ok(Self {
             path_patterns,
             start_from,
+            mode,
+            flush_idle_timeout,
+            meta_fields,
             stream_decoder,
             frame_event_decoder,
         })
@@ -114,6 +255,9 @@ impl Input for FileInput {
         let frame_event_decoder = self.config.frame_event_decoder.clone();
         let path_patterns = Arc::new(self.config.path_patterns.clone());
         let start_from = self.config.start_from;
+        let mode = self.config.mode;
+        let flush_idle_timeout = self.config.flush_idle_timeout;
+        let meta_fields = Arc::new(self.config.meta_fields.clone());
 
         executor::spawn(Interval::new(Instant::now(), Duration::from_secs(5))
             .take_until(shutdown_rx.clone().map(|_| {}))
@@ -121,42 +265,50 @@ impl Input for FileInput {
             .and_then(clone!(path_patterns => move |_| {
                 blocking(clone!(path_patterns => move || {
                     let mut discovered_files = Vec::new();
-                    for path_pattern in path_patterns.iter() {
+                    for (pattern_idx, path_pattern) in path_patterns.iter().enumerate() {
                         debug!("discovering files in {}", path_pattern);
                         for path in try_cont!(glob::glob(path_pattern).map_err(|e| error!("{}", e))) {
                             let path = try_cont!(path.map_err(|e| error!("{}", e)));
                             let stat = try_cont!(stat(&path)
                                 .map_err(|e| error!("couldn't get file stat: {}", e)));
-                            discovered_files.push((path, stat));
+                            discovered_files.push((path, pattern_idx, stat));
                         }
                     }
                     discovered_files
                 }))
             }))
-            .for_each(clone!(stateh, stream_decoder, frame_event_decoder => move |discovered_files| {
+            .for_each(clone!(stateh, path_patterns, stream_decoder, frame_event_decoder,
+                    meta_fields => move |discovered_files| {
                 if discovered_files.is_empty() {
                     return Ok(());
                 }
 
                 let mut trigger = false;
                 let mut state = stateh.lock();
-                for (path, stat) in discovered_files {
+                for (path, pattern_idx, stat) in discovered_files {
                     let next_idx = state.files.len();
                     let idx = *state.file_id_to_idx.entry(stat.id).or_insert(next_idx);
                     if idx == state.files.len() {
                         debug!("discovered new file: {:?} {:?}", path, stat);
+                        let offset = match start_from {
+                            StartFrom::Beginning => 0,
+                            StartFrom::End => stat.len,
+                        };
+                        let decoder = BufDecoder::new(
+                            stream_decoder.new(),
+                            frame_event_decoder.new());
                         state.files.push(Arc::new(Mutex::new(WatchedFile {
                             id: stat.id,
                             path,
+                            pattern: path_patterns[pattern_idx].clone(),
                             file: None,
-                            offset: match start_from {
-                                StartFrom::Beginning => 0,
-                                StartFrom::End => stat.len,
-                            },
+                            offset,
                             len: stat.len,
-                            decoder: BufDecoder::new(
-                                stream_decoder.new(),
-                                frame_event_decoder.new()),
+                            mtime: stat.mtime,
+                            last_read: Instant::now(),
+                            needs_flush: false,
+                            decoder,
+                            meta_fields: meta_fields.clone(),
                         })));
                         trigger = true;
                     } else {
@@ -231,7 +383,20 @@ impl Input for FileInput {
                 if file.offset == file.len {
                     trace!("[{:?}] file.offset == file.len: {}", file.path, file.offset);
                     state.next_file();
-                    return future::ok(stream::empty().into_box()).into_box();
+
+                    let flush = file.needs_flush && match mode {
+                        Mode::Tail => file.last_read.elapsed() >= flush_idle_timeout,
+                        Mode::Read => true,
+                    };
+                    if !flush {
+                        return future::ok(stream::empty().into_box()).into_box();
+                    }
+
+                    trace!("[{:?}] flushing decoder", file.path);
+                    return blocking(clone!(fileh => move || fileh.lock().flush()))
+                        .infallible()
+                        .and_then(|events| Ok(stream::iter_ok(events?).into_box()))
+                        .into_box();
                 }
 
                 trace!("[{:?}] file.offset < file.len: {} < {}",
@@ -262,6 +427,24 @@ impl Input for FileInput {
                 }
             }))
             .flatten()
+            .chain(future::lazy(clone!(stateh => move || {
+                    let files = stateh.lock().files.clone();
+                    blocking(move || {
+                        let mut events = Vec::new();
+                        for fileh in &files {
+                            let mut file = fileh.lock();
+                            match file.flush() {
+                                Ok(e) => events.extend(e),
+                                Err(e) => warn!("flushing file {:?} on shutdown failed: {:?}",
+                                    file.path, e),
+                            }
+                        }
+                        events
+                    })
+                    .infallible()
+                }))
+                .map(stream::iter_ok)
+                .flatten_stream())
         );
 
         Box::new(future::ok(Started {
@@ -274,10 +457,23 @@ impl Input for FileInput {
 struct WatchedFile {
     id: FileId,
     path: PathBuf,
+
+    /// The path pattern this file was discovered with.
+    pattern: String,
+
     file: Option<File>,
     offset: u64,
     len: u64,
+    mtime: SystemTime,
+
+    /// Time of the last read that returned some data.
+    last_read: Instant,
+
+    /// Whether the decoder may hold a partial frame that needs to be flushed.
+    needs_flush: bool,
+
     decoder: BufDecoder,
+    meta_fields: Arc<Vec<(MetaField, String)>>,
 }
 
 impl WatchedFile {
@@ -292,6 +488,7 @@ impl WatchedFile {
             debug!("file len changed: {:?} {} -> {}", self.path, self.len, stat.len);
             self.len = stat.len;
         }
+        self.mtime = stat.mtime;
     }
 
     pub fn fill_buf(&mut self) -> Result<()> {
@@ -306,20 +503,55 @@ impl WatchedFile {
             .wrap_err_id(ErrorId::Io)?;
         buf.advance_write_pos(read);
         self.offset += read as u64;
+        if read > 0 {
+            self.last_read = Instant::now();
+            self.needs_flush = true;
+        }
 
         Ok(())
     }
 
     pub fn decode(&mut self) -> Result<Vec<Event>> {
         let mut events = Vec::new();
-        while self.decoder.decode(&mut events)? > 0 {
+        let mut pos = Vec::new();
+        while self.decoder.decode(&mut events, &mut pos)? > 0 {
         }
-        for event in &mut events {
-            event.fields_mut().insert("path".into(),
-                Value::String(self.path.to_string_lossy().into()));
+        self.add_fields(&mut events, &pos);
+        Ok(events)
+    }
+
+    /// Flushes the trailing partial frame (if any) held by the decoder.
+    pub fn flush(&mut self) -> Result<Vec<Event>> {
+        let mut events = Vec::new();
+        if self.needs_flush {
+            self.needs_flush = false;
+            let mut pos = Vec::new();
+            self.decoder.flush(&mut events, &mut pos)?;
+            self.add_fields(&mut events, &pos);
         }
         Ok(events)
     }
+
+    fn add_fields(&self, events: &mut [Event], pos: &[Pos]) {
+        assert_eq!(events.len(), pos.len());
+        for (event, pos) in events.iter_mut().zip(pos) {
+            for (field, name) in self.meta_fields.iter() {
+                let value = match field {
+                    MetaField::Path => Value::String(self.path.to_string_lossy().into()),
+                    MetaField::Offset => Value::Int(pos.offset as i64),
+                    MetaField::Line => Value::Int(pos.line as i64),
+                    MetaField::Device => Value::Int((self.id.0).0 as i64),
+                    MetaField::Inode => Value::Int((self.id.0).1 as i64),
+                    // Can't be formatted.
+                    MetaField::Mtime if self.mtime < UNIX_EPOCH => continue,
+                    MetaField::Mtime => Value::String(
+                        humantime::format_rfc3339_nanos(self.mtime).to_string()),
+                    MetaField::Pattern => Value::String(self.pattern.clone()),
+                };
+                event.fields_mut().insert(name.clone(), value);
+            }
+        }
+    }
 }
 
 struct State {
@@ -361,8 +593,10 @@ impl State {
 struct FileStat {
     id: FileId,
     len: u64,
+    mtime: SystemTime,
 }
 
+/// (device, inode)
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
 struct FileId((u64, u64));
 
@@ -372,5 +606,97 @@ fn stat<P: AsRef<Path>>(path: P) -> io::Result<FileStat> {
     Ok(FileStat {
         id: FileId((meta.dev(), meta.ino())),
         len: meta.len(),
+        mtime: meta.modified()?,
     })
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod test {
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    use super::*;
+    use crate::util::test::TempDir;
+
+    fn write_gz(path: &str, data: &[u8]) {
+        let mut gz = GzEncoder::new(File::create(path).unwrap(), Compression::default());
+        gz.write_all(data).unwrap();
+        gz.finish().unwrap();
+    }
+
+    /// Reads `n` events with the input and shuts it down.
+    fn read(config: Value, n: usize) -> Vec<Event> {
+        let input = ProviderImpl.new(New {
+            config: config.into(),
+            common_config: Default::default(),
+            metrics: Default::default(),
+        }).unwrap();
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(future::lazy(move || input.start())
+            .and_then(move |started| {
+                let shutdown = started.shutdown;
+                started.stream.take(n as u64).collect()
+                    .inspect(move |_| shutdown.signal())
+            }))
+            .unwrap()
+    }
+
+    #[test]
+    fn access_log() {
+        let dir = TempDir::new("file-input-access-log");
+        write_gz(&dir.path("access.log.gz"),
+            b"127.0.0.1 - - [10/Oct/2000:13:55:36 -0700] \"GET /a.gif HTTP/1.0\" 200 2326\n");
+        let events = read(value!{{
+            "path" => [dir.path("*.log.gz")],
+            "mode" => "read",
+            "event_decoder" => {"type" => "access_log", "format" => "common"},
+        }}, 1);
+        assert_eq!(events[0].fields()["path"], Value::String(dir.path("access.log.gz")));
+        assert_eq!(events[0].fields()["request_path"], Value::String("/a.gif".into()));
+    }
+
+    #[test]
+    fn metadata() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = TempDir::new("file-input-metadata");
+        write_gz(&dir.path("a.log.gz"), b"foo\nbar\n");
+        let meta = fs::metadata(dir.path("a.log.gz")).unwrap();
+        let pattern = dir.path("*.log.gz");
+        let events = read(value!{{
+            "path" => [pattern.clone()],
+            "mode" => "read",
+            "metadata" => ["path", "offset", "line", "device", "inode", "mtime", "pattern"],
+            "metadata_prefix" => "file_",
+        }}, 2);
+
+        let s = |s: &str| Value::String(s.into());
+        for (i, e) in events.iter().enumerate() {
+            let f = e.fields();
+            assert_eq!(f.len(), 8);
+            assert_eq!(f["message"], s(["foo", "bar"][i]));
+            assert_eq!(f["file_path"], s(&dir.path("a.log.gz")));
+            assert_eq!(f["file_offset"], Value::Int(i as i64 * 4));
+            assert_eq!(f["file_line"], Value::Int(i as i64 + 1));
+            assert_eq!(f["file_device"], Value::Int(meta.dev() as i64));
+            assert_eq!(f["file_inode"], Value::Int(meta.ino() as i64));
+            assert_eq!(f["file_mtime"], s(&humantime::format_rfc3339_nanos(
+                meta.modified().unwrap()).to_string()));
+            assert_eq!(f["file_pattern"], s(&pattern));
+        }
+    }
+
+    #[test]
+    fn invalid_metadata() {
+        for config in vec![
+            value!{{ "path" => [], "metadata" => ["foo"] }},
+            value!{{ "path" => [], "metadata" => ["line", "line"] }},
+        ] {
+            assert!(ProviderImpl.new(New {
+                config: config.into(),
+                common_config: Default::default(),
+                metrics: Default::default(),
+            }).is_err());
+        }
+    }
+}
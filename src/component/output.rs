@@ -1,22 +1,45 @@
+pub mod elasticsearch;
+pub mod exec;
+pub mod file;
+pub mod http;
 pub mod null;
 pub mod stdout;
+pub mod syslog;
+pub mod tcp;
+pub mod udp;
 
+use futures::prelude::*;
 use futures::sink::BoxSink;
+use futures::try_ready;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use super::*;
-use crate::error::Error;
+use crate::dead_letter::{DeadLetterQueue, DeadLetters};
+use crate::error::{Error, ErrorId};
 use crate::event::*;
+use crate::metric::Metrics;
 use crate::util::futures::BoxFuture;
 
 #[derive(Default)]
 pub struct CommonConfig {
     pub id: Option<String>,
+
+    /// Queue for the events the output fails to deliver permanently.
+    pub dead_letter_queue: Option<Arc<DeadLetterQueue>>,
+}
+
+impl CommonConfig {
+    /// Returns the dead letter queue handle for the output with the `id`.
+    pub fn dead_letters(&self, id: &str) -> Option<DeadLetters> {
+        self.dead_letter_queue.as_ref().map(|q| DeadLetters::new(q.clone(), id))
+    }
 }
 
 pub struct New {
     pub config: Spanned<Value>,
     pub common_config: CommonConfig,
+    pub metrics: Arc<Metrics>,
 }
 
 pub struct Started {
@@ -29,4 +52,314 @@ pub trait Provider: super::Provider {
 
 pub trait Output: 'static + Send + Sync {
     fn start(&self) -> BoxFuture<Started, Error>;
-}
\ No newline at end of file
+}
+
+const CODEC: &'static str = "codec";
+const BATCH_MAX_EVENTS: &'static str = "batch_max_events";
+const BATCH_MAX_BYTES: &'static str = "batch_max_bytes";
+const BATCH_TIMEOUT: &'static str = "batch_timeout";
+
+const DEFAULT_BATCH_MAX_EVENTS: usize = 1000;
+const DEFAULT_BATCH_MAX_BYTES: usize = 1024 * 1024;
+const DEFAULT_BATCH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Removes the `codec` key from the `config` and creates the encoder factory it specifies or the
+/// `default` encoder factory if the key is absent.
+pub fn parse_codec(config: &mut Spanned<Value>, default: &str) -> Result<Arc<encoder::Factory>> {
+    if let Some(v) = config.remove_opt(CODEC)? {
+        let (name, config) = split_component_config(v)?;
+        registry().encoder(&name)
+            .ok_or_else(|| name.new_error(format!("unknown encoder `{}`", *name)))?
+            .new(encoder::New { config })
+    } else {
+        registry().encoder(default).unwrap().new(Default::default())
+    }
+}
+
+/// Encodes the `event` as a standalone message by flushing the `encoder` right after it.
+pub fn encode_event(encoder: &mut encoder::Encoder, event: &Event, out: &mut Vec<u8>)
+    -> Result<()>
+{
+    encoder.encode(event, out)?;
+    encoder.flush(out)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BatchConfig {
+    pub max_events: usize,
+
+    /// Max size of the encoded batch. A batch is written once it reaches this size, so a single
+    /// event can make the batch larger.
+    pub max_bytes: usize,
+
+    /// Max time the events are added to a batch before it's written, counted from adding
+    /// the first event.
+    pub timeout: Duration,
+}
+
+impl BatchConfig {
+    /// Parses the `batch_max_events`, `batch_max_bytes` and `batch_timeout` keys of the `config`.
+    pub fn parse(config: &Spanned<Value>) -> Result<Self> {
+        Ok(Self {
+            max_events: parse_positive(config, BATCH_MAX_EVENTS)?
+                .unwrap_or(DEFAULT_BATCH_MAX_EVENTS),
+            max_bytes: parse_positive(config, BATCH_MAX_BYTES)?
+                .unwrap_or(DEFAULT_BATCH_MAX_BYTES),
+            timeout: config.get_opt_duration(BATCH_TIMEOUT)?.unwrap_or(DEFAULT_BATCH_TIMEOUT),
+        })
+    }
+}
+
+fn parse_positive(config: &Spanned<Value>, key: &str) -> Result<Option<usize>> {
+    Ok(if let Some(v) = config.get_opt(key)? {
+        let r = *v.as_int()?;
+        if r <= 0 {
+            return Err(v.new_error(format!("`{}` must be positive", key)));
+        }
+        Some(r as usize)
+    } else {
+        None
+    })
+}
+
+/// Event of a batch along with its encoding.
+pub struct BatchItem {
+    pub event: Event,
+    pub data: Vec<u8>,
+}
+
+impl BatchItem {
+    /// Concatenates the encoded events of the `items`.
+    pub fn concat(items: &[Self]) -> Vec<u8> {
+        let mut r = Vec::with_capacity(items.iter().map(|v| v.data.len()).sum());
+        for item in items {
+            r.extend_from_slice(&item.data);
+        }
+        r
+    }
+}
+
+/// Batch-oriented writer that `BatchSink` adapts to the event sink.
+pub trait BatchWriter: 'static + Send {
+    /// Encodes the `event` as a batch item.
+    fn encode(&mut self, event: &Event) -> Result<Vec<u8>>;
+
+    /// Writes out the batch `items` retrying as needed. The error is final and fails the sink.
+    fn write(&mut self, items: Vec<BatchItem>) -> BoxFuture<(), Error>;
+}
+
+/// Collects the events encoded by the `BatchWriter` into batches and writes them out, at most one
+/// batch at a time. A new batch is collected while the previous one is being written.
+///
+/// A batch is written once it's full or its timeout expires while the events keep coming.
+/// `poll_complete()` and `close()` write out the batch regardless, so the batches collect
+/// the events sent while the previous batch is being written.
+pub struct BatchSink<W> {
+    config: BatchConfig,
+    writer: W,
+
+    batch: Vec<BatchItem>,
+    batch_bytes: usize,
+    batch_deadline: Option<Instant>,
+
+    in_flight: Option<BoxFuture<(), Error>>,
+}
+
+impl<W: BatchWriter> BatchSink<W> {
+    pub fn new(config: BatchConfig, writer: W) -> Self {
+        Self {
+            config,
+            writer,
+            batch: Vec::new(),
+            batch_bytes: 0,
+            batch_deadline: None,
+            in_flight: None,
+        }
+    }
+
+    fn is_batch_full(&self) -> bool {
+        self.batch.len() >= self.config.max_events
+            || self.batch_bytes >= self.config.max_bytes
+    }
+
+    fn is_batch_expired(&self) -> bool {
+        self.batch_deadline.map(|d| Instant::now() >= d).unwrap_or(false)
+    }
+
+    /// Starts writing the collected batch. There must be no batch in flight.
+    fn write_batch(&mut self) {
+        assert!(self.in_flight.is_none());
+        let items = std::mem::replace(&mut self.batch, Vec::new());
+        self.in_flight = Some(self.writer.write(items));
+        self.batch_bytes = 0;
+        self.batch_deadline = None;
+    }
+
+    fn poll_in_flight(&mut self) -> Poll<(), Error> {
+        if let Some(f) = &mut self.in_flight {
+            try_ready!(f.poll());
+            self.in_flight = None;
+        }
+        Ok(Async::Ready(()))
+    }
+
+    /// Writes out all the collected events.
+    fn poll_flush(&mut self) -> Poll<(), Error> {
+        loop {
+            try_ready!(self.poll_in_flight());
+            if self.batch.is_empty() {
+                return Ok(Async::Ready(()));
+            }
+            self.write_batch();
+        }
+    }
+}
+
+impl<W: BatchWriter> Sink for BatchSink<W> {
+    type SinkItem = Event;
+    type SinkError = Error;
+
+    fn start_send(&mut self, event: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
+        if self.is_batch_full() || self.is_batch_expired() {
+            if self.poll_in_flight()?.is_not_ready() {
+                return Ok(AsyncSink::NotReady(event));
+            }
+            self.write_batch();
+            self.poll_in_flight()?;
+        }
+        let data = self.writer.encode(&event)?;
+        if self.batch.is_empty() {
+            self.batch_deadline = Some(Instant::now() + self.config.timeout);
+        }
+        self.batch_bytes += data.len();
+        self.batch.push(BatchItem { event, data });
+        Ok(AsyncSink::Ready)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
+        self.poll_flush()
+    }
+
+    fn close(&mut self) -> Poll<(), Self::SinkError> {
+        self.poll_flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::future;
+    use futures::stream;
+    use parking_lot::Mutex;
+    use tokio::timer::Delay;
+
+    use super::*;
+    use crate::util::futures::BoxStream;
+
+    /// Records the batches of the event messages.
+    #[derive(Default)]
+    struct Writer {
+        batches: Arc<Mutex<Vec<Vec<String>>>>,
+        fail: bool,
+    }
+
+    impl BatchWriter for Writer {
+        fn encode(&mut self, event: &Event) -> Result<Vec<u8>> {
+            match event.fields().get("message") {
+                Some(Value::String(s)) => Ok(s.clone().into_bytes()),
+                _ => Err(Error::new(ErrorId::Parse, "no message")),
+            }
+        }
+
+        fn write(&mut self, items: Vec<BatchItem>) -> BoxFuture<(), Error> {
+            self.batches.lock().push(items.into_iter()
+                .map(|v| String::from_utf8(v.data).unwrap())
+                .collect());
+            if self.fail {
+                Box::new(future::err(Error::new(ErrorId::Io, "write failed")))
+            } else {
+                Box::new(future::ok(()))
+            }
+        }
+    }
+
+    fn event(message: &str) -> Event {
+        let mut e = Event::new();
+        e.fields_mut().insert("message".into(), Value::String(message.into()));
+        e
+    }
+
+    fn config(max_events: usize, max_bytes: usize, timeout: Duration) -> BatchConfig {
+        BatchConfig {
+            max_events,
+            max_bytes,
+            timeout,
+        }
+    }
+
+    fn send(config: BatchConfig, writer: Writer, events: BoxStream<Event, Error>)
+        -> Result<Vec<Vec<String>>>
+    {
+        let batches = writer.batches.clone();
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(events.forward(BatchSink::new(config, writer)).map(|_| {}))?;
+        let r = batches.lock().clone();
+        Ok(r)
+    }
+
+    fn events(messages: &[&str]) -> BoxStream<Event, Error> {
+        Box::new(stream::iter_ok(messages.iter().map(|m| event(m)).collect::<Vec<_>>()))
+    }
+
+    #[test]
+    fn max_events() {
+        let config = config(2, 1024, Duration::from_secs(3600));
+        assert_eq!(send(config, Writer::default(), events(&["0", "1", "2", "3", "4"])).unwrap(),
+            vec![vec!["0", "1"], vec!["2", "3"], vec!["4"]]);
+    }
+
+    #[test]
+    fn max_bytes() {
+        let config = config(1000, 3, Duration::from_secs(3600));
+        assert_eq!(send(config, Writer::default(), events(&["ab", "c", "def", "g"])).unwrap(),
+            vec![vec!["ab", "c"], vec!["def"], vec!["g"]]);
+    }
+
+    #[test]
+    fn timeout() {
+        // The events keep coming so the batch is written only when it expires.
+        let config = config(1000, 1024, Duration::from_millis(0));
+        assert_eq!(send(config, Writer::default(), events(&["0", "1", "2"])).unwrap(),
+            vec![vec!["0"], vec!["1"], vec!["2"]]);
+    }
+
+    #[test]
+    fn poll_complete() {
+        let config = config(1000, 1024, Duration::from_secs(3600));
+        let late = Delay::new(Instant::now() + Duration::from_millis(200))
+            .map(|_| event("2"))
+            .map_err(|e| Error::new(ErrorId::Unknown, e.to_string()))
+            .into_stream();
+        assert_eq!(send(config, Writer::default(), Box::new(events(&["0", "1"]).chain(late)))
+                .unwrap(),
+            vec![vec!["0", "1"], vec!["2"]]);
+    }
+
+    #[test]
+    fn encode_error() {
+        let config = config(1000, 1024, Duration::from_secs(3600));
+        let e = send(config, Writer::default(), Box::new(stream::iter_ok(vec![Event::new()])))
+            .err().unwrap();
+        assert_eq!(*e.id(), ErrorId::Parse);
+    }
+
+    #[test]
+    fn write_error() {
+        let config = config(1, 1024, Duration::from_secs(3600));
+        let writer = Writer {
+            fail: true,
+            ..Default::default()
+        };
+        let e = send(config, writer, events(&["0", "1", "2"])).err().unwrap();
+        assert_eq!(*e.id(), ErrorId::Io);
+    }
+}
@@ -0,0 +1,460 @@
+use chrono::Utc;
+use futures::future::{self, Either, Loop};
+use futures::prelude::*;
+use futures_retry::{ErrorHandler, RetryPolicy};
+use hyper::{Body, Request, StatusCode, Uri};
+use hyper::header::{self, HeaderMap, HeaderValue};
+use log::*;
+use serde_json as json;
+use std::time::Instant;
+use tokio::timer::Delay;
+
+use super::*;
+use super::http::{self, Client, ClientConfig, Compression, Outcome, Response};
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::event::*;
+use crate::metric::{self, Metrics};
+use crate::util::futures::*;
+use crate::util::json::to_text;
+use crate::util::template::{self, Template};
+
+pub const NAME: &'static str = "elasticsearch";
+
+const URL: &'static str = "url";
+const INDEX: &'static str = "index";
+const TIMESTAMP_FIELD: &'static str = "timestamp_field";
+const ID_FIELD: &'static str = "id_field";
+const ACTION: &'static str = "action";
+const ACTION_INDEX: &'static str = "index";
+const ACTION_CREATE: &'static str = "create";
+
+const DEFAULT_CODEC: &'static str = "json";
+const DEFAULT_INDEX: &'static str = "events-%Y.%m.%d";
+const DEFAULT_TIMESTAMP_FIELD: &'static str = "timestamp";
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::Output,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Box<Output>> {
+        let mut config = ctx.config;
+
+        let id = ctx.common_config.id.clone().unwrap_or_else(|| NAME.into());
+        let dead_letters = ctx.common_config.dead_letters(&id);
+
+        let url = {
+            let v = config.get(URL)?;
+            let mut url = v.as_str()?.trim_end_matches('/').to_owned();
+            url.push_str("/_bulk");
+            http::parse_url(&Spanned { value: Value::String(url), span: v.span.clone() })?
+        };
+
+        let index = if let Some(v) = config.get_opt(INDEX)? {
+            Template::parse_path(v)?
+        } else {
+            Template::parse_path(&DEFAULT_INDEX.into())?
+        };
+
+        let timestamp_field = config.get_opt_str(TIMESTAMP_FIELD)?
+            .unwrap_or(DEFAULT_TIMESTAMP_FIELD)
+            .to_owned();
+        let id_field = config.get_opt_str(ID_FIELD)?.map(|s| s.to_owned());
+
+        let action = if let Some(v) = config.get_opt(ACTION)? {
+            match v.as_str()? {
+                ACTION_INDEX => ACTION_INDEX,
+                ACTION_CREATE => ACTION_CREATE,
+                _ => return Err(v.new_error(format!("`{}` must be one of [\"{}\", \"{}\"]",
+                    ACTION, ACTION_INDEX, ACTION_CREATE))),
+            }
+        } else {
+            ACTION_INDEX
+        };
+
+        let encoder_factory = parse_codec(&mut config, DEFAULT_CODEC)?;
+
+        let mut headers = http::parse_headers(&config)?;
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/x-ndjson"));
+        let compression = http::parse_compression(&config)?;
+        if compression == Compression::Gzip {
+            headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+        }
+
+        let batch = BatchConfig::parse(&config)?;
+        let client = Arc::new(ClientConfig::parse(&config, id.clone(), ctx.metrics.clone())?);
+
+        ctx.metrics.set_default(rejected_metric(&id), metric::Value::Counter(0.into()));
+
+        Ok(Box::new(ElasticsearchOutput {
+            config: Arc::new(Config {
+                id,
+                url,
+                index,
+                timestamp_field,
+                id_field,
+                action,
+                encoder_factory,
+                headers,
+                compression,
+                batch,
+                client,
+                metrics: ctx.metrics,
+                dead_letters,
+            }),
+        }))
+    }
+}
+
+/// Name of the counter of the documents permanently rejected by the cluster.
+fn rejected_metric(id: &str) -> String {
+    format!("output.{}.elasticsearch.rejected", id)
+}
+
+struct Config {
+    /// Used as the log context and in the metric names.
+    id: String,
+
+    /// URL of the `_bulk` endpoint.
+    url: Uri,
+
+    /// Index name template. The date specifiers are taken from the event timestamp.
+    index: Template,
+
+    timestamp_field: String,
+
+    /// Field to take the document `_id` from. If not set the cluster generates the ids.
+    id_field: Option<String>,
+
+    /// Bulk action to use for the documents.
+    action: &'static str,
+
+    encoder_factory: Arc<encoder::Factory>,
+    headers: HeaderMap,
+    compression: Compression,
+    batch: BatchConfig,
+    client: Arc<ClientConfig>,
+    metrics: Arc<Metrics>,
+
+    /// Receives the events of the documents rejected by the cluster.
+    dead_letters: Option<DeadLetters>,
+}
+
+impl Config {
+    fn new_request(&self, items: &[BatchItem]) -> Request<Body> {
+        let body = self.compression.compress(BatchItem::concat(items));
+        let mut r = Request::new(Body::from(body));
+        *r.method_mut() = hyper::Method::POST;
+        *r.uri_mut() = self.url.clone();
+        *r.headers_mut() = self.headers.clone();
+        r
+    }
+
+    /// Counts the `items` rejected permanently in the rejected metric and sends their events to
+    /// the dead letter queue.
+    fn reject(&self, items: Vec<BatchItem>, e: &Error) {
+        self.metrics.inc(&rejected_metric(&self.id), items.len() as i64);
+        if let Some(dead_letters) = &self.dead_letters {
+            for item in items {
+                dead_letters.push(e, item.event);
+            }
+        }
+    }
+
+    /// Checks the result of the bulk request. The request rejected permanently is logged and
+    /// rejected. If the request or some of its items failed temporarily, returns the items to
+    /// retry along with the error describing the failure.
+    fn check_response(&self, items: Vec<BatchItem>, resp: Result<Response>)
+        -> std::result::Result<(), (Vec<BatchItem>, Error)>
+    {
+        let resp = match resp {
+            Ok(v) => v,
+            Err(e) => return Err((items, e)),
+        };
+        match Outcome::of(resp.status) {
+            Outcome::Success => self.check_items(items, &resp.body),
+            Outcome::Retry => Err((items, Error::new(ErrorId::Io,
+                format!("server responded with {}: {}", resp.status,
+                    http::body_excerpt(&resp.body))))),
+            Outcome::Fail => {
+                let e = Error::new(ErrorId::Io, format!("server rejected the request with {}: {}",
+                    resp.status, http::body_excerpt(&resp.body)));
+                error!("[{}] {} documents rejected: {}", self.id, items.len(), e);
+                self.reject(items, &e);
+                Ok(())
+            }
+        }
+    }
+
+    /// Checks the per-item results of the bulk response. The items rejected permanently are
+    /// logged and rejected. If some items failed temporarily, returns them along with the error
+    /// describing the failure.
+    fn check_items(&self, items: Vec<BatchItem>, body: &[u8])
+        -> std::result::Result<(), (Vec<BatchItem>, Error)>
+    {
+        let resp: json::Value = match json::from_slice(body) {
+            Ok(v) => v,
+            Err(e) => return Err((items, Error::new(ErrorId::Parse,
+                format!("invalid bulk response: {}: {}", e, http::body_excerpt(body))))),
+        };
+        if resp["errors"] != json::Value::Bool(true) {
+            return Ok(());
+        }
+        let results = match resp["items"].as_array() {
+            Some(v) if v.len() == items.len() => v,
+            _ => return Err((items, Error::new(ErrorId::Parse,
+                "bulk response items don't match the request items"))),
+        };
+
+        let mut retry = Vec::new();
+        let mut retry_error = None;
+        for (item, result) in items.into_iter().zip(results) {
+            // Each result is an object with the action name as the only key.
+            let result = result.as_object()
+                .and_then(|v| v.values().next())
+                .unwrap_or(&json::Value::Null);
+            let status = result["status"].as_u64()
+                .and_then(|v| StatusCode::from_u16(v as u16).ok())
+                .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            match Outcome::of(status) {
+                Outcome::Success => {}
+                Outcome::Retry => {
+                    if retry_error.is_none() {
+                        retry_error = Some(format!("{}: {}", status, result["error"]));
+                    }
+                    retry.push(item);
+                }
+                Outcome::Fail => {
+                    let e = Error::new(ErrorId::Io, format!("document rejected with {}: {}",
+                        status, result["error"]));
+                    error!("[{}] {}; document: {}", self.id, e, http::body_excerpt(&item.data));
+                    self.reject(vec![item], &e);
+                }
+            }
+        }
+        if retry.is_empty() {
+            Ok(())
+        } else {
+            let e = Error::new(ErrorId::Io, format!(
+                "{} documents failed temporarily, first error: {}",
+                retry.len(), retry_error.unwrap()));
+            Err((retry, e))
+        }
+    }
+}
+
+struct ElasticsearchOutput {
+    config: Arc<Config>,
+}
+
+impl Output for ElasticsearchOutput {
+    fn start(&self) -> BoxFuture<Started, Error> {
+        Box::new(future::ok(Started {
+            sink: Box::new(BatchSink::new(self.config.batch, ElasticsearchWriter {
+                client: Client::new(self.config.client.clone()),
+                encoder: self.config.encoder_factory.new(),
+                config: self.config.clone(),
+            })),
+        }))
+    }
+}
+
+/// Sends each batch with a bulk request. After each attempt only the items that failed
+/// temporarily are resent.
+struct ElasticsearchWriter {
+    config: Arc<Config>,
+    client: Client,
+    encoder: Box<encoder::Encoder>,
+}
+
+impl BatchWriter for ElasticsearchWriter {
+    /// Encodes the `event` as the action and document line pair.
+    fn encode(&mut self, event: &Event) -> Result<Vec<u8>> {
+        let time = event.fields().get(&self.config.timestamp_field)
+            .and_then(template::timestamp)
+            .unwrap_or_else(Utc::now);
+        let mut meta = json::Map::new();
+        meta.insert("_index".into(), self.config.index.render_path(event, &time).into());
+        if let Some(id_field) = &self.config.id_field {
+            if let Some(id) = event.fields().get(id_field) {
+                meta.insert("_id".into(), to_text(id).into_owned().into());
+            }
+        }
+        let mut action = json::Map::new();
+        action.insert(self.config.action.into(), json::Value::Object(meta));
+
+        let mut r = json::to_vec(&action).unwrap();
+        r.push(b'\n');
+        encode_event(&mut *self.encoder, event, &mut r)?;
+        if r.last() != Some(&b'\n') {
+            r.push(b'\n');
+        }
+        Ok(r)
+    }
+
+    fn write(&mut self, items: Vec<BatchItem>) -> BoxFuture<(), Error> {
+        debug!("[{}] sending {} documents ({} bytes)", self.config.id, items.len(),
+            items.iter().map(|v| v.data.len()).sum::<usize>());
+        let config = self.config.clone();
+        let client = self.client.clone();
+        Box::new(future::loop_fn((items, self.client.retry_handler()),
+            move |(items, mut retry)| {
+                let config = config.clone();
+                client.send(config.new_request(&items))
+                    .then(move |resp| match config.check_response(items, resp) {
+                        Ok(()) => Either::A(future::ok(Loop::Break(()))),
+                        Err((items, e)) => match retry.handle(e) {
+                            RetryPolicy::Repeat =>
+                                Either::A(future::ok(Loop::Continue((items, retry)))),
+                            RetryPolicy::WaitRetry(delay) =>
+                                Either::B(Delay::new(Instant::now() + delay)
+                                    .map_err(|e| Error::new(ErrorId::Unknown, e.to_string()))
+                                    .map(move |_| Loop::Continue((items, retry)))),
+                            RetryPolicy::ForwardError(e) => Either::A(future::err(e)),
+                        }
+                    })
+            }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::stream;
+
+    use super::*;
+    use super::http::test::MockServer;
+
+    fn new(config: Value, metrics: Arc<Metrics>) -> Result<Box<Output>> {
+        ProviderImpl.new(New {
+            config: config.into(),
+            common_config: Default::default(),
+            metrics,
+        })
+    }
+
+    fn event(message: &str, timestamp: &str) -> Event {
+        let mut e = Event::new();
+        let f = e.fields_mut();
+        f.insert("message".into(), Value::String(message.into()));
+        f.insert("timestamp".into(), Value::String(timestamp.into()));
+        f.insert("service".into(), Value::String("Web".into()));
+        e
+    }
+
+    fn events() -> Vec<Event> {
+        vec![
+            event("a", "2019-01-02T03:04:05Z"),
+            event("b", "2019-01-03T03:04:05Z"),
+            event("c", "2019-01-04T03:04:05Z"),
+        ]
+    }
+
+    fn bulk_response(statuses: &[u16]) -> String {
+        let items: Vec<_> = statuses.iter()
+            .map(|&status| if status < 300 {
+                json::json!({"index": {"status": status}})
+            } else {
+                json::json!({"index": {"status": status, "error": {"type": "test"}}})
+            })
+            .collect();
+        json::json!({"took": 1, "errors": statuses.iter().any(|&s| s >= 300), "items": items})
+            .to_string()
+    }
+
+    /// Sends the `events` to the output configured to post to the mock server.
+    fn send(config: Value, responses: &[(u16, String)], events: Vec<Event>)
+        -> (Result<()>, MockServer, Arc<Metrics>)
+    {
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let server = MockServer::start(&mut rt);
+        for (status, body) in responses {
+            server.respond(*status, body);
+        }
+        let mut config = config;
+        config.as_map_mut().unwrap().insert(URL.into(),
+            Value::String(server.url("/")).into());
+        let metrics = Arc::new(Metrics::new());
+        let output = new(config, metrics.clone()).unwrap();
+        let r = rt.block_on(output.start()
+            .and_then(|started| stream::iter_ok(events).forward(started.sink))
+            .map(|_| {}));
+        (r, server, metrics)
+    }
+
+    fn rejected(metrics: &Metrics) -> metric::Value {
+        metrics.get(&rejected_metric(NAME)).unwrap()
+    }
+
+    #[test]
+    fn bulk_body() {
+        let (r, server, _) = send(value!{{
+            INDEX => "logs-%{service|lower}-%Y.%m.%d",
+            ID_FIELD => "message",
+            ACTION => ACTION_CREATE,
+        }}, &[(200, bulk_response(&[201, 201, 201]))], events());
+        r.unwrap();
+        assert_eq!(server.bodies(), vec![concat!(
+            r#"{"create":{"_id":"a","_index":"logs-web-2019.01.02"}}"#, "\n",
+            r#"{"message":"a","service":"Web","timestamp":"2019-01-02T03:04:05Z"}"#, "\n",
+            r#"{"create":{"_id":"b","_index":"logs-web-2019.01.03"}}"#, "\n",
+            r#"{"message":"b","service":"Web","timestamp":"2019-01-03T03:04:05Z"}"#, "\n",
+            r#"{"create":{"_id":"c","_index":"logs-web-2019.01.04"}}"#, "\n",
+            r#"{"message":"c","service":"Web","timestamp":"2019-01-04T03:04:05Z"}"#, "\n",
+        )]);
+        let requests = server.requests.lock();
+        assert_eq!(requests[0].uri(), "/_bulk");
+        assert_eq!(requests[0].headers()[header::CONTENT_TYPE], "application/x-ndjson");
+    }
+
+    #[test]
+    fn retry_rejected_items() {
+        let (r, server, metrics) = send(value!{{
+            INDEX => "i",
+            "retry_delay" => "1ms",
+        }}, &[
+            (200, bulk_response(&[201, 429, 400])),
+            (503, String::new()),
+            (200, bulk_response(&[201])),
+        ], events());
+        r.unwrap();
+        let bodies = server.bodies();
+        assert_eq!(bodies.len(), 3);
+        assert_eq!(bodies[0].lines().count(), 6);
+        let exp = concat!(r#"{"index":{"_index":"i"}}"#, "\n",
+            r#"{"message":"b","service":"Web","timestamp":"2019-01-03T03:04:05Z"}"#, "\n");
+        assert_eq!(bodies[1], exp);
+        assert_eq!(bodies[2], exp);
+        assert_eq!(rejected(&metrics), metric::Value::Counter(1.into()));
+    }
+
+    #[test]
+    fn request_rejected() {
+        let (r, server, metrics) = send(value!{{
+            INDEX => "i",
+            "batch_max_events" => 2,
+        }}, &[(413, String::new()), (200, bulk_response(&[201]))], events());
+        r.unwrap();
+        assert_eq!(server.bodies().len(), 2);
+        assert_eq!(rejected(&metrics), metric::Value::Counter(2.into()));
+    }
+
+    #[test]
+    fn invalid_config() {
+        let new = |config| new(config, Default::default());
+        assert!(new(value!{{}}).is_err());
+        assert!(new(value!{{ URL => "localhost:9200" }}).is_err());
+        assert!(new(value!{{ URL => "http://localhost:9200", INDEX => "%{a" }}).is_err());
+        assert!(new(value!{{ URL => "http://localhost:9200", ACTION => "delete" }}).is_err());
+    }
+}
@@ -0,0 +1,536 @@
+use futures::future;
+use futures::prelude::*;
+use futures::try_ready;
+use futures_retry::{ErrorHandler, FutureRetry, RetryPolicy};
+use log::*;
+use std::io;
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWrite;
+use tokio::timer::Delay;
+use tokio_process::{Child, ChildStdin, CommandExt};
+
+use super::*;
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::event::*;
+use crate::retry::RetryErrorHandler;
+use crate::util::futures::*;
+
+pub const NAME: &'static str = "exec";
+
+const COMMAND: &'static str = "command";
+const MODE: &'static str = "mode";
+const MODE_STREAM: &'static str = "stream";
+const MODE_BATCH: &'static str = "batch";
+const BUFFER_SIZE: &'static str = "buffer_size";
+const MAX_RETRIES: &'static str = "max_retries";
+const RESTART_DELAY: &'static str = "restart_delay";
+const MAX_RESTART_DELAY: &'static str = "max_restart_delay";
+
+const DEFAULT_CODEC: &'static str = "json_lines";
+const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+const DEFAULT_RESTART_DELAY: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::Output,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Box<Output>> {
+        let mut config = ctx.config;
+
+        let command = {
+            let v = config.get(COMMAND)?;
+            if let Value::String(s) = &v.value {
+                vec!["/bin/sh".into(), "-c".into(), s.clone()]
+            } else {
+                let r = v.as_list()?.iter()
+                    .map(|v| v.as_str().map(|s| s.to_owned()))
+                    .collect::<Result<Vec<_>>>()?;
+                if r.is_empty() {
+                    return Err(v.new_error(format!("`{}` can't be empty", COMMAND)));
+                }
+                r
+            }
+        };
+
+        let encoder_factory = parse_codec(&mut config, DEFAULT_CODEC)?;
+
+        let mode = if let Some(v) = config.get_opt(MODE)? {
+            match v.as_str()? {
+                MODE_STREAM => Mode::Stream,
+                MODE_BATCH => Mode::Batch(BatchConfig::parse(&config)?),
+                _ => return Err(v.new_error(format!("`{}` must be one of [\"{}\", \"{}\"]",
+                    MODE, MODE_STREAM, MODE_BATCH))),
+            }
+        } else {
+            Mode::Stream
+        };
+
+        let buffer_size = if let Some(v) = config.get_opt(BUFFER_SIZE)? {
+            let r = *v.as_int()?;
+            if r <= 0 {
+                return Err(v.new_error(format!("`{}` must be positive", BUFFER_SIZE)));
+            }
+            r as usize
+        } else {
+            DEFAULT_BUFFER_SIZE
+        };
+
+        let max_retries = if let Some(v) = config.get_opt(MAX_RETRIES)? {
+            let r = *v.as_int()?;
+            if r < 0 {
+                return Err(v.new_error(format!("`{}` can't be negative", MAX_RETRIES)));
+            }
+            Some(r as usize)
+        } else {
+            None
+        };
+
+        Ok(Box::new(ExecOutput {
+            config: Arc::new(Config {
+                id: ctx.common_config.id.unwrap_or_else(|| NAME.into()),
+                command,
+                encoder_factory,
+                mode,
+                buffer_size,
+                max_retries,
+                restart_delay: config.get_opt_duration(RESTART_DELAY)?
+                    .unwrap_or(DEFAULT_RESTART_DELAY),
+                max_restart_delay: config.get_opt_duration(MAX_RESTART_DELAY)?
+                    .unwrap_or(DEFAULT_MAX_RESTART_DELAY),
+            }),
+        }))
+    }
+}
+
+enum Mode {
+    /// A single long-running process reads all the events.
+    Stream,
+
+    /// A process is run per batch of events.
+    Batch(BatchConfig),
+}
+
+struct Config {
+    /// Used as the log context.
+    id: String,
+
+    /// Program and its arguments.
+    command: Vec<String>,
+
+    encoder_factory: Arc<encoder::Factory>,
+    mode: Mode,
+
+    /// Size of the buffer of the encoded events after reaching which the stream mode sink stops
+    /// accepting events until the buffer is written out.
+    buffer_size: usize,
+
+    /// Max number of retries of a failed batch. The long-running process is restarted
+    /// indefinitely.
+    max_retries: Option<usize>,
+
+    restart_delay: Duration,
+    max_restart_delay: Duration,
+}
+
+impl Config {
+    fn spawn(&self) -> io::Result<Child> {
+        debug!("[{}] running {:?}", self.id, self.command);
+        Command::new(&self.command[0])
+            .args(&self.command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .spawn_async()
+    }
+
+    fn retry_handler(&self, max_attempts: Option<usize>, action: &str) -> RetryErrorHandler {
+        RetryErrorHandler::new(max_attempts, self.restart_delay, self.max_restart_delay,
+            &self.id, format_args!("{} `{}`", action, self.command.join(" ")))
+    }
+}
+
+fn exit_error(status: ExitStatus) -> Error {
+    Error::new(ErrorId::Io, format!("process exited with {}", status))
+}
+
+struct ExecOutput {
+    config: Arc<Config>,
+}
+
+impl Output for ExecOutput {
+    fn start(&self) -> BoxFuture<Started, Error> {
+        let sink: BoxSink<Event, Error> = match &self.config.mode {
+            Mode::Stream => Box::new(StreamSink::new(self.config.clone())),
+            Mode::Batch(batch) => Box::new(BatchSink::new(*batch, ExecWriter {
+                encoder: self.config.encoder_factory.new(),
+                config: self.config.clone(),
+            })),
+        };
+        Box::new(future::ok(Started {
+            sink,
+        }))
+    }
+}
+
+enum State {
+    Stopped,
+    Waiting(Delay),
+    Running {
+        child: Child,
+
+        /// `None` once the sink is closed.
+        stdin: Option<ChildStdin>,
+    },
+}
+
+/// Writes the encoded events to stdin of a long-running process restarting it with exponential
+/// backoff when it exits.
+struct StreamSink {
+    config: Arc<Config>,
+    encoder: Box<encoder::Encoder>,
+    state: State,
+    retry: RetryErrorHandler,
+
+    buf: Vec<u8>,
+
+    /// Number of bytes of `buf` written to the current process.
+    written: usize,
+
+    /// End offsets of the events in `buf`.
+    event_ends: Vec<usize>,
+}
+
+impl StreamSink {
+    fn new(config: Arc<Config>) -> Self {
+        Self {
+            encoder: config.encoder_factory.new(),
+            retry: config.retry_handler(None, "running"),
+            config,
+            state: State::Stopped,
+            buf: Vec::new(),
+            written: 0,
+            event_ends: Vec::new(),
+        }
+    }
+
+    /// Drops the process and schedules restart.
+    fn restart(&mut self, e: Error) -> Result<()> {
+        // Resend the partially written event in whole.
+        self.written = self.event_ends.iter().cloned()
+            .take_while(|&end| end <= self.written)
+            .last()
+            .unwrap_or(0);
+        self.state = match self.retry.handle(e) {
+            RetryPolicy::Repeat => State::Stopped,
+            RetryPolicy::WaitRetry(delay) => State::Waiting(Delay::new(Instant::now() + delay)),
+            RetryPolicy::ForwardError(e) => return Err(e),
+        };
+        Ok(())
+    }
+
+    /// Starts the process if needed and writes the whole buffer out.
+    fn poll_write(&mut self) -> Poll<(), Error> {
+        loop {
+            match &mut self.state {
+                State::Stopped => match self.config.spawn() {
+                    Ok(mut child) => {
+                        info!("[{}] started process {}", self.config.id, child.id());
+                        let stdin = child.stdin().take();
+                        self.state = State::Running { child, stdin };
+                    }
+                    Err(e) => self.restart(e.wrap_id(ErrorId::Io))?,
+                }
+                State::Waiting(delay) => {
+                    try_ready!(delay.poll().map_err(|e| Error::new(ErrorId::Unknown,
+                        e.to_string())));
+                    self.state = State::Stopped;
+                }
+                State::Running { child, stdin } => {
+                    match child.poll() {
+                        Ok(Async::Ready(status)) => {
+                            warn!("[{}] process exited with {}", self.config.id, status);
+                            self.restart(exit_error(status))?;
+                            continue;
+                        }
+                        Ok(Async::NotReady) => {}
+                        Err(e) => {
+                            self.restart(e.wrap_id(ErrorId::Io))?;
+                            continue;
+                        }
+                    }
+                    let stdin = stdin.as_mut().unwrap();
+                    let r = loop {
+                        if self.written == self.buf.len() {
+                            break stdin.poll_flush();
+                        }
+                        match stdin.poll_write(&self.buf[self.written..]) {
+                            Ok(Async::Ready(0)) => break Err(io::ErrorKind::WriteZero.into()),
+                            Ok(Async::Ready(n)) => self.written += n,
+                            r => break r.map(|a| a.map(|_| {})),
+                        }
+                    };
+                    match r {
+                        Ok(Async::Ready(())) => {
+                            if !self.buf.is_empty() {
+                                self.retry = self.config.retry_handler(None, "running");
+                            }
+                            self.buf.clear();
+                            self.event_ends.clear();
+                            self.written = 0;
+                            return Ok(Async::Ready(()));
+                        }
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        Err(e) => {
+                            warn!("[{}] error writing to process: {}", self.config.id, e);
+                            self.restart(e.wrap_id(ErrorId::Io))?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Sink for StreamSink {
+    type SinkItem = Event;
+    type SinkError = Error;
+
+    fn start_send(&mut self, event: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
+        if self.buf.len() >= self.config.buffer_size {
+            if self.poll_write()?.is_not_ready() {
+                return Ok(AsyncSink::NotReady(event));
+            }
+        }
+        encode_event(&mut *self.encoder, &event, &mut self.buf)?;
+        self.event_ends.push(self.buf.len());
+        Ok(AsyncSink::Ready)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
+        if self.buf.is_empty() {
+            return Ok(Async::Ready(()));
+        }
+        self.poll_write()
+    }
+
+    /// Writes out the buffer, closes stdin of the process and waits for it to exit.
+    fn close(&mut self) -> Poll<(), Self::SinkError> {
+        try_ready!(self.poll_complete());
+        if let State::Running { child, stdin } = &mut self.state {
+            stdin.take();
+            let status = try_ready!(child.poll().wrap_err_id(ErrorId::Io));
+            if status.success() {
+                info!("[{}] process exited", self.config.id);
+            } else {
+                warn!("[{}] process exited with {}", self.config.id, status);
+            }
+        }
+        self.state = State::Stopped;
+        Ok(Async::Ready(()))
+    }
+}
+
+/// Runs the command per batch of events writing the encoded batch to its stdin. The batch is
+/// retried if the command fails.
+struct ExecWriter {
+    config: Arc<Config>,
+    encoder: Box<encoder::Encoder>,
+}
+
+impl ExecWriter {
+    /// Runs the command with the `input` on stdin. Succeeds if the command exits successfully.
+    fn run(config: &Config, input: Arc<Vec<u8>>) -> BoxFuture<(), Error> {
+        let mut child = match config.spawn() {
+            Ok(v) => v,
+            Err(e) => return Box::new(future::err(e.wrap_id(ErrorId::Io))),
+        };
+        let stdin = child.stdin().take().unwrap();
+        Box::new(tokio::io::write_all(stdin, ArcBytes(input))
+            // The stdin is dropped here so the command sees EOF.
+            .then(|r| r.map(|_| {}))
+            .then(|write| child
+                .then(move |status| match (status, write) {
+                    (Ok(status), _) if !status.success() => Err(exit_error(status)),
+                    (Ok(_), write) => write.wrap_err_id(ErrorId::Io),
+                    (Err(e), _) => Err(e.wrap_id(ErrorId::Io)),
+                })))
+    }
+}
+
+impl BatchWriter for ExecWriter {
+    fn encode(&mut self, event: &Event) -> Result<Vec<u8>> {
+        let mut r = Vec::new();
+        encode_event(&mut *self.encoder, event, &mut r)?;
+        Ok(r)
+    }
+
+    fn write(&mut self, items: Vec<BatchItem>) -> BoxFuture<(), Error> {
+        let events = items.len();
+        let input = Arc::new(BatchItem::concat(&items));
+        debug!("[{}] processing {} events ({} bytes)", self.config.id, events, input.len());
+        let config = self.config.clone();
+        Box::new(FutureRetry::new(clone!(config => move || Self::run(&config, input.clone())),
+                self.config.retry_handler(self.config.max_retries, "processing batch with"))
+            .inspect(move |_| debug!("[{}] processed {} events", config.id, events)))
+    }
+}
+
+/// Shared buffer that can be written with `write_all()`.
+struct ArcBytes(Arc<Vec<u8>>);
+
+impl AsRef<[u8]> for ArcBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::stream;
+    use std::fs;
+
+    use super::*;
+    use crate::util::test::TempDir;
+
+    fn new(config: Value) -> Result<Box<Output>> {
+        ProviderImpl.new(New {
+            config: config.into(),
+            common_config: Default::default(),
+            metrics: Default::default(),
+        })
+    }
+
+    fn events(n: usize) -> Vec<Event> {
+        (0..n)
+            .map(|i| {
+                let mut e = Event::new();
+                e.fields_mut().insert("message".into(), Value::String(i.to_string()));
+                e
+            })
+            .collect()
+    }
+
+    fn send(config: Value, events: Vec<Event>) -> Result<()> {
+        let output = new(config).unwrap();
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(output.start()
+            .and_then(|started| stream::iter_ok(events).forward(started.sink))
+            .map(|_| {}))
+    }
+
+    fn lines(n: usize) -> String {
+        (0..n).map(|i| format!("{}\n", i)).collect()
+    }
+
+    #[test]
+    fn stream() {
+        let dir = TempDir::new("stream");
+        send(value!{{
+            COMMAND => ["sh", "-c", format!("cat > {}", dir.path("out"))],
+            CODEC => {"type" => "template", "format" => "%{message}\n"},
+        }}, events(100)).unwrap();
+        assert_eq!(dir.read("out"), lines(100));
+    }
+
+    #[test]
+    fn restart() {
+        let dir = TempDir::new("restart");
+        // Each process reads a single line and exits.
+        let output = new(value!{{
+            COMMAND => format!("echo >> {} && read l && echo $l >> {}",
+                dir.path("spawns"), dir.path("out")),
+            CODEC => {"type" => "template", "format" => "%{message}\n"},
+            BUFFER_SIZE => 1,
+            RESTART_DELAY => "1ms",
+            MAX_RESTART_DELAY => "1ms",
+        }}).unwrap();
+        // Give each process the time to exit before sending the next event.
+        let events = stream::iter_ok(events(5))
+            .and_then(|e| Delay::new(Instant::now() + Duration::from_millis(100))
+                .map(move |_| e)
+                .map_err(|e| Error::new(ErrorId::Unknown, e.to_string())));
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(output.start()
+            .and_then(|started| events.forward(started.sink))
+            .map(|_| {}))
+            .unwrap();
+
+        let spawns = dir.read("spawns").lines().count();
+        let out = dir.read("out").lines()
+            .map(|l| l.parse::<usize>().unwrap())
+            .collect::<Vec<_>>();
+        assert!(spawns > 1, "{}", spawns);
+        assert!(out.len() > 1, "{:?}", out);
+        // The events written to a process that exits without reading them are lost.
+        assert!(out.windows(2).all(|w| w[0] < w[1]) && out.iter().all(|&i| i < 5), "{:?}", out);
+    }
+
+    #[test]
+    fn batch() {
+        let dir = TempDir::new("batch");
+        send(value!{{
+            COMMAND => format!("cat > {}/$(date +%s%N)", dir.path("")),
+            CODEC => {"type" => "template", "format" => "%{message}\n"},
+            MODE => MODE_BATCH,
+            "batch_max_events" => 3,
+        }}, events(7)).unwrap();
+        let mut batches = fs::read_dir(dir.path("")).unwrap()
+            .map(|e| fs::read_to_string(e.unwrap().path()).unwrap())
+            .collect::<Vec<_>>();
+        batches.sort_by_key(|b| b.lines().next().unwrap().parse::<u32>().unwrap());
+        assert_eq!(batches, vec!["0\n1\n2\n", "3\n4\n5\n", "6\n"]);
+    }
+
+    #[test]
+    fn batch_retry() {
+        let dir = TempDir::new("batch_retry");
+        // Fails on the first attempt.
+        let flag = dir.path("flag");
+        send(value!{{
+            COMMAND => format!("if [ -e {0} ]; then cat > {1}; else touch {0}; exit 1; fi",
+                flag, dir.path("out")),
+            CODEC => {"type" => "template", "format" => "%{message}\n"},
+            MODE => MODE_BATCH,
+            RESTART_DELAY => "1ms",
+        }}, events(3)).unwrap();
+        assert_eq!(dir.read("out"), lines(3));
+    }
+
+    #[test]
+    fn batch_retries_exhausted() {
+        let e = send(value!{{
+            COMMAND => "exit 1",
+            MODE => MODE_BATCH,
+            MAX_RETRIES => 1,
+            RESTART_DELAY => "1ms",
+        }}, events(1)).err().unwrap();
+        assert_eq!(*e.id(), ErrorId::Io);
+    }
+
+    #[test]
+    fn invalid_config() {
+        for config in vec![
+            value!{{}},
+            value!{{ COMMAND => [] }},
+            value!{{ COMMAND => "true", MODE => "foo" }},
+            value!{{ COMMAND => "true", BUFFER_SIZE => 0 }},
+            value!{{ COMMAND => "true", MAX_RETRIES => -1 }},
+        ] {
+            assert!(new(config).is_err());
+        }
+    }
+}
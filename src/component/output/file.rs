@@ -0,0 +1,579 @@
+use chrono::Utc;
+use flate2::Compression as Level;
+use flate2::write::GzEncoder;
+use futures::future;
+use futures::prelude::*;
+use log::*;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use super::*;
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::event::*;
+use crate::util::futures::*;
+use crate::util::template::Template;
+
+pub const NAME: &'static str = "file";
+
+const PATH: &'static str = "path";
+const MAX_OPEN_FILES: &'static str = "max_open_files";
+const ROTATE_SIZE: &'static str = "rotate_size";
+const ROTATE_INTERVAL: &'static str = "rotate_interval";
+const COMPRESSION: &'static str = "compression";
+const COMPRESSION_NONE: &'static str = "none";
+const COMPRESSION_GZIP: &'static str = "gzip";
+const FSYNC: &'static str = "fsync";
+const FSYNC_NEVER: &'static str = "never";
+const FSYNC_FLUSH: &'static str = "flush";
+
+const DEFAULT_CODEC: &'static str = "json_lines";
+const DEFAULT_MAX_OPEN_FILES: usize = 64;
+
+/// Size of the per-file buffer of the encoded events after reaching which the buffer is written
+/// to the file.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::Output,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Box<Output>> {
+        Ok(Box::new(FileOutput {
+            config: Arc::new(Config::parse(ctx.config)?),
+        }))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Compression {
+    None,
+    Gzip,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Fsync {
+    Never,
+
+    /// Fsync the files each time the sink is flushed.
+    Flush,
+
+    /// Fsync the files when the sink is flushed but not more often than the specified interval.
+    Interval(Duration),
+}
+
+struct Config {
+    path: Template,
+    encoder_factory: Arc<encoder::Factory>,
+
+    /// Max number of files kept open at once. When the limit is reached the least recently
+    /// written file is closed.
+    max_open_files: usize,
+
+    /// Size of the file in bytes after reaching which the file is rotated. For gzip-compressed
+    /// files the compressed size is used.
+    rotate_size: Option<u64>,
+
+    /// Max time a file is written to before it's rotated.
+    rotate_interval: Option<Duration>,
+
+    compression: Compression,
+    fsync: Fsync,
+}
+
+impl Config {
+    fn parse(mut config: Spanned<Value>) -> Result<Self> {
+        let path = Template::parse_path(config.get(PATH)?)?;
+
+        let encoder_factory = parse_codec(&mut config, DEFAULT_CODEC)?;
+
+        let max_open_files = if let Some(v) = config.get_opt(MAX_OPEN_FILES)? {
+            let r = *v.as_int()?;
+            if r <= 0 {
+                return Err(v.new_error(format!("`{}` must be positive", MAX_OPEN_FILES)));
+            }
+            r as usize
+        } else {
+            DEFAULT_MAX_OPEN_FILES
+        };
+
+        let rotate_size = if let Some(v) = config.get_opt(ROTATE_SIZE)? {
+            let r = *v.as_int()?;
+            if r <= 0 {
+                return Err(v.new_error(format!("`{}` must be positive", ROTATE_SIZE)));
+            }
+            Some(r as u64)
+        } else {
+            None
+        };
+
+        let rotate_interval = config.get_opt_duration(ROTATE_INTERVAL)?;
+
+        let compression = if let Some(v) = config.get_opt(COMPRESSION)? {
+            match v.as_str()? {
+                COMPRESSION_NONE => Compression::None,
+                COMPRESSION_GZIP => Compression::Gzip,
+                _ => return Err(v.new_error(format!("`{}` must be one of [\"{}\", \"{}\"]",
+                    COMPRESSION, COMPRESSION_NONE, COMPRESSION_GZIP))),
+            }
+        } else {
+            Compression::None
+        };
+
+        let fsync = if let Some(v) = config.get_opt(FSYNC)? {
+            match v.as_str()? {
+                FSYNC_NEVER => Fsync::Never,
+                FSYNC_FLUSH => Fsync::Flush,
+                _ => Fsync::Interval(v.as_duration().map_err(|_| v.new_error(format!(
+                    "`{}` must be one of [\"{}\", \"{}\"] or a duration",
+                    FSYNC, FSYNC_NEVER, FSYNC_FLUSH)))?),
+            }
+        } else {
+            Fsync::Never
+        };
+
+        Ok(Self {
+            path,
+            encoder_factory,
+            max_open_files,
+            rotate_size,
+            rotate_interval,
+            compression,
+            fsync,
+        })
+    }
+}
+
+struct FileOutput {
+    config: Arc<Config>,
+}
+
+impl Output for FileOutput {
+    fn start(&self) -> BoxFuture<Started, Error> {
+        Box::new(future::ok(Started {
+            sink: Box::new(FileSink::new(self.config.clone())),
+        }))
+    }
+}
+
+/// File writer that counts the bytes written to the file.
+struct CountingFile {
+    file: File,
+    len: u64,
+}
+
+impl Write for CountingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let r = self.file.write(buf)?;
+        self.len += r as u64;
+        Ok(r)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+enum Writer {
+    Plain(CountingFile),
+    Gzip(GzEncoder<CountingFile>),
+}
+
+impl Writer {
+    fn file(&self) -> &CountingFile {
+        match self {
+            Writer::Plain(w) => w,
+            Writer::Gzip(w) => w.get_ref(),
+        }
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            Writer::Plain(w) => w.write_all(buf),
+            Writer::Gzip(w) => w.write_all(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Writer::Plain(w) => w.flush(),
+            Writer::Gzip(w) => w.flush(),
+        }
+    }
+
+    /// Writes the pending data and the gzip trailer if needed.
+    fn finish(self) -> io::Result<File> {
+        Ok(match self {
+            Writer::Plain(w) => w.file,
+            Writer::Gzip(w) => w.finish()?.file,
+        })
+    }
+}
+
+struct OpenFile {
+    writer: Writer,
+    encoder: Box<encoder::Encoder>,
+
+    /// Whether the last line of the encoded events needs to be terminated on flush.
+    line_oriented: bool,
+
+    /// Encoded events not yet written to the file.
+    buf: Vec<u8>,
+
+    /// Whether the data written to the file so far doesn't end with newline.
+    unterminated: bool,
+
+    opened: Instant,
+
+    /// Value of `FileSink::clock` when the file was last written to.
+    last_used: u64,
+}
+
+impl OpenFile {
+    fn open(path: &Path, config: &Config) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let len = file.metadata()?.len();
+        // The file could be written by the encoder of the evicted file or before the restart.
+        let encoder = if len > 0 {
+            config.encoder_factory.new_appending()
+        } else {
+            config.encoder_factory.new()
+        };
+        let file = CountingFile { file, len };
+        let writer = match config.compression {
+            Compression::None => Writer::Plain(file),
+            // Concatenated gzip members make a valid gzip file so appending is fine.
+            Compression::Gzip => Writer::Gzip(GzEncoder::new(file, Level::default())),
+        };
+        Ok(Self {
+            writer,
+            encoder,
+            line_oriented: config.encoder_factory.line_oriented(),
+            buf: Vec::new(),
+            unterminated: false,
+            opened: Instant::now(),
+            last_used: 0,
+        })
+    }
+
+    fn needs_rotation(&self, config: &Config) -> bool {
+        config.rotate_size.map(|s| self.writer.file().len >= s).unwrap_or(false) ||
+            config.rotate_interval.map(|i| self.opened.elapsed() >= i).unwrap_or(false)
+    }
+
+    fn write_buf(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            self.writer.write_all(&self.buf)?;
+            self.unterminated = self.buf.last() != Some(&b'\n');
+            self.buf.clear();
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self, fsync: bool) -> io::Result<()> {
+        self.encoder.flush(&mut self.buf).map_err(|e| io::Error::new(io::ErrorKind::Other,
+            e.to_string()))?;
+        // Encoders that separate events rather than terminate them don't end the last event with
+        // a newline.
+        let unterminated = self.buf.last().map(|&b| b != b'\n').unwrap_or(self.unterminated);
+        if self.line_oriented && unterminated {
+            self.buf.push(b'\n');
+        }
+        self.write_buf()?;
+        self.writer.flush()?;
+        if fsync {
+            self.writer.file().file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    fn close(mut self, fsync: bool) -> io::Result<()> {
+        self.flush(false)?;
+        let file = self.writer.finish()?;
+        if fsync {
+            file.sync_data()?;
+        }
+        Ok(())
+    }
+}
+
+/// Moves the file at `path` to the first free `<path>.<n>` path.
+fn rotate(path: &Path) -> io::Result<()> {
+    for n in 1.. {
+        let mut rotated = path.as_os_str().to_owned();
+        rotated.push(format!(".{}", n));
+        let rotated = PathBuf::from(rotated);
+        if !rotated.exists() {
+            debug!("rotating {:?} to {:?}", path, rotated);
+            return fs::rename(path, rotated);
+        }
+    }
+    unreachable!();
+}
+
+/// Writes the events to the files the path template renders to.
+///
+/// Unlike the network outputs this sink doesn't use `BatchSink`. The batch items are encoded as
+/// standalone messages, while each file here has its own encoder that keeps state between the
+/// events, such as the csv header written once per file. The writes are also done in place on the
+/// blocking thread rather than as a future per batch, and are already buffered per file.
+struct FileSink {
+    config: Arc<Config>,
+    files: HashMap<PathBuf, OpenFile>,
+
+    /// Incremented on each write, used to find the least recently used file.
+    clock: u64,
+
+    last_fsync: Instant,
+}
+
+impl FileSink {
+    fn new(config: Arc<Config>) -> Self {
+        Self {
+            config,
+            files: HashMap::new(),
+            clock: 0,
+            last_fsync: Instant::now(),
+        }
+    }
+
+    fn fsync_on_flush(&self) -> bool {
+        match self.config.fsync {
+            Fsync::Never => false,
+            Fsync::Flush => true,
+            Fsync::Interval(i) => self.last_fsync.elapsed() >= i,
+        }
+    }
+
+    fn write_event(&mut self, event: &Event) -> io::Result<()> {
+        let path = PathBuf::from(self.config.path.render_path(event, &Utc::now()));
+
+        let rotate_file = self.files.get(&path)
+            .map(|f| f.needs_rotation(&self.config))
+            .unwrap_or(false);
+        if rotate_file {
+            let fsync = self.config.fsync != Fsync::Never;
+            self.files.remove(&path).unwrap().close(fsync)?;
+            rotate(&path)?;
+        }
+
+        if !self.files.contains_key(&path) {
+            if self.files.len() >= self.config.max_open_files {
+                let lru = self.files.iter()
+                    .min_by_key(|(_, f)| f.last_used)
+                    .map(|(p, _)| p.clone())
+                    .unwrap();
+                debug!("closing least recently used file {:?}", lru);
+                let fsync = self.config.fsync != Fsync::Never;
+                self.files.remove(&lru).unwrap().close(fsync)?;
+            }
+            debug!("opening {:?}", path);
+            let file = OpenFile::open(&path, &self.config)?;
+            self.files.insert(path.clone(), file);
+        }
+
+        self.clock += 1;
+        let file = self.files.get_mut(&path).unwrap();
+        file.last_used = self.clock;
+        file.encoder.encode(event, &mut file.buf).map_err(|e| io::Error::new(
+            io::ErrorKind::Other, e.to_string()))?;
+        if file.buf.len() >= BUFFER_SIZE {
+            file.write_buf()?;
+        }
+        Ok(())
+    }
+
+    fn flush_files(&mut self) -> io::Result<()> {
+        let fsync = self.fsync_on_flush();
+        for file in self.files.values_mut() {
+            file.flush(fsync)?;
+        }
+        if fsync {
+            self.last_fsync = Instant::now();
+        }
+        Ok(())
+    }
+
+    fn close_files(&mut self) -> io::Result<()> {
+        let fsync = self.config.fsync != Fsync::Never;
+        for (_, file) in self.files.drain() {
+            file.close(fsync)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs blocking IO operation on the current thread pool thread.
+fn blocking_io<T>(f: impl FnOnce() -> io::Result<T>) -> Poll<T, Error> {
+    match tokio_threadpool::blocking(f).unwrap() {
+        Async::Ready(r) => Ok(Async::Ready(r.wrap_err_id(ErrorId::Io)?)),
+        Async::NotReady => Ok(Async::NotReady),
+    }
+}
+
+impl Sink for FileSink {
+    type SinkItem = Event;
+    type SinkError = Error;
+
+    fn start_send(&mut self, event: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
+        Ok(match blocking_io(|| self.write_event(&event))? {
+            Async::Ready(()) => AsyncSink::Ready,
+            Async::NotReady => AsyncSink::NotReady(event),
+        })
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
+        blocking_io(|| self.flush_files())
+    }
+
+    fn close(&mut self) -> Poll<(), Self::SinkError> {
+        blocking_io(|| self.close_files())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use flate2::read::MultiGzDecoder;
+    use std::io::Read;
+
+    use super::*;
+    use crate::util::test::TempDir;
+
+    fn sink(config: Value) -> FileSink {
+        let mut config = Config::parse(config.into()).unwrap();
+        config.fsync = Fsync::Flush;
+        FileSink::new(Arc::new(config))
+    }
+
+    fn event(service: &str, message: &str) -> Event {
+        let mut e = Event::new();
+        e.fields_mut().insert("service".into(), Value::String(service.into()));
+        e.fields_mut().insert("message".into(), Value::String(message.into()));
+        e
+    }
+
+    #[test]
+    fn templated_path() {
+        let dir = TempDir::new("templated_path");
+        let mut sink = sink(value!{{
+            PATH => dir.path("%{service}/out.log"),
+            CODEC => {"type" => "template", "format" => "%{message}"},
+            MAX_OPEN_FILES => 1,
+        }});
+        sink.write_event(&event("a", "1")).unwrap();
+        sink.write_event(&event("b", "2")).unwrap();
+        sink.write_event(&event("a", "3")).unwrap();
+        sink.flush_files().unwrap();
+        sink.write_event(&event("a", "4")).unwrap();
+        sink.close_files().unwrap();
+        assert_eq!(dir.read("a/out.log"), "1\n3\n4\n");
+        assert_eq!(dir.read("b/out.log"), "2\n");
+    }
+
+    #[test]
+    fn csv_header_once() {
+        let dir = TempDir::new("csv_header_once");
+        let config = value!{{
+            PATH => dir.path("%{service}.csv"),
+            CODEC => {"type" => "csv", "columns" => ["message"]},
+            MAX_OPEN_FILES => 1,
+        }};
+        {
+            let mut sink = sink(config.clone());
+            sink.write_event(&event("a", "1")).unwrap();
+            // Evicts and then reopens "a.csv".
+            sink.write_event(&event("b", "2")).unwrap();
+            sink.write_event(&event("a", "3")).unwrap();
+            sink.close_files().unwrap();
+        }
+        // Reopens "a.csv" after the restart.
+        let mut sink = sink(config);
+        sink.write_event(&event("a", "4")).unwrap();
+        sink.close_files().unwrap();
+
+        assert_eq!(dir.read("a.csv"), "message\n1\n3\n4\n");
+        assert_eq!(dir.read("b.csv"), "message\n2\n");
+    }
+
+    #[test]
+    fn rotate_size() {
+        let dir = TempDir::new("rotate_size");
+        let mut sink = sink(value!{{
+            PATH => dir.path("out.log"),
+            CODEC => {"type" => "template", "format" => "%{message}"},
+            ROTATE_SIZE => 3,
+        }});
+        for m in &["1", "2", "3", "4"] {
+            sink.write_event(&event("a", m)).unwrap();
+            sink.flush_files().unwrap();
+        }
+        sink.close_files().unwrap();
+        assert_eq!(dir.read("out.log.1"), "1\n2\n");
+        assert_eq!(dir.read("out.log"), "3\n4\n");
+    }
+
+    #[test]
+    fn gzip() {
+        let dir = TempDir::new("gzip");
+        let config = value!{{
+            PATH => dir.path("out.log.gz"),
+            COMPRESSION => COMPRESSION_GZIP,
+        }};
+        for m in &["1", "2"] {
+            let mut sink = sink(config.clone());
+            sink.write_event(&event("a", m)).unwrap();
+            sink.close_files().unwrap();
+        }
+        let mut s = String::new();
+        MultiGzDecoder::new(File::open(dir.path("out.log.gz")).unwrap())
+            .read_to_string(&mut s).unwrap();
+        assert_eq!(s, concat!(r#"{"message":"1","service":"a"}"#, "\n",
+            r#"{"message":"2","service":"a"}"#, "\n"));
+    }
+
+    #[test]
+    fn binary_codec() {
+        let dir = TempDir::new("binary_codec");
+        let mut sink = sink(value!{{
+            PATH => dir.path("out.msgpack"),
+            CODEC => "msgpack",
+        }});
+        sink.write_event(&Event::new()).unwrap();
+        sink.flush_files().unwrap();
+        sink.close_files().unwrap();
+        assert_eq!(fs::read(dir.path("out.msgpack")).unwrap(), b"\x80");
+    }
+
+    #[test]
+    fn invalid_config() {
+        let new = |config: Value| ProviderImpl.new(New {
+            config: config.into(),
+            common_config: Default::default(),
+            metrics: Default::default(),
+        });
+        assert!(new(value!{{}}).is_err());
+        assert!(new(value!{{ PATH => "/tmp/%{a" }}).is_err());
+        assert!(new(value!{{ PATH => "/tmp/a", CODEC => "unknown" }}).is_err());
+        assert!(new(value!{{ PATH => "/tmp/a", MAX_OPEN_FILES => 0 }}).is_err());
+        assert!(new(value!{{ PATH => "/tmp/a", COMPRESSION => "zip" }}).is_err());
+        assert!(new(value!{{ PATH => "/tmp/a", FSYNC => "sometimes" }}).is_err());
+        assert!(new(value!{{ PATH => "/tmp/a", FSYNC => "1s" }}).is_ok());
+    }
+}
@@ -0,0 +1,710 @@
+use flate2::Compression as Level;
+use flate2::write::GzEncoder;
+use futures::future;
+use futures::prelude::*;
+use futures_retry::FutureRetry;
+use hyper::{Body, Chunk, Method, Request, StatusCode, Uri};
+use hyper::client::HttpConnector;
+use hyper::header::{self, HeaderMap, HeaderName, HeaderValue};
+use log::*;
+use std::borrow::Cow;
+use std::io::Write;
+use std::time::Duration;
+use tokio::timer::Timeout;
+
+use super::*;
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::event::*;
+use crate::metric::{self, Metrics};
+use crate::retry::RetryErrorHandler;
+use crate::util::futures::*;
+
+pub const NAME: &'static str = "http";
+
+const URL: &'static str = "url";
+const METHOD: &'static str = "method";
+const HEADERS: &'static str = "headers";
+const BATCH_FORMAT: &'static str = "batch_format";
+const BATCH_FORMAT_NDJSON: &'static str = "ndjson";
+const BATCH_FORMAT_JSON_ARRAY: &'static str = "json_array";
+const COMPRESSION: &'static str = "compression";
+const COMPRESSION_NONE: &'static str = "none";
+const COMPRESSION_GZIP: &'static str = "gzip";
+const REQUEST_TIMEOUT: &'static str = "request_timeout";
+const MAX_RETRIES: &'static str = "max_retries";
+const RETRY_DELAY: &'static str = "retry_delay";
+const MAX_RETRY_DELAY: &'static str = "max_retry_delay";
+
+const DEFAULT_CODEC: &'static str = "json_lines";
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::Output,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Box<Output>> {
+        let mut config = ctx.config;
+
+        let id = ctx.common_config.id.clone().unwrap_or_else(|| NAME.into());
+        let dead_letters = ctx.common_config.dead_letters(&id);
+
+        let url = parse_url(config.get(URL)?)?;
+
+        let method = if let Some(v) = config.get_opt(METHOD)? {
+            Method::from_bytes(v.as_str()?.as_bytes())
+                .map_err(|_| v.new_error(format!("invalid `{}`", METHOD)))?
+        } else {
+            Method::POST
+        };
+
+        let encoder_factory = parse_codec(&mut config, DEFAULT_CODEC)?;
+
+        let batch_format = if let Some(v) = config.get_opt(BATCH_FORMAT)? {
+            match v.as_str()? {
+                BATCH_FORMAT_NDJSON => BatchFormat::Ndjson,
+                BATCH_FORMAT_JSON_ARRAY => BatchFormat::JsonArray,
+                _ => return Err(v.new_error(format!("`{}` must be one of [\"{}\", \"{}\"]",
+                    BATCH_FORMAT, BATCH_FORMAT_NDJSON, BATCH_FORMAT_JSON_ARRAY))),
+            }
+        } else {
+            BatchFormat::Ndjson
+        };
+
+        let mut headers = parse_headers(&config)?;
+        if !headers.contains_key(header::CONTENT_TYPE) {
+            let content_type = match batch_format {
+                BatchFormat::Ndjson => "application/x-ndjson",
+                BatchFormat::JsonArray => "application/json",
+            };
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+        }
+
+        let compression = parse_compression(&config)?;
+        if compression == Compression::Gzip {
+            headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+        }
+
+        let batch = BatchConfig::parse(&config)?;
+        let client = Arc::new(ClientConfig::parse(&config, id.clone(), ctx.metrics)?);
+
+        Ok(Box::new(HttpOutput {
+            config: Arc::new(Config {
+                id,
+                url,
+                method,
+                headers,
+                encoder_factory,
+                batch_format,
+                batch,
+                compression,
+                client,
+                dead_letters,
+            }),
+        }))
+    }
+}
+
+pub(super) fn parse_url(v: &Spanned<Value>) -> Result<Uri> {
+    let r: Uri = v.as_str()?.parse()
+        .map_err(|e| v.new_error(format!("invalid URL: {}", e)))?;
+    if r.scheme_part().map(|s| s.as_str()) != Some("http") || r.host().is_none() {
+        return Err(v.new_error("only absolute `http://` URLs are supported"));
+    }
+    Ok(r)
+}
+
+/// Parses the `headers` map of header names to values.
+pub(super) fn parse_headers(config: &Spanned<Value>) -> Result<HeaderMap> {
+    let mut r = HeaderMap::new();
+    if let Some(v) = config.get_opt(HEADERS)? {
+        for (name, value) in v.as_map()? {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| v.new_error(format!("invalid header name `{}`", name)))?;
+            let value = HeaderValue::from_str(value.as_str()?)
+                .map_err(|_| value.new_error("invalid header value"))?;
+            r.insert(name, value);
+        }
+    }
+    Ok(r)
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(super) enum Compression {
+    None,
+    Gzip,
+}
+
+impl Compression {
+    pub fn compress(self, body: Vec<u8>) -> Vec<u8> {
+        match self {
+            Compression::None => body,
+            Compression::Gzip => {
+                let mut w = GzEncoder::new(Vec::new(), Level::default());
+                w.write_all(&body).unwrap();
+                w.finish().unwrap()
+            }
+        }
+    }
+}
+
+pub(super) fn parse_compression(config: &Spanned<Value>) -> Result<Compression> {
+    Ok(if let Some(v) = config.get_opt(COMPRESSION)? {
+        match v.as_str()? {
+            COMPRESSION_NONE => Compression::None,
+            COMPRESSION_GZIP => Compression::Gzip,
+            _ => return Err(v.new_error(format!("`{}` must be one of [\"{}\", \"{}\"]",
+                COMPRESSION, COMPRESSION_NONE, COMPRESSION_GZIP))),
+        }
+    } else {
+        Compression::None
+    })
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum BatchFormat {
+    /// The encoded events are separated by newlines.
+    Ndjson,
+
+    /// The encoded events are elements of JSON array.
+    JsonArray,
+}
+
+pub(super) struct ClientConfig {
+    id: String,
+    request_timeout: Duration,
+    max_retries: Option<usize>,
+    retry_delay: Duration,
+    max_retry_delay: Duration,
+    metrics: Arc<Metrics>,
+}
+
+impl ClientConfig {
+    pub fn parse(config: &Spanned<Value>, id: String, metrics: Arc<Metrics>) -> Result<Self> {
+        let max_retries = if let Some(v) = config.get_opt(MAX_RETRIES)? {
+            let r = *v.as_int()?;
+            if r < 0 {
+                return Err(v.new_error(format!("`{}` can't be negative", MAX_RETRIES)));
+            }
+            Some(r as usize)
+        } else {
+            None
+        };
+        metrics.set_default(error_metric(&id), metric::Value::Counter(0.into()));
+        Ok(Self {
+            request_timeout: config.get_opt_duration(REQUEST_TIMEOUT)?
+                .unwrap_or(DEFAULT_REQUEST_TIMEOUT),
+            max_retries,
+            retry_delay: config.get_opt_duration(RETRY_DELAY)?.unwrap_or(DEFAULT_RETRY_DELAY),
+            max_retry_delay: config.get_opt_duration(MAX_RETRY_DELAY)?
+                .unwrap_or(DEFAULT_MAX_RETRY_DELAY),
+            id,
+            metrics,
+        })
+    }
+
+    pub fn retry_handler(&self) -> RetryErrorHandler {
+        RetryErrorHandler::new(self.max_retries, self.retry_delay, self.max_retry_delay,
+            &self.id, "sending request")
+    }
+}
+
+/// Name of the counter of the responses with the `status`.
+pub(super) fn status_metric(id: &str, status: StatusCode) -> String {
+    format!("output.{}.http.status.{}", id, status.as_u16())
+}
+
+/// Name of the counter of the requests that failed without a response or timed out.
+pub(super) fn error_metric(id: &str) -> String {
+    format!("output.{}.http.error", id)
+}
+
+pub(super) struct Response {
+    pub status: StatusCode,
+    pub body: Chunk,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(super) enum Outcome {
+    Success,
+
+    /// The request may succeed if retried: server errors and timeouts.
+    Retry,
+
+    /// The request won't succeed no matter how many times it's retried.
+    Fail,
+}
+
+impl Outcome {
+    pub fn of(status: StatusCode) -> Self {
+        if status.is_success() {
+            Outcome::Success
+        } else if status.is_server_error()
+            || status == StatusCode::REQUEST_TIMEOUT
+            || status == StatusCode::TOO_MANY_REQUESTS
+        {
+            Outcome::Retry
+        } else {
+            Outcome::Fail
+        }
+    }
+}
+
+/// HTTP client that applies the request timeout and counts the responses per status code.
+#[derive(Clone)]
+pub(super) struct Client {
+    config: Arc<ClientConfig>,
+    inner: hyper::Client<HttpConnector>,
+}
+
+impl Client {
+    pub fn new(config: Arc<ClientConfig>) -> Self {
+        Self {
+            config,
+            inner: hyper::Client::new(),
+        }
+    }
+
+    pub fn retry_handler(&self) -> RetryErrorHandler {
+        self.config.retry_handler()
+    }
+
+    pub fn send(&self, request: Request<Body>) -> BoxFuture<Response, Error> {
+        let config = self.config.clone();
+        let f = self.inner.request(request)
+            .and_then(|resp| {
+                let status = resp.status();
+                resp.into_body().concat2().map(move |body| Response { status, body })
+            });
+        Box::new(Timeout::new(f, self.config.request_timeout)
+            .then(move |r| {
+                let r = r.map_err(|e| if e.is_elapsed() {
+                    Error::new(ErrorId::Io, format!("request timed out after {}",
+                        humantime::format_duration(config.request_timeout)))
+                } else if e.is_inner() {
+                    e.into_inner().unwrap().wrap_id(ErrorId::Io)
+                } else {
+                    Error::new(ErrorId::Unknown, e.to_string())
+                });
+                match &r {
+                    Ok(resp) => {
+                        let name = status_metric(&config.id, resp.status);
+                        config.metrics.set_default(name.clone(),
+                            metric::Value::Counter(0.into()));
+                        config.metrics.inc(&name, 1);
+                    }
+                    Err(_) => config.metrics.inc(&error_metric(&config.id), 1),
+                }
+                r
+            }))
+    }
+}
+
+/// Returns the beginning of the response body for logging.
+pub(super) fn body_excerpt(body: &[u8]) -> Cow<'_, str> {
+    const MAX_LEN: usize = 200;
+    if body.len() <= MAX_LEN {
+        String::from_utf8_lossy(body)
+    } else {
+        format!("{}...", String::from_utf8_lossy(&body[..MAX_LEN])).into()
+    }
+}
+
+struct Config {
+    /// Used as the log context and in the metric names.
+    id: String,
+
+    url: Uri,
+    method: Method,
+    headers: HeaderMap,
+    encoder_factory: Arc<encoder::Factory>,
+    batch_format: BatchFormat,
+    batch: BatchConfig,
+    compression: Compression,
+    client: Arc<ClientConfig>,
+
+    /// Receives the events of the batches rejected by the server.
+    dead_letters: Option<DeadLetters>,
+}
+
+impl Config {
+    fn new_request(&self, body: &[u8]) -> Request<Body> {
+        let mut r = Request::new(Body::from(body.to_vec()));
+        *r.method_mut() = self.method.clone();
+        *r.uri_mut() = self.url.clone();
+        *r.headers_mut() = self.headers.clone();
+        r
+    }
+}
+
+struct HttpOutput {
+    config: Arc<Config>,
+}
+
+impl Output for HttpOutput {
+    fn start(&self) -> BoxFuture<Started, Error> {
+        Box::new(future::ok(Started {
+            sink: Box::new(BatchSink::new(self.config.batch, HttpWriter {
+                client: Client::new(self.config.client.clone()),
+                encoder: self.config.encoder_factory.new(),
+                config: self.config.clone(),
+            })),
+        }))
+    }
+}
+
+/// Sends each batch with a single request.
+struct HttpWriter {
+    config: Arc<Config>,
+    client: Client,
+    encoder: Box<encoder::Encoder>,
+}
+
+impl BatchWriter for HttpWriter {
+    fn encode(&mut self, event: &Event) -> Result<Vec<u8>> {
+        let mut r = Vec::new();
+        encode_event(&mut *self.encoder, event, &mut r)?;
+        if self.config.batch_format == BatchFormat::Ndjson && r.last() != Some(&b'\n') {
+            r.push(b'\n');
+        }
+        Ok(r)
+    }
+
+    fn write(&mut self, items: Vec<BatchItem>) -> BoxFuture<(), Error> {
+        let body = match self.config.batch_format {
+            BatchFormat::Ndjson => BatchItem::concat(&items),
+            BatchFormat::JsonArray => {
+                let mut r = vec![b'['];
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        r.push(b',');
+                    }
+                    r.extend_from_slice(&item.data);
+                }
+                r.push(b']');
+                r
+            }
+        };
+        let body = self.config.compression.compress(body);
+        debug!("[{}] sending {} events ({} bytes)", self.config.id, items.len(), body.len());
+        let config = self.config.clone();
+        let client = self.client.clone();
+        Box::new(FutureRetry::new(clone!(config => move || {
+                client.send(config.new_request(&body))
+                    .and_then(|resp| match Outcome::of(resp.status) {
+                        Outcome::Success => Ok(None),
+                        Outcome::Retry => Err(Error::new(ErrorId::Io,
+                            format!("server responded with {}: {}", resp.status,
+                                body_excerpt(&resp.body)))),
+                        Outcome::Fail => Ok(Some(Error::new(ErrorId::Io,
+                            format!("server rejected the request with {}: {}", resp.status,
+                                body_excerpt(&resp.body))))),
+                    })
+            }),
+            self.client.retry_handler())
+            .map(move |rejected| if let Some(e) = rejected {
+                if let Some(dead_letters) = &config.dead_letters {
+                    for item in items {
+                        dead_letters.push(&e, item.event);
+                    }
+                } else {
+                    error!("[{}] dropping {} events: {}", config.id, items.len(), e);
+                }
+            }))
+    }
+}
+
+#[cfg(test)]
+pub(super) mod test {
+    use flate2::read::GzDecoder;
+    use futures::stream;
+    use hyper::Server;
+    use hyper::service::service_fn;
+    use parking_lot::Mutex;
+    use serde_json as json;
+    use std::collections::VecDeque;
+    use std::io::Read;
+    use std::net::SocketAddr;
+    use std::time::Instant;
+    use tokio::timer::Delay;
+
+    use super::*;
+    use crate::dead_letter;
+    use crate::util::test::TempDir;
+
+    /// HTTP server that records the requests and responds with the statuses from the queue,
+    /// or with 200 when the queue is empty.
+    pub struct MockServer {
+        addr: SocketAddr,
+        pub requests: Arc<Mutex<Vec<Request<Vec<u8>>>>>,
+        statuses: Arc<Mutex<VecDeque<(u16, String)>>>,
+    }
+
+    impl MockServer {
+        pub fn start(rt: &mut tokio::runtime::Runtime) -> Self {
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let statuses = Arc::new(Mutex::new(VecDeque::new()));
+            let server = Server::bind(&"127.0.0.1:0".parse().unwrap())
+                .serve(clone!(requests, statuses => move || {
+                    service_fn(clone!(requests, statuses => move |req| {
+                        let (parts, body): (_, Body) = Request::into_parts(req);
+                        body.concat2().map(clone!(requests, statuses => move |body| {
+                            requests.lock().push(Request::from_parts(parts, body.to_vec()));
+                            let (status, body) = statuses.lock().pop_front()
+                                .unwrap_or((200, String::new()));
+                            hyper::Response::builder()
+                                .status(status)
+                                .body(Body::from(body))
+                                .unwrap()
+                        }))
+                    }))
+                }));
+            let addr = server.local_addr();
+            rt.spawn(server.map_err(|e| panic!("{}", e)));
+            Self {
+                addr,
+                requests,
+                statuses,
+            }
+        }
+
+        pub fn url(&self, path: &str) -> String {
+            format!("http://{}{}", self.addr, path)
+        }
+
+        pub fn respond(&self, status: u16, body: &str) {
+            self.statuses.lock().push_back((status, body.into()));
+        }
+
+        pub fn bodies(&self) -> Vec<String> {
+            self.requests.lock().iter()
+                .map(|r| String::from_utf8(r.body().clone()).unwrap())
+                .collect()
+        }
+    }
+
+    fn new(config: Value, metrics: Arc<Metrics>) -> Result<Box<Output>> {
+        ProviderImpl.new(New {
+            config: config.into(),
+            common_config: Default::default(),
+            metrics,
+        })
+    }
+
+    fn events(n: usize) -> Vec<Event> {
+        (0..n)
+            .map(|i| {
+                let mut e = Event::new();
+                e.fields_mut().insert("message".into(), Value::String(i.to_string()));
+                e
+            })
+            .collect()
+    }
+
+    /// Sends the `events` to the output configured to post to the mock server.
+    fn send(config: impl FnOnce(&MockServer) -> Value, responses: &[(u16, &str)],
+        events: Vec<Event>) -> (Result<()>, MockServer, Arc<Metrics>)
+    {
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let server = MockServer::start(&mut rt);
+        for &(status, body) in responses {
+            server.respond(status, body);
+        }
+        let metrics = Arc::new(Metrics::new());
+        let output = new(config(&server), metrics.clone()).unwrap();
+        let r = rt.block_on(output.start()
+            .and_then(|started| stream::iter_ok(events).forward(started.sink))
+            .map(|_| {}));
+        (r, server, metrics)
+    }
+
+    fn status_count(metrics: &Metrics, status: u16) -> metric::Value {
+        metrics.get(&status_metric(NAME, StatusCode::from_u16(status).unwrap())).unwrap()
+    }
+
+    #[test]
+    fn batching() {
+        let (r, server, _) = send(|s| value!{{
+            URL => s.url("/events"),
+            BATCH_MAX_EVENTS => 2,
+            HEADERS => {"x-token" => "secret"},
+        }}, &[], events(5));
+        r.unwrap();
+        assert_eq!(server.bodies(), vec![
+            "{\"message\":\"0\"}\n{\"message\":\"1\"}\n",
+            "{\"message\":\"2\"}\n{\"message\":\"3\"}\n",
+            "{\"message\":\"4\"}\n",
+        ]);
+        let requests = server.requests.lock();
+        assert_eq!(requests[0].method(), Method::POST);
+        assert_eq!(requests[0].uri(), "/events");
+        assert_eq!(requests[0].headers()[header::CONTENT_TYPE], "application/x-ndjson");
+        assert_eq!(requests[0].headers()["x-token"], "secret");
+    }
+
+    #[test]
+    fn batch_timeout() {
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let server = MockServer::start(&mut rt);
+        let output = new(value!{{
+            URL => server.url("/"),
+            BATCH_TIMEOUT => "10ms",
+        }}, Default::default()).unwrap();
+        let late = Delay::new(Instant::now() + Duration::from_millis(200))
+            .map(|_| events(3).pop().unwrap())
+            .map_err(|e| Error::new(ErrorId::Unknown, e.to_string()))
+            .into_stream();
+        rt.block_on(output.start()
+            .and_then(|started| stream::iter_ok(events(2)).chain(late).forward(started.sink))
+            .map(|_| {}))
+            .unwrap();
+        assert_eq!(server.bodies(), vec![
+            "{\"message\":\"0\"}\n{\"message\":\"1\"}\n",
+            "{\"message\":\"2\"}\n",
+        ]);
+    }
+
+    #[test]
+    fn batch_max_bytes() {
+        let (r, server, _) = send(|s| value!{{
+            URL => s.url("/"),
+            BATCH_MAX_BYTES => 20,
+            CODEC => "json",
+        }}, &[], events(3));
+        r.unwrap();
+        assert_eq!(server.bodies(), vec![
+            "{\"message\":\"0\"}\n{\"message\":\"1\"}\n",
+            "{\"message\":\"2\"}\n",
+        ]);
+    }
+
+    #[test]
+    fn json_array_gzip() {
+        let (r, server, _) = send(|s| value!{{
+            URL => s.url("/"),
+            CODEC => "json",
+            BATCH_FORMAT => BATCH_FORMAT_JSON_ARRAY,
+            COMPRESSION => COMPRESSION_GZIP,
+            METHOD => "PUT",
+        }}, &[], events(3));
+        r.unwrap();
+        let requests = server.requests.lock();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method(), Method::PUT);
+        assert_eq!(requests[0].headers()[header::CONTENT_TYPE], "application/json");
+        assert_eq!(requests[0].headers()[header::CONTENT_ENCODING], "gzip");
+        let mut body = String::new();
+        GzDecoder::new(&requests[0].body()[..]).read_to_string(&mut body).unwrap();
+        let body: json::Value = json::from_str(&body).unwrap();
+        assert_eq!(body, json::json!([{"message": "0"}, {"message": "1"}, {"message": "2"}]));
+    }
+
+    #[test]
+    fn retry() {
+        let (r, server, metrics) = send(|s| value!{{
+            URL => s.url("/"),
+            RETRY_DELAY => "1ms",
+        }}, &[(500, ""), (503, ""), (429, "")], events(1));
+        r.unwrap();
+        assert_eq!(server.bodies(), vec!["{\"message\":\"0\"}\n"; 4]);
+        for &status in &[500, 503, 429, 200] {
+            assert_eq!(status_count(&metrics, status), metric::Value::Counter(1.into()));
+        }
+    }
+
+    #[test]
+    fn retries_exhausted() {
+        let (r, server, _) = send(|s| value!{{
+            URL => s.url("/"),
+            RETRY_DELAY => "1ms",
+            MAX_RETRIES => 1,
+        }}, &[(500, ""), (500, "")], events(1));
+        assert!(r.is_err());
+        assert_eq!(server.requests.lock().len(), 2);
+    }
+
+    #[test]
+    fn permanent_failure() {
+        let (r, server, metrics) = send(|s| value!{{
+            URL => s.url("/"),
+            BATCH_MAX_EVENTS => 1,
+            RETRY_DELAY => "1ms",
+        }}, &[(400, "bad event")], events(2));
+        r.unwrap();
+        assert_eq!(server.bodies(), vec!["{\"message\":\"0\"}\n", "{\"message\":\"1\"}\n"]);
+        assert_eq!(status_count(&metrics, 400), metric::Value::Counter(1.into()));
+        assert_eq!(status_count(&metrics, 200), metric::Value::Counter(1.into()));
+    }
+
+    #[test]
+    fn dead_letters() {
+        let dir = TempDir::new("http-output-dead-letters");
+        let (queue, writer) = dead_letter::test::open(&dir);
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let server = MockServer::start(&mut rt);
+        server.respond(400, "bad event");
+        let output = ProviderImpl.new(New {
+            config: value!{{
+                URL => server.url("/"),
+                BATCH_MAX_EVENTS => 1,
+            }}.into(),
+            common_config: CommonConfig {
+                id: Some("events".into()),
+                dead_letter_queue: Some(queue),
+            },
+            metrics: Default::default(),
+        }).unwrap();
+        rt.block_on(output.start()
+            .and_then(|started| stream::iter_ok(events(2)).forward(started.sink))
+            .map(|_| {}))
+            .unwrap();
+        drop(output);
+        let letters = dead_letter::test::read(&dir, writer);
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].component_id, "events");
+        assert_eq!(letters[0].event, events(1)[0]);
+    }
+
+    #[test]
+    fn connection_error() {
+        let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let metrics = Arc::new(Metrics::new());
+        let output = new(value!{{
+            URL => format!("http://{}/", addr),
+            RETRY_DELAY => "1ms",
+            MAX_RETRIES => 2,
+        }}, metrics.clone()).unwrap();
+        assert!(rt.block_on(output.start()
+            .and_then(|started| stream::iter_ok(events(1)).forward(started.sink)))
+            .is_err());
+        assert_eq!(metrics.get(&error_metric(NAME)).unwrap(), metric::Value::Counter(3.into()));
+    }
+
+    #[test]
+    fn invalid_config() {
+        let new = |config| new(config, Default::default());
+        assert!(new(value!{{}}).is_err());
+        assert!(new(value!{{ URL => "localhost" }}).is_err());
+        assert!(new(value!{{ URL => "https://localhost/" }}).is_err());
+        assert!(new(value!{{ URL => "http://localhost/", METHOD => "" }}).is_err());
+        assert!(new(value!{{ URL => "http://localhost/", BATCH_FORMAT => "xml" }}).is_err());
+        assert!(new(value!{{ URL => "http://localhost/", BATCH_MAX_EVENTS => 0 }}).is_err());
+        assert!(new(value!{{ URL => "http://localhost/", COMPRESSION => "zip" }}).is_err());
+        assert!(new(value!{{ URL => "http://localhost/", HEADERS => {"a b" => "c"} }}).is_err());
+        assert!(new(value!{{ URL => "http://localhost/", MAX_RETRIES => -1 }}).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use futures::future;
 use futures::prelude::*;
+use std::io::{self, Write};
 
 use super::*;
 use crate::component::{ComponentKind, Metadata, Provider as CProvider};
@@ -8,6 +9,14 @@ use crate::util::futures::*;
 
 pub const NAME: &'static str = "stdout";
 
+const TARGET: &'static str = "target";
+const TARGET_STDOUT: &'static str = "stdout";
+const TARGET_STDERR: &'static str = "stderr";
+const BUFFER_SIZE: &'static str = "buffer_size";
+
+const DEFAULT_CODEC: &'static str = "debug";
+const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
 pub fn provider() -> Box<Provider> {
     Box::new(ProviderImpl)
 }
@@ -24,17 +33,69 @@ impl CProvider for ProviderImpl {
 }
 
 impl Provider for ProviderImpl {
-    fn new(&self, _ctx: New) -> Result<Box<Output>> {
-        let encoder_factory = registry().encoder("debug").unwrap().new(Default::default())?;
+    fn new(&self, ctx: New) -> Result<Box<Output>> {
+        let mut config = ctx.config;
+
+        let encoder_factory = parse_codec(&mut config, DEFAULT_CODEC)?;
+
+        let target = if let Some(v) = config.get_opt(TARGET)? {
+            match v.as_str()? {
+                TARGET_STDOUT => Target::Stdout,
+                TARGET_STDERR => Target::Stderr,
+                _ => return Err(v.new_error(format!("`{}` must be one of [\"{}\", \"{}\"]",
+                    TARGET, TARGET_STDOUT, TARGET_STDERR))),
+            }
+        } else {
+            Target::Stdout
+        };
+
+        let buffer_size = if let Some(v) = config.get_opt(BUFFER_SIZE)? {
+            let r = *v.as_int()?;
+            if r <= 0 {
+                return Err(v.new_error(format!("`{}` must be positive", BUFFER_SIZE)));
+            }
+            r as usize
+        } else {
+            DEFAULT_BUFFER_SIZE
+        };
+
         Ok(Box::new(StdoutOutput {
-            config: Config { encoder_factory },
+            config: Config {
+                encoder_factory,
+                target,
+                buffer_size,
+            },
         }))
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Target {
+    Stdout,
+    Stderr,
+}
+
+impl Target {
+    fn write_all(self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            Target::Stdout => {
+                let out = io::stdout();
+                let mut out = out.lock();
+                out.write_all(buf)?;
+                out.flush()
+            }
+            Target::Stderr => io::stderr().lock().write_all(buf),
+        }
+    }
+}
+
 #[derive(Clone)]
 struct Config {
     encoder_factory: Arc<encoder::Factory>,
+    target: Target,
+
+    /// Size of the encoded events buffer after reaching which the buffer is written out.
+    buffer_size: usize,
 }
 
 #[derive(Clone)]
@@ -47,13 +108,61 @@ impl Output for StdoutOutput {
         Box::new(future::ok(Started {
             sink: Box::new(StdoutSink {
                 encoder: self.config.encoder_factory.new(),
+                line_oriented: self.config.encoder_factory.line_oriented(),
+                target: self.config.target,
+                buffer_size: self.config.buffer_size,
+                buf: Vec::new(),
+                flushed: false,
             }),
         }))
     }
 }
 
+/// Buffers the encoded events and writes them out when the buffer is full or when the sink is
+/// flushed, so a single write may carry many events.
 struct StdoutSink {
     encoder: Box<encoder::Encoder>,
+
+    /// Whether the last line of the encoded events needs to be terminated before writing.
+    line_oriented: bool,
+
+    target: Target,
+    buffer_size: usize,
+    buf: Vec<u8>,
+
+    /// Whether the encoder has been flushed into `buf` since the last event was encoded.
+    flushed: bool,
+}
+
+impl StdoutSink {
+    /// Flushes the encoder into the buffer unless it's been flushed already, so retrying the write
+    /// doesn't change the buffer.
+    fn flush_encoder(&mut self) -> Result<()> {
+        if !self.flushed {
+            self.encoder.flush(&mut self.buf)?;
+            if self.line_oriented && self.buf.last() != Some(&b'\n') {
+                self.buf.push(b'\n');
+            }
+            self.flushed = true;
+        }
+        Ok(())
+    }
+
+    fn poll_write(&mut self) -> Poll<(), Error> {
+        if self.buf.is_empty() {
+            return Ok(Async::Ready(()));
+        }
+        self.flush_encoder()?;
+
+        let target = self.target;
+        let buf = &self.buf;
+        match tokio_threadpool::blocking(move || target.write_all(buf)).unwrap() {
+            Async::Ready(r) => r.wrap_err_id(ErrorId::Io)?,
+            Async::NotReady => return Ok(Async::NotReady),
+        }
+        self.buf.clear();
+        Ok(Async::Ready(()))
+    }
 }
 
 impl Sink for StdoutSink {
@@ -61,17 +170,87 @@ impl Sink for StdoutSink {
     type SinkError = Error;
 
     fn start_send(&mut self, event: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
-        let mut buf = Vec::new();
-        self.encoder.encode(&event, &mut buf)?;
-        let s = String::from_utf8_lossy(&buf);
+        if self.buf.len() >= self.buffer_size {
+            if self.poll_write()?.is_not_ready() {
+                return Ok(AsyncSink::NotReady(event));
+            }
+        }
+        self.encoder.encode(&event, &mut self.buf)?;
+        self.flushed = false;
+        Ok(AsyncSink::Ready)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
+        self.poll_write()
+    }
+}
 
-        Ok(match tokio_threadpool::blocking(move || println!("{}", s)).unwrap() {
-            Async::Ready(()) => AsyncSink::Ready,
-            Async::NotReady => AsyncSink::NotReady(event),
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn new(config: Value) -> Result<Box<Output>> {
+        ProviderImpl.new(New {
+            config: config.into(),
+            common_config: Default::default(),
+            metrics: Default::default(),
         })
     }
 
-    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
-        Ok(Async::Ready(()))
+    fn sink(codec: &str) -> StdoutSink {
+        let mut config = value!{{ CODEC => codec }}.into();
+        let encoder_factory = parse_codec(&mut config, DEFAULT_CODEC).unwrap();
+        StdoutSink {
+            encoder: encoder_factory.new(),
+            line_oriented: encoder_factory.line_oriented(),
+            target: Target::Stdout,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            buf: Vec::new(),
+            flushed: false,
+        }
+    }
+
+    fn event(message: &str) -> Event {
+        let mut e = Event::new();
+        e.fields_mut().insert("message".into(), Value::String(message.into()));
+        e
+    }
+
+    #[test]
+    fn line_termination() {
+        let mut json = sink("json");
+        json.start_send(event("a")).unwrap();
+        json.start_send(event("b")).unwrap();
+        json.flush_encoder().unwrap();
+        json.flush_encoder().unwrap();
+        assert_eq!(json.buf, b"{\"message\":\"a\"}\n{\"message\":\"b\"}\n");
+        json.buf.clear();
+        json.start_send(event("c")).unwrap();
+        json.flush_encoder().unwrap();
+        assert_eq!(json.buf, b"{\"message\":\"c\"}\n");
+
+        let mut msgpack = sink("msgpack");
+        msgpack.start_send(event("a")).unwrap();
+        msgpack.flush_encoder().unwrap();
+        assert_eq!(msgpack.buf, b"\x81\xa7message\xa1a");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn config() {
+        assert!(new(value!{{}}).is_ok());
+        assert!(new(value!{{
+            CODEC => "json_lines",
+            TARGET => TARGET_STDERR,
+            BUFFER_SIZE => 1,
+        }}).is_ok());
+        assert!(new(value!{{ CODEC => {"type" => "template", "format" => "%{message}"} }}).is_ok());
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert!(new(value!{{ CODEC => "unknown" }}).is_err());
+        assert!(new(value!{{ CODEC => {"type" => "template"} }}).is_err());
+        assert!(new(value!{{ TARGET => "stdin" }}).is_err());
+        assert!(new(value!{{ BUFFER_SIZE => 0 }}).is_err());
+    }
+}
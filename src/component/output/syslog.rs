@@ -0,0 +1,575 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::future;
+use futures::prelude::*;
+use log::*;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::io;
+use std::path::PathBuf;
+use tokio::net::UnixDatagram;
+
+use super::*;
+use super::tcp::{self, Framing, TcpSink};
+use super::udp::{self, Chunking, UdpSink};
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::event::*;
+use crate::util::futures::*;
+use crate::util::sys::hostname;
+use crate::util::template::{self, Template};
+
+pub const NAME: &'static str = "syslog";
+
+const TRANSPORT: &'static str = "transport";
+const TRANSPORT_UDP: &'static str = "udp";
+const TRANSPORT_TCP: &'static str = "tcp";
+const TRANSPORT_UNIX: &'static str = "unix";
+const ADDRESS: &'static str = "address";
+const FRAMING: &'static str = "framing";
+const FRAMING_OCTET_COUNTING: &'static str = "octet_counting";
+const FRAMING_NEWLINE: &'static str = "newline";
+const FORMAT: &'static str = "format";
+const FORMAT_RFC5424: &'static str = "rfc5424";
+const FORMAT_RFC3164: &'static str = "rfc3164";
+const FACILITY: &'static str = "facility";
+const FACILITY_FIELD: &'static str = "facility_field";
+const SEVERITY: &'static str = "severity";
+const SEVERITY_FIELD: &'static str = "severity_field";
+const APP_NAME: &'static str = "app_name";
+const HOSTNAME: &'static str = "hostname";
+const PROC_ID: &'static str = "proc_id";
+const MSG_ID: &'static str = "msg_id";
+const MESSAGE: &'static str = "message";
+const TIMESTAMP_FIELD: &'static str = "timestamp_field";
+
+const DEFAULT_UNIX_ADDRESS: &'static str = "/dev/log";
+const DEFAULT_FACILITY: u8 = 1;
+const DEFAULT_SEVERITY: u8 = 6;
+const DEFAULT_APP_NAME: &'static str = "xerocole";
+const DEFAULT_MESSAGE: &'static str = "%{message}";
+const DEFAULT_TIMESTAMP_FIELD: &'static str = "timestamp";
+
+const FACILITIES: &[&str] = &["kern", "user", "mail", "daemon", "auth", "syslog", "lpr", "news",
+    "uucp", "cron", "authpriv", "ftp", "ntp", "security", "console", "solaris-cron",
+    "local0", "local1", "local2", "local3", "local4", "local5", "local6", "local7"];
+
+/// Severity names along with the common log level names that map to them.
+const SEVERITIES: &[&[&str]] = &[
+    &["emerg", "emergency", "panic"],
+    &["alert"],
+    &["crit", "critical", "fatal"],
+    &["err", "error"],
+    &["warning", "warn"],
+    &["notice"],
+    &["info", "informational"],
+    &["debug", "trace"],
+];
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::Output,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Box<Output>> {
+        let config = ctx.config;
+        let id = ctx.common_config.id.unwrap_or_else(|| NAME.into());
+
+        let encoder_factory: Arc<encoder::Factory> =
+            Arc::new(FactoryImpl(Arc::new(Formatter::parse(&config)?)));
+
+        let transport = if let Some(v) = config.get_opt(TRANSPORT)? {
+            v.as_str()?
+        } else {
+            TRANSPORT_UDP
+        };
+        let transport = match transport {
+            TRANSPORT_UDP => Transport::Udp(Arc::new(udp::Config::parse(&config, id,
+                encoder_factory, Chunking::None)?)),
+            TRANSPORT_TCP => {
+                let framing = if let Some(v) = config.get_opt(FRAMING)? {
+                    match v.as_str()? {
+                        FRAMING_OCTET_COUNTING => Framing::OctetCounting,
+                        FRAMING_NEWLINE => Framing::Newline,
+                        _ => return Err(v.new_error(format!(
+                            "`{}` must be one of [\"{}\", \"{}\"]",
+                            FRAMING, FRAMING_OCTET_COUNTING, FRAMING_NEWLINE))),
+                    }
+                } else {
+                    Framing::OctetCounting
+                };
+                Transport::Tcp(Arc::new(tcp::Config::parse(&config, id, encoder_factory,
+                    framing)?))
+            }
+            TRANSPORT_UNIX => {
+                let path = config.get_opt_str(ADDRESS)?.unwrap_or(DEFAULT_UNIX_ADDRESS).into();
+                Transport::Unix(Arc::new(UnixConfig {
+                    id,
+                    path,
+                    encoder_factory,
+                }))
+            }
+            _ => return Err(config.get(TRANSPORT)?.new_error(format!(
+                "`{}` must be one of [\"{}\", \"{}\", \"{}\"]",
+                TRANSPORT, TRANSPORT_UDP, TRANSPORT_TCP, TRANSPORT_UNIX))),
+        };
+
+        Ok(Box::new(SyslogOutput {
+            transport,
+        }))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Format {
+    Rfc5424,
+    Rfc3164,
+}
+
+/// Parses facility name or number.
+fn facility(v: &Value) -> Option<u8> {
+    match v {
+        Value::Int(v) if *v >= 0 && (*v as usize) < FACILITIES.len() => Some(*v as u8),
+        Value::String(v) => FACILITIES.iter()
+            .position(|f| f.eq_ignore_ascii_case(v))
+            .map(|i| i as u8)
+            .or_else(|| facility(&Value::Int(v.parse().ok()?))),
+        _ => None,
+    }
+}
+
+/// Parses severity name, log level name or severity number.
+fn severity(v: &Value) -> Option<u8> {
+    match v {
+        Value::Int(v) if *v >= 0 && (*v as usize) < SEVERITIES.len() => Some(*v as u8),
+        Value::String(v) => SEVERITIES.iter()
+            .position(|names| names.iter().any(|s| s.eq_ignore_ascii_case(v)))
+            .map(|i| i as u8)
+            .or_else(|| severity(&Value::Int(v.parse().ok()?))),
+        _ => None,
+    }
+}
+
+/// Formats events as syslog messages.
+struct Formatter {
+    format: Format,
+    facility: u8,
+    facility_field: Option<String>,
+    severity: u8,
+    severity_field: Option<String>,
+    app_name: Template,
+    hostname: Template,
+    proc_id: Option<Template>,
+    msg_id: Option<Template>,
+    message: Template,
+    timestamp_field: String,
+}
+
+impl Formatter {
+    fn parse(config: &Spanned<Value>) -> Result<Self> {
+        let format = if let Some(v) = config.get_opt(FORMAT)? {
+            match v.as_str()? {
+                FORMAT_RFC5424 => Format::Rfc5424,
+                FORMAT_RFC3164 => Format::Rfc3164,
+                _ => return Err(v.new_error(format!("`{}` must be one of [\"{}\", \"{}\"]",
+                    FORMAT, FORMAT_RFC5424, FORMAT_RFC3164))),
+            }
+        } else {
+            Format::Rfc5424
+        };
+
+        let facility = if let Some(v) = config.get_opt(FACILITY)? {
+            self::facility(v).ok_or_else(|| v.new_error(format!("invalid `{}`", FACILITY)))?
+        } else {
+            DEFAULT_FACILITY
+        };
+        let severity = if let Some(v) = config.get_opt(SEVERITY)? {
+            self::severity(v).ok_or_else(|| v.new_error(format!("invalid `{}`", SEVERITY)))?
+        } else {
+            DEFAULT_SEVERITY
+        };
+
+        let template = |key: &str| -> Result<Option<Template>> {
+            config.get_opt(key)?.map(Template::parse).transpose()
+        };
+        let hostname = if let Some(v) = template(HOSTNAME)? {
+            v
+        } else {
+            let host = hostname().unwrap_or_else(|| "localhost".into()).replace('%', "%%");
+            Template::parse(&host.as_str().into())?
+        };
+
+        Ok(Self {
+            format,
+            facility,
+            facility_field: config.get_opt_str(FACILITY_FIELD)?.map(|s| s.to_owned()),
+            severity,
+            severity_field: config.get_opt_str(SEVERITY_FIELD)?.map(|s| s.to_owned()),
+            app_name: template(APP_NAME)?
+                .map_or_else(|| Template::parse(&DEFAULT_APP_NAME.into()), Ok)?,
+            hostname,
+            proc_id: template(PROC_ID)?,
+            msg_id: template(MSG_ID)?,
+            message: template(MESSAGE)?
+                .map_or_else(|| Template::parse(&DEFAULT_MESSAGE.into()), Ok)?,
+            timestamp_field: config.get_opt_str(TIMESTAMP_FIELD)?
+                .unwrap_or(DEFAULT_TIMESTAMP_FIELD)
+                .to_owned(),
+        })
+    }
+
+    fn priority(&self, event: &Event) -> u8 {
+        let field = |name: &Option<String>, parse: fn(&Value) -> Option<u8>| name.as_ref()
+            .and_then(|name| event.fields().get(name))
+            .and_then(parse);
+        let facility = field(&self.facility_field, facility).unwrap_or(self.facility);
+        let severity = field(&self.severity_field, severity).unwrap_or(self.severity);
+        facility * 8 + severity
+    }
+
+    fn format(&self, event: &Event, time: &DateTime<Utc>, out: &mut String) {
+        write!(out, "<{}>", self.priority(event)).unwrap();
+        match self.format {
+            Format::Rfc5424 => {
+                write!(out, "1 {} ", time.to_rfc3339_opts(SecondsFormat::Micros, true)).unwrap();
+                header_field(out, &self.hostname, event, 255);
+                out.push(' ');
+                header_field(out, &self.app_name, event, 48);
+                out.push(' ');
+                match &self.proc_id {
+                    Some(v) => header_field(out, v, event, 128),
+                    None => out.push('-'),
+                }
+                out.push(' ');
+                match &self.msg_id {
+                    Some(v) => header_field(out, v, event, 32),
+                    None => out.push('-'),
+                }
+                // No structured data.
+                out.push_str(" - ");
+            }
+            Format::Rfc3164 => {
+                write!(out, "{} ", time.format("%b %e %H:%M:%S")).unwrap();
+                header_field(out, &self.hostname, event, 255);
+                out.push(' ');
+                header_field(out, &self.app_name, event, 32);
+                if let Some(v) = &self.proc_id {
+                    out.push('[');
+                    header_field(out, v, event, 128);
+                    out.push(']');
+                }
+                out.push_str(": ");
+            }
+        }
+        self.message.render(event, out);
+    }
+}
+
+/// Renders the header field `template` replacing the characters that aren't allowed in the
+/// header fields with `_` and truncating the result to `max_len`. Empty value is written as `-`.
+fn header_field(out: &mut String, template: &Template, event: &Event, max_len: usize) {
+    let mut s = String::new();
+    template.render(event, &mut s);
+    if s.is_empty() {
+        out.push('-');
+    } else {
+        out.extend(s.chars()
+            .map(|c| if c >= '!' && c <= '~' { c } else { '_' })
+            .take(max_len));
+    }
+}
+
+/// Encoder for the underlying transport that writes each event as syslog message.
+struct FactoryImpl(Arc<Formatter>);
+
+impl encoder::Factory for FactoryImpl {
+    fn new(&self) -> Box<encoder::Encoder> {
+        Box::new(EncoderImpl(self.0.clone()))
+    }
+}
+
+struct EncoderImpl(Arc<Formatter>);
+
+impl encoder::Encoder for EncoderImpl {
+    fn encode(&mut self, event: &Event, out: &mut Vec<u8>) -> Result<()> {
+        let time = event.fields().get(&self.0.timestamp_field)
+            .and_then(template::timestamp)
+            .unwrap_or_else(Utc::now);
+        let mut s = String::new();
+        self.0.format(event, &time, &mut s);
+        out.extend_from_slice(s.as_bytes());
+        Ok(())
+    }
+
+    fn flush(&mut self, _out: &mut Vec<u8>) -> Result<()> {
+        Ok(())
+    }
+}
+
+struct SyslogOutput {
+    transport: Transport,
+}
+
+enum Transport {
+    Udp(Arc<udp::Config>),
+    Tcp(Arc<tcp::Config>),
+    Unix(Arc<UnixConfig>),
+}
+
+impl Output for SyslogOutput {
+    fn start(&self) -> BoxFuture<Started, Error> {
+        let sink: io::Result<BoxSink<Event, Error>> = match &self.transport {
+            Transport::Udp(config) => Ok(Box::new(UdpSink::new(config.clone()))),
+            Transport::Tcp(config) => Ok(Box::new(TcpSink::new(config.clone()))),
+            Transport::Unix(config) => UnixSink::new(config.clone())
+                .map(|s| Box::new(s) as BoxSink<_, _>),
+        };
+        Box::new(future::result(sink.wrap_err_id(ErrorId::Io))
+            .map(|sink| Started {
+                sink,
+            }))
+    }
+}
+
+struct UnixConfig {
+    /// Used as the log context.
+    id: String,
+
+    /// Path of the datagram socket.
+    path: PathBuf,
+
+    encoder_factory: Arc<encoder::Factory>,
+}
+
+/// Sends each message in a separate datagram to the local syslog daemon socket. Messages that
+/// can't be sent are logged and dropped.
+struct UnixSink {
+    config: Arc<UnixConfig>,
+    encoder: Box<encoder::Encoder>,
+    socket: UnixDatagram,
+    datagrams: VecDeque<Vec<u8>>,
+}
+
+impl UnixSink {
+    fn new(config: Arc<UnixConfig>) -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(&config.path)?;
+        Ok(Self {
+            encoder: config.encoder_factory.new(),
+            config,
+            socket,
+            datagrams: VecDeque::new(),
+        })
+    }
+
+    fn poll_send(&mut self) -> Poll<(), Error> {
+        while let Some(datagram) = self.datagrams.front() {
+            match self.socket.poll_send(datagram) {
+                Ok(Async::Ready(_)) => {}
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(e) => warn!("[{}] error sending datagram to {}: {}", self.config.id,
+                    self.config.path.display(), e),
+            }
+            self.datagrams.pop_front();
+        }
+        Ok(Async::Ready(()))
+    }
+}
+
+impl Sink for UnixSink {
+    type SinkItem = Event;
+    type SinkError = Error;
+
+    fn start_send(&mut self, event: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
+        if self.poll_send()?.is_not_ready() {
+            return Ok(AsyncSink::NotReady(event));
+        }
+        let mut buf = Vec::new();
+        encode_event(&mut *self.encoder, &event, &mut buf)?;
+        self.datagrams.push_back(buf);
+        Ok(AsyncSink::Ready)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
+        self.poll_send()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+    use futures::stream;
+    use std::io::Read;
+    use std::net;
+    use std::os::unix::net as unix;
+    use std::thread;
+
+    use super::*;
+
+    fn new(config: Value) -> Result<Box<Output>> {
+        ProviderImpl.new(New {
+            config: config.into(),
+            common_config: Default::default(),
+            metrics: Default::default(),
+        })
+    }
+
+    fn format(config: Value, event: &Event) -> String {
+        let time = Utc.with_ymd_and_hms(2019, 1, 2, 3, 4, 5).unwrap();
+        let mut r = String::new();
+        Formatter::parse(&config.into()).unwrap().format(event, &time, &mut r);
+        r
+    }
+
+    fn event() -> Event {
+        let mut e = Event::new();
+        let f = e.fields_mut();
+        f.insert("message".into(), Value::String("hello world".into()));
+        f.insert("level".into(), Value::String("WARN".into()));
+        f.insert("fac".into(), Value::String("local0".into()));
+        f.insert("service".into(), Value::String("web".into()));
+        f.insert("pid".into(), Value::Int(42));
+        f.insert("timestamp".into(), Value::String("2019-01-02T03:04:05Z".into()));
+        e
+    }
+
+    fn mapped() -> Value {
+        value!{{
+            HOSTNAME => "h",
+            APP_NAME => "%{service}",
+            PROC_ID => "%{pid}",
+            MSG_ID => "ID 1",
+            FACILITY_FIELD => "fac",
+            SEVERITY_FIELD => "level",
+        }}
+    }
+
+    #[test]
+    fn rfc5424() {
+        assert_eq!(format(mapped(), &event()),
+            "<132>1 2019-01-02T03:04:05.000000Z h web 42 ID_1 - hello world");
+        assert_eq!(format(value!{{ HOSTNAME => "h" }}, &Event::new()),
+            "<14>1 2019-01-02T03:04:05.000000Z h xerocole - - - ");
+    }
+
+    #[test]
+    fn rfc3164() {
+        let mut config = mapped();
+        config.as_map_mut().unwrap().insert(FORMAT.into(), Value::from(FORMAT_RFC3164).into());
+        assert_eq!(format(config, &event()), "<132>Jan  2 03:04:05 h web[42]: hello world");
+        assert_eq!(format(value!{{ FORMAT => FORMAT_RFC3164, HOSTNAME => "h" }}, &Event::new()),
+            "<14>Jan  2 03:04:05 h xerocole: ");
+    }
+
+    #[test]
+    fn priority() {
+        let cases = &[
+            (Value::Int(3), Value::Int(7), 31),
+            (Value::String("4".into()), Value::String("0".into()), 32),
+            (Value::String("AUTH".into()), Value::String("crit".into()), 34),
+            (Value::String("bad".into()), Value::Int(8), 14),
+        ];
+        for (facility, severity, exp) in cases {
+            let mut e = Event::new();
+            e.fields_mut().insert("f".into(), facility.clone());
+            e.fields_mut().insert("s".into(), severity.clone());
+            let config = value!{{ FACILITY_FIELD => "f", SEVERITY_FIELD => "s" }};
+            assert_eq!(Formatter::parse(&config.into()).unwrap().priority(&e), *exp);
+        }
+    }
+
+    fn send(output: Box<Output>) {
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(output.start()
+            .and_then(|started| stream::iter_ok(vec![event(), event()]).forward(started.sink))
+            .map(|_| {}))
+            .unwrap();
+    }
+
+    const MESSAGE: &str = "<14>1 2019-01-02T03:04:05.000000Z h xerocole - - - hello world";
+
+    #[test]
+    fn udp() {
+        let socket = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(std::time::Duration::from_millis(200))).unwrap();
+        send(new(value!{{
+            ADDRESS => socket.local_addr().unwrap().to_string(),
+            HOSTNAME => "h",
+        }}).unwrap());
+        let mut buf = [0; 1024];
+        for _ in 0..2 {
+            let len = socket.recv(&mut buf).unwrap();
+            assert_eq!(&buf[..len], MESSAGE.as_bytes());
+        }
+    }
+
+    #[test]
+    fn tcp() {
+        for &(framing, sep) in &[(FRAMING_OCTET_COUNTING, ""), (FRAMING_NEWLINE, "\n")] {
+            let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let address = listener.local_addr().unwrap().to_string();
+            let server = thread::spawn(move || {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut r = String::new();
+                stream.read_to_string(&mut r).unwrap();
+                r
+            });
+            send(new(value!{{
+                TRANSPORT => TRANSPORT_TCP,
+                ADDRESS => address,
+                FRAMING => framing,
+                HOSTNAME => "h",
+            }}).unwrap());
+            let frame = if sep.is_empty() {
+                format!("{} {}", MESSAGE.len(), MESSAGE)
+            } else {
+                format!("{}{}", MESSAGE, sep)
+            };
+            assert_eq!(server.join().unwrap(), frame.repeat(2));
+        }
+    }
+
+    #[test]
+    fn unix() {
+        let path = std::env::temp_dir().join(format!("xerocole-test-{}-syslog.sock",
+            std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let socket = unix::UnixDatagram::bind(&path).unwrap();
+        socket.set_read_timeout(Some(std::time::Duration::from_millis(200))).unwrap();
+        send(new(value!{{
+            TRANSPORT => TRANSPORT_UNIX,
+            ADDRESS => path.to_str().unwrap(),
+            HOSTNAME => "h",
+        }}).unwrap());
+        let mut buf = [0; 1024];
+        for _ in 0..2 {
+            let len = socket.recv(&mut buf).unwrap();
+            assert_eq!(&buf[..len], MESSAGE.as_bytes());
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert!(new(value!{{}}).is_err());
+        assert!(new(value!{{ ADDRESS => "localhost:514", TRANSPORT => "sctp" }}).is_err());
+        assert!(new(value!{{ ADDRESS => "localhost:514", FORMAT => "rfc1" }}).is_err());
+        assert!(new(value!{{ ADDRESS => "localhost:514", FACILITY => 24 }}).is_err());
+        assert!(new(value!{{ ADDRESS => "localhost:514", SEVERITY => "verbose" }}).is_err());
+        assert!(new(value!{{
+            ADDRESS => "localhost:514",
+            TRANSPORT => TRANSPORT_TCP,
+            FRAMING => "length_prefixed",
+        }}).is_err());
+        assert!(new(value!{{ ADDRESS => "localhost:514", APP_NAME => "%{a" }}).is_err());
+    }
+}
@@ -0,0 +1,458 @@
+use futures::future;
+use futures::prelude::*;
+use futures::try_ready;
+use futures_retry::{ErrorHandler, RetryPolicy};
+use log::*;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWrite;
+use tokio::net::TcpStream;
+use tokio::net::tcp::ConnectFuture;
+use tokio::timer::Delay;
+
+use super::*;
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::event::*;
+use crate::retry::RetryErrorHandler;
+use crate::util::futures::*;
+use crate::util::futures::future::blocking;
+
+pub const NAME: &'static str = "tcp";
+
+const ADDRESS: &'static str = "address";
+const FRAMING: &'static str = "framing";
+const FRAMING_NONE: &'static str = "none";
+const FRAMING_NEWLINE: &'static str = "newline";
+const FRAMING_LENGTH_PREFIXED: &'static str = "length_prefixed";
+const FRAMING_OCTET_COUNTING: &'static str = "octet_counting";
+const BUFFER_SIZE: &'static str = "buffer_size";
+const RECONNECT_DELAY: &'static str = "reconnect_delay";
+const MAX_RECONNECT_DELAY: &'static str = "max_reconnect_delay";
+
+const DEFAULT_CODEC: &'static str = "json_lines";
+const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+const DEFAULT_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::Output,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Box<Output>> {
+        let mut config = ctx.config;
+
+        let encoder_factory = parse_codec(&mut config, DEFAULT_CODEC)?;
+
+        let framing = if let Some(v) = config.get_opt(FRAMING)? {
+            match v.as_str()? {
+                FRAMING_NONE => Framing::None,
+                FRAMING_NEWLINE => Framing::Newline,
+                FRAMING_LENGTH_PREFIXED => Framing::LengthPrefixed,
+                FRAMING_OCTET_COUNTING => Framing::OctetCounting,
+                _ => return Err(v.new_error(format!(
+                    "`{}` must be one of [\"{}\", \"{}\", \"{}\", \"{}\"]",
+                    FRAMING, FRAMING_NONE, FRAMING_NEWLINE, FRAMING_LENGTH_PREFIXED,
+                    FRAMING_OCTET_COUNTING))),
+            }
+        } else {
+            Framing::None
+        };
+
+        Ok(Box::new(TcpOutput {
+            config: Arc::new(Config::parse(&config,
+                ctx.common_config.id.unwrap_or_else(|| NAME.into()),
+                encoder_factory, framing)?),
+        }))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(super) enum Framing {
+    /// The encoded events are written as is.
+    None,
+
+    /// Each encoded event is followed by newline unless the encoder produces lines of text and
+    /// has already terminated it.
+    Newline,
+
+    /// Each encoded event is preceded by its length as 4-byte big-endian integer.
+    LengthPrefixed,
+
+    /// Each encoded event is preceded by its length as decimal number and space as in RFC 6587.
+    OctetCounting,
+}
+
+pub(super) struct Config {
+    /// Used as the log context.
+    id: String,
+
+    /// Address in `host:port` form. The host is resolved on each connection attempt.
+    address: String,
+
+    encoder_factory: Arc<encoder::Factory>,
+    framing: Framing,
+
+    /// Size of the buffer of the encoded events after reaching which the sink stops accepting
+    /// events until the buffer is written out.
+    buffer_size: usize,
+
+    reconnect_delay: Duration,
+    max_reconnect_delay: Duration,
+}
+
+impl Config {
+    /// Parses the connection options.
+    pub fn parse(config: &Spanned<Value>, id: String, encoder_factory: Arc<encoder::Factory>,
+        framing: Framing) -> Result<Self>
+    {
+        let address = config.get(ADDRESS)?.as_str()?.to_owned();
+
+        let buffer_size = if let Some(v) = config.get_opt(BUFFER_SIZE)? {
+            let r = *v.as_int()?;
+            if r <= 0 {
+                return Err(v.new_error(format!("`{}` must be positive", BUFFER_SIZE)));
+            }
+            r as usize
+        } else {
+            DEFAULT_BUFFER_SIZE
+        };
+
+        let reconnect_delay = config.get_opt_duration(RECONNECT_DELAY)?
+            .unwrap_or(DEFAULT_RECONNECT_DELAY);
+        let max_reconnect_delay = config.get_opt_duration(MAX_RECONNECT_DELAY)?
+            .unwrap_or(DEFAULT_MAX_RECONNECT_DELAY);
+
+        Ok(Self {
+            id,
+            address,
+            encoder_factory,
+            framing,
+            buffer_size,
+            reconnect_delay,
+            max_reconnect_delay,
+        })
+    }
+
+    fn retry_handler(&self) -> RetryErrorHandler {
+        RetryErrorHandler::new(None, self.reconnect_delay, self.max_reconnect_delay,
+            &self.id, format_args!("connecting to {}", self.address))
+    }
+}
+
+struct TcpOutput {
+    config: Arc<Config>,
+}
+
+impl Output for TcpOutput {
+    fn start(&self) -> BoxFuture<Started, Error> {
+        Box::new(future::ok(Started {
+            sink: Box::new(TcpSink::new(self.config.clone())),
+        }))
+    }
+}
+
+enum State {
+    Disconnected,
+    Waiting(Delay),
+    Resolving(BoxFuture<io::Result<SocketAddr>, Error>),
+    Connecting(ConnectFuture),
+    Connected(TcpStream),
+}
+
+/// Writes the encoded events to TCP connection reconnecting with exponential backoff when
+/// the connection can't be established or breaks.
+pub(super) struct TcpSink {
+    config: Arc<Config>,
+    encoder: Box<encoder::Encoder>,
+    state: State,
+    retry: RetryErrorHandler,
+
+    buf: Vec<u8>,
+
+    /// Number of bytes of `buf` written to the current connection.
+    written: usize,
+
+    /// End offsets of the frames in `buf`.
+    frame_ends: Vec<usize>,
+}
+
+impl TcpSink {
+    pub fn new(config: Arc<Config>) -> Self {
+        Self {
+            encoder: config.encoder_factory.new(),
+            retry: config.retry_handler(),
+            config,
+            state: State::Disconnected,
+            buf: Vec::new(),
+            written: 0,
+            frame_ends: Vec::new(),
+        }
+    }
+
+    fn encode(&mut self, event: &Event) -> Result<()> {
+        let start = self.buf.len();
+        if self.config.framing == Framing::LengthPrefixed {
+            self.buf.extend_from_slice(&[0; 4]);
+        }
+        if let Err(e) = encode_event(&mut *self.encoder, event, &mut self.buf) {
+            self.buf.truncate(start);
+            return Err(e);
+        }
+        match self.config.framing {
+            Framing::None => {}
+            Framing::Newline => {
+                let terminated = self.config.encoder_factory.line_oriented()
+                    && self.buf.len() > start && self.buf.last() == Some(&b'\n');
+                if !terminated {
+                    self.buf.push(b'\n');
+                }
+            }
+            Framing::LengthPrefixed => {
+                let len = (self.buf.len() - start - 4) as u32;
+                for (i, b) in self.buf[start..start + 4].iter_mut().enumerate() {
+                    *b = (len >> (24 - i * 8)) as u8;
+                }
+            }
+            Framing::OctetCounting => {
+                let frame = self.buf.split_off(start);
+                self.buf.extend_from_slice(frame.len().to_string().as_bytes());
+                self.buf.push(b' ');
+                self.buf.extend_from_slice(&frame);
+            }
+        }
+        self.frame_ends.push(self.buf.len());
+        Ok(())
+    }
+
+    /// Drops the connection and schedules reconnection.
+    fn reconnect(&mut self, e: io::Error) -> Result<()> {
+        // Resend the partially written frame in whole.
+        self.written = self.frame_ends.iter().cloned()
+            .take_while(|&end| end <= self.written)
+            .last()
+            .unwrap_or(0);
+        self.state = match self.retry.handle(e.wrap_id(ErrorId::Io)) {
+            RetryPolicy::Repeat => State::Disconnected,
+            RetryPolicy::WaitRetry(delay) => State::Waiting(Delay::new(Instant::now() + delay)),
+            RetryPolicy::ForwardError(e) => return Err(e),
+        };
+        Ok(())
+    }
+
+    /// Connects if needed and writes the whole buffer out.
+    fn poll_write(&mut self) -> Poll<(), Error> {
+        loop {
+            match &mut self.state {
+                State::Disconnected => {
+                    let address = self.config.address.clone();
+                    self.state = State::Resolving(Box::new(blocking(move || resolve(&address))
+                        .infallible()));
+                }
+                State::Resolving(f) => match try_ready!(f.poll()) {
+                    Ok(addr) => {
+                        debug!("[{}] connecting to {} ({})", self.config.id,
+                            self.config.address, addr);
+                        self.state = State::Connecting(TcpStream::connect(&addr));
+                    }
+                    Err(e) => self.reconnect(e)?,
+                }
+                State::Waiting(delay) => {
+                    try_ready!(delay.poll().map_err(|e| Error::new(ErrorId::Unknown,
+                        e.to_string())));
+                    self.state = State::Disconnected;
+                }
+                State::Connecting(f) => match f.poll() {
+                    Ok(Async::Ready(stream)) => {
+                        info!("[{}] connected to {}", self.config.id, self.config.address);
+                        self.retry = self.config.retry_handler();
+                        self.state = State::Connected(stream);
+                    }
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Err(e) => self.reconnect(e)?,
+                }
+                State::Connected(stream) => {
+                    let r = loop {
+                        if self.written == self.buf.len() {
+                            break stream.poll_flush();
+                        }
+                        match stream.poll_write(&self.buf[self.written..]) {
+                            Ok(Async::Ready(0)) => break Err(io::ErrorKind::WriteZero.into()),
+                            Ok(Async::Ready(n)) => self.written += n,
+                            r => break r.map(|_| Async::Ready(())),
+                        }
+                    };
+                    match r {
+                        Ok(Async::Ready(())) => {
+                            self.buf.clear();
+                            self.frame_ends.clear();
+                            self.written = 0;
+                            return Ok(Async::Ready(()));
+                        }
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        Err(e) => {
+                            warn!("[{}] error writing to {}: {}", self.config.id,
+                                self.config.address, e);
+                            self.reconnect(e)?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Resolves the `address` in `host:port` form. Blocks on DNS lookup.
+pub(super) fn resolve(address: &str) -> io::Result<SocketAddr> {
+    address.to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Other,
+            format!("couldn't resolve {}", address)))
+}
+
+impl Sink for TcpSink {
+    type SinkItem = Event;
+    type SinkError = Error;
+
+    fn start_send(&mut self, event: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
+        if self.buf.len() >= self.config.buffer_size {
+            if self.poll_write()?.is_not_ready() {
+                return Ok(AsyncSink::NotReady(event));
+            }
+        }
+        self.encode(&event)?;
+        Ok(AsyncSink::Ready)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
+        if self.buf.is_empty() {
+            return Ok(Async::Ready(()));
+        }
+        self.poll_write()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::stream;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+
+    fn new(config: Value) -> Result<Box<Output>> {
+        ProviderImpl.new(New {
+            config: config.into(),
+            common_config: Default::default(),
+            metrics: Default::default(),
+        })
+    }
+
+    fn events(n: usize) -> Vec<Event> {
+        (0..n)
+            .map(|i| {
+                let mut e = Event::new();
+                e.fields_mut().insert("message".into(), Value::String(i.to_string()));
+                e
+            })
+            .collect()
+    }
+
+    /// Sends the `events` to the output and returns the bytes received by the server.
+    fn send(listener: TcpListener, config: Value, events: Vec<Event>) -> Vec<u8> {
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut r = Vec::new();
+            stream.read_to_end(&mut r).unwrap();
+            r
+        });
+        let output = new(config).unwrap();
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(output.start()
+            .and_then(|started| stream::iter_ok(events).forward(started.sink))
+            .map(|_| {}))
+            .unwrap();
+        drop(rt);
+        server.join().unwrap()
+    }
+
+    #[test]
+    fn framing() {
+        let cases = &[
+            (FRAMING_NONE, &b"{\"message\":\"0\"}\n{\"message\":\"1\"}\n"[..]),
+            (FRAMING_NEWLINE, b"{\"message\":\"0\"}\n{\"message\":\"1\"}\n"),
+            (FRAMING_LENGTH_PREFIXED,
+                b"\x00\x00\x00\x10{\"message\":\"0\"}\n\x00\x00\x00\x10{\"message\":\"1\"}\n"),
+            (FRAMING_OCTET_COUNTING, b"16 {\"message\":\"0\"}\n16 {\"message\":\"1\"}\n"),
+        ];
+        for &(framing, exp) in cases {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let address = listener.local_addr().unwrap().to_string();
+            assert_eq!(&send(listener, value!{{
+                ADDRESS => address,
+                FRAMING => framing,
+            }}, events(2))[..], exp, "{}", framing);
+        }
+
+        // Binary encoders are always terminated.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        assert_eq!(&send(listener, value!{{
+            ADDRESS => address,
+            CODEC => "msgpack",
+            FRAMING => FRAMING_NEWLINE,
+        }}, events(1))[..], b"\x81\xa7message\xa10\n");
+    }
+
+    #[test]
+    fn reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+        let server = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            TcpListener::bind(address).unwrap()
+        });
+        let output_events = events(1000);
+        let config = value!{{
+            ADDRESS => address.to_string(),
+            CODEC => {"type" => "template", "format" => "%{message}"},
+            FRAMING => FRAMING_NEWLINE,
+            BUFFER_SIZE => 100,
+            RECONNECT_DELAY => "10ms",
+        }};
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let output = new(config).unwrap();
+        let listener = rt.block_on(output.start()
+            .and_then(|started| stream::iter_ok(output_events).forward(started.sink))
+            .map(|_| server.join().unwrap()))
+            .unwrap();
+        drop(rt);
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut r = String::new();
+        stream.read_to_string(&mut r).unwrap();
+        let exp: Vec<_> = (0..1000).map(|i| i.to_string()).collect();
+        assert_eq!(r.lines().collect::<Vec<_>>(), exp);
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert!(new(value!{{}}).is_err());
+        assert!(new(value!{{ ADDRESS => "localhost:1", FRAMING => "bad" }}).is_err());
+        assert!(new(value!{{ ADDRESS => "localhost:1", BUFFER_SIZE => 0 }}).is_err());
+        assert!(new(value!{{ ADDRESS => "localhost:1", CODEC => "bad" }}).is_err());
+    }
+}
@@ -0,0 +1,371 @@
+use futures::future;
+use futures::prelude::*;
+use futures::try_ready;
+use log::*;
+use std::collections::VecDeque;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+
+use super::*;
+use super::tcp::resolve;
+use crate::component::{ComponentKind, Metadata, Provider as CProvider};
+use crate::event::*;
+use crate::util::futures::*;
+use crate::util::futures::future::blocking;
+
+pub const NAME: &'static str = "udp";
+
+const ADDRESS: &'static str = "address";
+const MAX_DATAGRAM_SIZE: &'static str = "max_datagram_size";
+const CHUNKING: &'static str = "chunking";
+const CHUNKING_NONE: &'static str = "none";
+const CHUNKING_GELF: &'static str = "gelf";
+
+const DEFAULT_CODEC: &'static str = "json";
+
+/// Max payload of IPv4 UDP datagram.
+const DEFAULT_MAX_DATAGRAM_SIZE: usize = 65507;
+
+/// Chunk size recommended by the GELF spec.
+const DEFAULT_GELF_CHUNK_SIZE: usize = 8192;
+
+const GELF_CHUNK_MAGIC: &[u8] = b"\x1e\x0f";
+const GELF_CHUNK_HEADER_LEN: usize = 12;
+const GELF_MAX_CHUNKS: usize = 128;
+
+pub fn provider() -> Box<Provider> {
+    Box::new(ProviderImpl)
+}
+
+struct ProviderImpl;
+
+impl CProvider for ProviderImpl {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: ComponentKind::Output,
+            name: NAME,
+        }
+    }
+}
+
+impl Provider for ProviderImpl {
+    fn new(&self, ctx: New) -> Result<Box<Output>> {
+        let mut config = ctx.config;
+
+        let encoder_factory = parse_codec(&mut config, DEFAULT_CODEC)?;
+
+        let chunking = if let Some(v) = config.get_opt(CHUNKING)? {
+            match v.as_str()? {
+                CHUNKING_NONE => Chunking::None,
+                CHUNKING_GELF => Chunking::Gelf,
+                _ => return Err(v.new_error(format!("`{}` must be one of [\"{}\", \"{}\"]",
+                    CHUNKING, CHUNKING_NONE, CHUNKING_GELF))),
+            }
+        } else {
+            Chunking::None
+        };
+
+        Ok(Box::new(UdpOutput {
+            config: Arc::new(Config::parse(&config,
+                ctx.common_config.id.unwrap_or_else(|| NAME.into()),
+                encoder_factory, chunking)?),
+        }))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(super) enum Chunking {
+    /// Events that don't fit into a single datagram are dropped.
+    None,
+
+    /// Events that don't fit into a single datagram are split into GELF chunks.
+    Gelf,
+}
+
+pub(super) struct Config {
+    /// Used as the log context.
+    id: String,
+
+    /// Address in `host:port` form. The host is resolved again after the send errors.
+    address: String,
+
+    encoder_factory: Arc<encoder::Factory>,
+    max_datagram_size: usize,
+    chunking: Chunking,
+}
+
+impl Config {
+    /// Parses the socket options.
+    pub fn parse(config: &Spanned<Value>, id: String, encoder_factory: Arc<encoder::Factory>,
+        chunking: Chunking) -> Result<Self>
+    {
+        let address = config.get(ADDRESS)?.as_str()?.to_owned();
+
+        let max_datagram_size = if let Some(v) = config.get_opt(MAX_DATAGRAM_SIZE)? {
+            let r = *v.as_int()?;
+            let min = if chunking == Chunking::Gelf { GELF_CHUNK_HEADER_LEN as i64 + 1 } else { 1 };
+            if r < min || r > DEFAULT_MAX_DATAGRAM_SIZE as i64 {
+                return Err(v.new_error(format!("`{}` must be in range [{}, {}]",
+                    MAX_DATAGRAM_SIZE, min, DEFAULT_MAX_DATAGRAM_SIZE)));
+            }
+            r as usize
+        } else if chunking == Chunking::Gelf {
+            DEFAULT_GELF_CHUNK_SIZE
+        } else {
+            DEFAULT_MAX_DATAGRAM_SIZE
+        };
+
+        Ok(Self {
+            id,
+            address,
+            encoder_factory,
+            max_datagram_size,
+            chunking,
+        })
+    }
+}
+
+struct UdpOutput {
+    config: Arc<Config>,
+}
+
+impl Output for UdpOutput {
+    fn start(&self) -> BoxFuture<Started, Error> {
+        Box::new(future::ok(Started {
+            sink: Box::new(UdpSink::new(self.config.clone())),
+        }))
+    }
+}
+
+enum Target {
+    Unresolved,
+    Resolving(BoxFuture<io::Result<SocketAddr>, Error>),
+
+    /// The resolved address and the socket bound to the local address of the same family.
+    Resolved(SocketAddr, UdpSocket),
+}
+
+/// Sends each encoded event in a separate datagram. Since UDP is unreliable anyway, events that
+/// can't be sent are logged and dropped rather than failing the sink. The sink fails if the
+/// address can't be resolved.
+pub(super) struct UdpSink {
+    config: Arc<Config>,
+    encoder: Box<encoder::Encoder>,
+    target: Target,
+
+    /// Datagrams waiting to be sent.
+    datagrams: VecDeque<Vec<u8>>,
+}
+
+impl UdpSink {
+    pub fn new(config: Arc<Config>) -> Self {
+        Self {
+            encoder: config.encoder_factory.new(),
+            config,
+            target: Target::Unresolved,
+            datagrams: VecDeque::new(),
+        }
+    }
+
+    fn poll_send(&mut self) -> Poll<(), Error> {
+        while let Some(datagram) = self.datagrams.front() {
+            match &mut self.target {
+                Target::Unresolved => {
+                    let address = self.config.address.clone();
+                    self.target = Target::Resolving(Box::new(blocking(move || resolve(&address))
+                        .infallible()));
+                }
+                Target::Resolving(f) => {
+                    let addr = try_ready!(f.poll()).wrap_err_id(ErrorId::Io)?;
+                    debug!("[{}] sending to {} ({})", self.config.id, self.config.address, addr);
+                    let local: SocketAddr = if addr.is_ipv4() {
+                        "0.0.0.0:0".parse().unwrap()
+                    } else {
+                        "[::]:0".parse().unwrap()
+                    };
+                    let socket = UdpSocket::bind(&local).wrap_err_id(ErrorId::Io)?;
+                    self.target = Target::Resolved(addr, socket);
+                }
+                Target::Resolved(addr, socket) => {
+                    match socket.poll_send_to(datagram, addr) {
+                        Ok(Async::Ready(_)) => {}
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        Err(e) => {
+                            warn!("[{}] error sending datagram to {}: {}", self.config.id,
+                                addr, e);
+                            // The address may have changed.
+                            self.target = Target::Unresolved;
+                        }
+                    }
+                    self.datagrams.pop_front();
+                }
+            }
+        }
+        Ok(Async::Ready(()))
+    }
+}
+
+static GELF_MESSAGE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns a message ID that is unique enough to not clash with the other messages being
+/// reassembled by the receiver.
+fn gelf_message_id() -> [u8; 8] {
+    let t = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let id = (t.as_secs() << 32) ^ (t.subsec_nanos() as u64) ^
+        ((GELF_MESSAGE_COUNTER.fetch_add(1, Ordering::Relaxed) as u64) << 16);
+    let mut r = [0; 8];
+    for (i, b) in r.iter_mut().enumerate() {
+        *b = (id >> (56 - i * 8)) as u8;
+    }
+    r
+}
+
+/// Splits the `message` into GELF chunks of at most `max_size` bytes.
+fn gelf_chunks(message: &[u8], max_size: usize) -> Option<Vec<Vec<u8>>> {
+    let data_size = max_size - GELF_CHUNK_HEADER_LEN;
+    let count = (message.len() + data_size - 1) / data_size;
+    if count > GELF_MAX_CHUNKS {
+        return None;
+    }
+    let id = gelf_message_id();
+    Some(message.chunks(data_size)
+        .enumerate()
+        .map(|(seq, data)| {
+            let mut chunk = Vec::with_capacity(GELF_CHUNK_HEADER_LEN + data.len());
+            chunk.extend_from_slice(GELF_CHUNK_MAGIC);
+            chunk.extend_from_slice(&id);
+            chunk.push(seq as u8);
+            chunk.push(count as u8);
+            chunk.extend_from_slice(data);
+            chunk
+        })
+        .collect())
+}
+
+impl Sink for UdpSink {
+    type SinkItem = Event;
+    type SinkError = Error;
+
+    fn start_send(&mut self, event: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
+        if self.poll_send()?.is_not_ready() {
+            return Ok(AsyncSink::NotReady(event));
+        }
+
+        let mut buf = Vec::new();
+        encode_event(&mut *self.encoder, &event, &mut buf)?;
+        if buf.len() <= self.config.max_datagram_size {
+            self.datagrams.push_back(buf);
+        } else if let Some(chunks) = match self.config.chunking {
+            Chunking::None => None,
+            Chunking::Gelf => gelf_chunks(&buf, self.config.max_datagram_size),
+        } {
+            self.datagrams.extend(chunks);
+        } else {
+            warn!("[{}] dropping event that is too large to send: {} bytes", self.config.id,
+                buf.len());
+        }
+        Ok(AsyncSink::Ready)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
+        self.poll_send()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::stream;
+    use std::net;
+
+    use super::*;
+    use crate::component::registry;
+
+    fn new(config: Value) -> Result<Box<Output>> {
+        ProviderImpl.new(New {
+            config: config.into(),
+            common_config: Default::default(),
+            metrics: Default::default(),
+        })
+    }
+
+    fn event(message: &str) -> Event {
+        let mut e = Event::new();
+        e.fields_mut().insert("message".into(), Value::String(message.into()));
+        e
+    }
+
+    /// Sends the `events` to the output and returns the datagrams received.
+    fn send(config: Value, events: Vec<Event>) -> Vec<Vec<u8>> {
+        let socket = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(std::time::Duration::from_millis(200))).unwrap();
+        let mut config = config;
+        config.as_map_mut().unwrap().insert(ADDRESS.into(),
+            Value::String(socket.local_addr().unwrap().to_string()).into());
+
+        let output = new(config).unwrap();
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(output.start()
+            .and_then(|started| stream::iter_ok(events).forward(started.sink))
+            .map(|_| {}))
+            .unwrap();
+
+        let mut r = Vec::new();
+        let mut buf = [0; 65536];
+        while let Ok(len) = socket.recv(&mut buf) {
+            r.push(buf[..len].to_vec());
+        }
+        r
+    }
+
+    #[test]
+    fn datagrams() {
+        let r = send(value!{{ MAX_DATAGRAM_SIZE => 20 }},
+            vec![event("a"), event("too large to fit"), event("b")]);
+        assert_eq!(r, vec![b"{\"message\":\"a\"}".to_vec(), b"{\"message\":\"b\"}".to_vec()]);
+    }
+
+    #[test]
+    fn unresolved() {
+        let output = new(value!{{ ADDRESS => "localhost:port" }}).unwrap();
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let started = rt.block_on(output.start()).unwrap();
+        let r = rt.block_on(stream::iter_ok::<_, Error>(vec![event("a")]).forward(started.sink));
+        assert_eq!(r.err().unwrap().id(), &ErrorId::Io);
+    }
+
+    #[test]
+    fn gelf_chunking() {
+        let message = "x".repeat(100);
+        let r = send(value!{{
+            "codec" => {"type" => "gelf", "host" => "h"},
+            CHUNKING => CHUNKING_GELF,
+            MAX_DATAGRAM_SIZE => 50,
+        }}, vec![event(&message)]);
+        assert!(r.len() > 1);
+        assert!(r.iter().all(|d| d.len() <= 50 && d.starts_with(GELF_CHUNK_MAGIC)));
+
+        let mut dec = registry().event_decoder("gelf").unwrap()
+            .new(Default::default()).unwrap().new();
+        let mut events = Vec::new();
+        for d in r.iter().rev() {
+            dec.decode(d, &mut events).unwrap();
+        }
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].fields()["message"], Value::String(message));
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert!(new(value!{{}}).is_err());
+        assert!(new(value!{{ ADDRESS => "localhost:1", CHUNKING => "bad" }}).is_err());
+        assert!(new(value!{{ ADDRESS => "localhost:1", MAX_DATAGRAM_SIZE => 0 }}).is_err());
+        assert!(new(value!{{
+            ADDRESS => "localhost:1",
+            CHUNKING => CHUNKING_GELF,
+            MAX_DATAGRAM_SIZE => 12,
+        }}).is_err());
+    }
+}
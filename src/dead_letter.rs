@@ -0,0 +1,301 @@
+use chrono::{DateTime, Utc};
+use futures::prelude::*;
+use futures::sync::mpsc;
+use log::*;
+use parking_lot::Mutex;
+use serde_json as json;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::error::*;
+use crate::event::Event;
+use crate::util::futures::BoxFuture;
+use crate::util::futures::future::blocking;
+use crate::util::json::{from_json, to_json};
+use crate::value::*;
+
+const TIMESTAMP: &'static str = "timestamp";
+const COMPONENT_ID: &'static str = "component_id";
+const ERROR_ID: &'static str = "error_id";
+const MESSAGE: &'static str = "message";
+const EVENT: &'static str = "event";
+const FRAME: &'static str = "frame";
+const FIELDS: &'static str = "fields";
+const TAGS: &'static str = "tags";
+
+/// Event that couldn't be processed along with the failure details.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeadLetter {
+    pub timestamp: DateTime<Utc>,
+
+    /// Id of the component that failed to process the event.
+    pub component_id: String,
+
+    pub error_id: ErrorId,
+    pub message: String,
+    pub event: Event,
+
+    /// Frame the input failed to decode. The `event` is empty then.
+    pub frame: Option<Vec<u8>>,
+}
+
+impl DeadLetter {
+    pub fn new(component_id: impl Into<String>, error: &Error, event: Event) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            component_id: component_id.into(),
+            error_id: *error.id(),
+            message: error.to_string(),
+            event,
+            frame: None,
+        }
+    }
+
+    pub fn from_frame(component_id: impl Into<String>, error: &Error, frame: Vec<u8>) -> Self {
+        Self {
+            frame: Some(frame),
+            ..Self::new(component_id, error, Event::new())
+        }
+    }
+
+    /// Serializes the dead letter as a single line of JSON without the line terminator. The frame
+    /// is written as a string if it's valid UTF-8 and as an array of bytes otherwise.
+    pub fn to_json(&self) -> Vec<u8> {
+        let fields = |m: &HashMap<String, Value>| json::Value::Object(m.iter()
+            .map(|(k, v)| (k.clone(), to_json(v)))
+            .collect());
+        let mut event = json::Map::new();
+        event.insert(FIELDS.into(), fields(self.event.fields()));
+        event.insert(TAGS.into(), fields(self.event.tags()));
+
+        let mut r = json::Map::new();
+        r.insert(TIMESTAMP.into(), self.timestamp.to_rfc3339().into());
+        r.insert(COMPONENT_ID.into(), self.component_id.clone().into());
+        r.insert(ERROR_ID.into(), self.error_id.name().into());
+        r.insert(MESSAGE.into(), self.message.clone().into());
+        r.insert(EVENT.into(), json::Value::Object(event));
+        if let Some(frame) = &self.frame {
+            r.insert(FRAME.into(), match std::str::from_utf8(frame) {
+                Ok(s) => s.into(),
+                Err(_) => frame.iter().map(|&b| json::Value::from(b)).collect(),
+            });
+        }
+        json::to_vec(&r).unwrap()
+    }
+
+    /// Parses the dead letter serialized with `to_json()`.
+    pub fn parse(line: &[u8]) -> Result<Self> {
+        fn field<'a>(v: &'a json::Value, name: &str) -> Result<&'a json::Value> {
+            v.get(name).ok_or_else(|| Error::new(ErrorId::Parse,
+                format!("invalid dead letter: missing `{}`", name)))
+        }
+        fn str_field<'a>(v: &'a json::Value, name: &str) -> Result<&'a str> {
+            field(v, name)?.as_str().ok_or_else(|| Error::new(ErrorId::Parse,
+                format!("invalid dead letter: `{}` must be a string", name)))
+        }
+        fn fields(v: &json::Value, name: &str, out: &mut HashMap<String, Value>) -> Result<()> {
+            match from_json(field(v, name)?.clone()) {
+                Some(Value::Map(m)) => {
+                    out.extend(m.into_iter().map(|(k, v)| (k, v.value)));
+                    Ok(())
+                }
+                _ => Err(Error::new(ErrorId::Parse,
+                    format!("invalid dead letter: `{}` must be an object", name))),
+            }
+        }
+        fn frame(v: &json::Value) -> Result<Option<Vec<u8>>> {
+            let invalid = || Error::new(ErrorId::Parse,
+                format!("invalid dead letter: `{}` must be a string or an array of bytes", FRAME));
+            Ok(Some(match v.get(FRAME) {
+                None => return Ok(None),
+                Some(json::Value::String(s)) => s.as_bytes().into(),
+                Some(json::Value::Array(a)) => a.iter()
+                    .map(|b| b.as_u64().filter(|&b| b <= 0xff).map(|b| b as u8))
+                    .collect::<Option<_>>()
+                    .ok_or_else(invalid)?,
+                Some(_) => return Err(invalid()),
+            }))
+        }
+
+        let v: json::Value = json::from_slice(line).wrap_err_id(ErrorId::Parse)?;
+        let timestamp = DateTime::parse_from_rfc3339(str_field(&v, TIMESTAMP)?)
+            .wrap_err_id(ErrorId::Parse)?
+            .with_timezone(&Utc);
+        let error_id = str_field(&v, ERROR_ID)?;
+        let error_id = ErrorId::from_name(error_id).ok_or_else(|| Error::new(ErrorId::Parse,
+            format!("invalid dead letter: unknown `{}`: {}", ERROR_ID, error_id)))?;
+        let mut event = Event::new();
+        let json_event = field(&v, EVENT)?;
+        fields(json_event, FIELDS, event.fields_mut())?;
+        fields(json_event, TAGS, event.tags_mut())?;
+        Ok(Self {
+            timestamp,
+            component_id: str_field(&v, COMPONENT_ID)?.into(),
+            error_id,
+            message: str_field(&v, MESSAGE)?.into(),
+            event,
+            frame: frame(&v)?,
+        })
+    }
+}
+
+/// Local on-disk log of dead letters. Each dead letter is appended as a line of JSON.
+///
+/// Pushing a letter never blocks: the letters are written by the `Writer` future returned along
+/// with the queue.
+pub struct DeadLetterQueue {
+    path: PathBuf,
+    tx: mpsc::UnboundedSender<Vec<u8>>,
+}
+
+impl DeadLetterQueue {
+    pub fn open(path: impl Into<PathBuf>) -> Result<(Self, Writer)> {
+        let path = path.into();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).wrap_err_id(ErrorId::Io)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .wrap_err_id(ErrorId::Io)?;
+        let file = Arc::new(Mutex::new(file));
+        let (tx, rx) = mpsc::unbounded::<Vec<u8>>();
+        let writer = rx.for_each(clone!(path => move |line| {
+            blocking(clone!(file, path => move || {
+                // Write errors are logged since there's nowhere else to send the letter.
+                if let Err(e) = file.lock().write_all(&line) {
+                    error!("couldn't write dead letter to {:?}: {}; dead letter: {}",
+                        path, e, String::from_utf8_lossy(&line[..line.len() - 1]));
+                }
+            }))
+        }));
+        Ok((Self {
+            path,
+            tx,
+        }, Writer(Box::new(writer))))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends the `letter` to the log.
+    pub fn push(&self, letter: &DeadLetter) {
+        let mut line = letter.to_json();
+        line.push(b'\n');
+        if let Err(e) = self.tx.unbounded_send(line) {
+            let line = e.into_inner();
+            error!("couldn't write dead letter to {:?}: writer is gone; dead letter: {}",
+                self.path, String::from_utf8_lossy(&line[..line.len() - 1]));
+        }
+    }
+}
+
+/// Writes the letters pushed to the `DeadLetterQueue` on a blocking thread. Must be spawned on
+/// the thread pool. Finishes once the queue and all its handles are dropped.
+pub struct Writer(BoxFuture<(), ()>);
+
+impl Future for Writer {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        self.0.poll()
+    }
+}
+
+/// Handle to the dead letter queue bound to a component.
+#[derive(Clone)]
+pub struct DeadLetters {
+    queue: Arc<DeadLetterQueue>,
+    component_id: String,
+}
+
+impl DeadLetters {
+    pub fn new(queue: Arc<DeadLetterQueue>, component_id: impl Into<String>) -> Self {
+        Self {
+            queue,
+            component_id: component_id.into(),
+        }
+    }
+
+    pub fn push(&self, error: &Error, event: Event) {
+        warn!("[{}] sending event to dead letter queue {:?}: {}", self.component_id,
+            self.queue.path(), error);
+        self.queue.push(&DeadLetter::new(self.component_id.clone(), error, event));
+    }
+
+    /// Pushes the `frame` the input failed to decode.
+    pub fn push_frame(&self, error: &Error, frame: &[u8]) {
+        warn!("[{}] sending frame to dead letter queue {:?}: {}", self.component_id,
+            self.queue.path(), error);
+        self.queue.push(&DeadLetter::from_frame(self.component_id.clone(), error, frame.into()));
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::util::test::TempDir;
+
+    const PATH: &'static str = "dlq.log";
+
+    /// Opens the queue in the `dir`.
+    pub fn open(dir: &TempDir) -> (Arc<DeadLetterQueue>, Writer) {
+        let (queue, writer) = DeadLetterQueue::open(dir.path(PATH)).unwrap();
+        (Arc::new(queue), writer)
+    }
+
+    /// Runs the `writer` of the queue opened with `open()` and reads the letters written. The
+    /// queue and all its handles must be dropped beforehand.
+    pub fn read(dir: &TempDir, writer: Writer) -> Vec<DeadLetter> {
+        tokio::runtime::Runtime::new().unwrap().block_on(writer).unwrap();
+        dir.read(PATH).lines()
+            .map(|l| DeadLetter::parse(l.as_bytes()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn json() {
+        let mut event = Event::new();
+        event.fields_mut().insert("message".into(), Value::String("foo".into()));
+        event.fields_mut().insert("n".into(), Value::Int(42));
+        event.tags_mut().insert("tag".into(), Value::Bool(true));
+        let letter = DeadLetter::new("test",
+            &Error::new(ErrorId::Parse, "invalid event"), event);
+        assert_eq!(DeadLetter::parse(&letter.to_json()).unwrap(), letter);
+        for frame in &[&b"foo"[..], b"\xff\x00"] {
+            let letter = DeadLetter::from_frame("test",
+                &Error::new(ErrorId::Parse, "invalid frame"), frame.to_vec());
+            assert_eq!(DeadLetter::parse(&letter.to_json()).unwrap(), letter);
+        }
+        assert!(DeadLetter::parse(b"{}").is_err());
+        assert!(DeadLetter::parse(b"foo").is_err());
+    }
+
+    #[test]
+    fn queue() {
+        let dir = TempDir::new("dead-letter-queue");
+        let (queue, writer) = open(&dir);
+        let dead_letters = DeadLetters::new(queue, "test");
+        for message in &["foo", "bar"] {
+            let mut event = Event::new();
+            event.fields_mut().insert("message".into(), Value::String(message.to_string()));
+            dead_letters.push(&Error::new(ErrorId::Io, "rejected"), event);
+        }
+        drop(dead_letters);
+        let letters = read(&dir, writer);
+        assert_eq!(letters.iter()
+                .map(|l| (l.component_id.as_str(), l.error_id,
+                    l.event.fields()["message"].clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("test", ErrorId::Io, Value::String("foo".into())),
+                ("test", ErrorId::Io, Value::String("bar".into())),
+            ]);
+    }
+}
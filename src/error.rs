@@ -14,4 +14,23 @@ pub enum ErrorId {
 
     #[fail(display = "Unknown error")]
     Unknown,
+}
+
+impl ErrorId {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorId::Io => "io",
+            ErrorId::Parse => "parse",
+            ErrorId::Unknown => "unknown",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "io" => ErrorId::Io,
+            "parse" => ErrorId::Parse,
+            "unknown" => ErrorId::Unknown,
+            _ => return None,
+        })
+    }
 }
\ No newline at end of file
@@ -23,4 +23,12 @@ impl Event {
     pub fn fields_mut(&mut self) -> &mut HashMap<String, Value> {
         &mut self.fields
     }
+
+    pub fn tags(&self) -> &HashMap<String, Value> {
+        &self.tags
+    }
+
+    pub fn tags_mut(&mut self) -> &mut HashMap<String, Value> {
+        &mut self.tags
+    }
 }
\ No newline at end of file
@@ -6,6 +6,7 @@
 #[macro_use]
 mod macros;
 
+mod dead_letter;
 mod error;
 mod event;
 mod component;
@@ -48,6 +49,8 @@ fn main() {
         })
         .init();
 
+    let metrics = Arc::new(Metrics::new());
+
     let input = registry().input("file").unwrap().new(input::New {
         config: value!{{
             "path" => [
@@ -60,10 +63,9 @@ fn main() {
         }}.into(),
         common_config: input::CommonConfig {
             .. Default::default()
-        }})
-        .unwrap();
-
-    let metrics = Arc::new(Metrics::new());
+        },
+        metrics: metrics.clone(),
+    }).unwrap();
 
     let mut ppl_builder = pipeline::PipelineBuilder::new(metrics.clone());
     ppl_builder
@@ -78,14 +80,18 @@ fn main() {
                         common_config: Default::default()
                     }).unwrap()],
                 Box::new(pipeline::Node::Outputs(vec![
-                    component::registry().output("stdout").unwrap().new(output::New {
-                        config: value! {{}}.into(),
-                        common_config: Default::default()
-                    }).unwrap(),
-                    component::registry().output("null").unwrap().new(output::New {
-                        config: value! {{}}.into(),
-                        common_config: Default::default(),
-                    }).unwrap(),
+                    pipeline::OutputInfo::new("stdout".into(),
+                        component::registry().output("stdout").unwrap().new(output::New {
+                            config: value! {{}}.into(),
+                            common_config: Default::default(),
+                            metrics: metrics.clone(),
+                        }).unwrap()),
+                    pipeline::OutputInfo::new("null".into(),
+                        component::registry().output("null").unwrap().new(output::New {
+                            config: value! {{}}.into(),
+                            common_config: Default::default(),
+                            metrics: metrics.clone(),
+                        }).unwrap()),
                 ])))))
     ;
 
@@ -42,6 +42,15 @@ impl Metrics {
         self.values.lock().insert(name, value);
     }
 
+    /// Sets the metric value unless the metric already exists.
+    pub fn set_default(&self, name: String, value: Value) {
+        self.values.lock().entry(name).or_insert(value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.values.lock().get(name).cloned()
+    }
+
     pub fn inc(&self, name: &str, delta: impl Into<Number>) {
         let delta = delta.into();
         match delta {
@@ -72,6 +81,12 @@ impl Metrics {
     }
 }
 
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl fmt::Debug for Metrics {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:#?}", &*self.values.lock())
@@ -1,17 +1,20 @@
 use futures::future;
 use futures::prelude::*;
-use futures::stream::futures_unordered::FuturesUnordered;
+use futures::sink::BoxSink;
 use futures::sync::mpsc;
+use futures::try_ready;
 use futures_mpmc::{array as mpmc};
-use futures_retry::{FutureRetry, StreamRetryExt};
+use futures_retry::{ErrorHandler, FutureRetry, RetryPolicy, StreamRetryExt};
 use log::*;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::executor;
+use tokio::timer::Delay;
 
 use crate::component::input::Input;
 use crate::component::filter;
-use crate::component::output::Output;
+use crate::component::output::{Output, Started};
+use crate::dead_letter::{DeadLetterQueue, DeadLetters, Writer};
 use crate::error::*;
 use crate::event::Event;
 use crate::metric::{self, Metrics};
@@ -24,6 +27,38 @@ struct InputInfo {
     pub input: Box<Input>,
 }
 
+pub struct OutputInfo {
+    pub id: String,
+    pub output: Box<Output>,
+    pub failure_policy: FailurePolicy,
+}
+
+impl OutputInfo {
+    pub fn new(id: String, output: Box<Output>) -> Self {
+        Self {
+            id,
+            output,
+            failure_policy: FailurePolicy::Block,
+        }
+    }
+}
+
+/// Specifies what happens to the events for an output while it's being restarted after a
+/// failure.
+pub enum FailurePolicy {
+    /// The events are held. Once the output queue is full the whole output group is blocked.
+    /// The output stops restarting once its queue is closed and no event is held, otherwise it
+    /// keeps restarting until it delivers the held event.
+    Block,
+
+    /// The events are dropped and counted in the `output.<id>.dropped` metric.
+    Drop,
+
+    /// The events are sent to the fallback output. The fallback output is started along with the
+    /// output and has its own failure policy.
+    Fallback(Box<OutputInfo>),
+}
+
 pub trait Predicate: 'static + Send + Sync {
     fn test(&self, event: &Event) -> Result<bool>;
 }
@@ -31,7 +66,7 @@ pub trait Predicate: 'static + Send + Sync {
 pub enum Node {
     Filters((Vec<Arc<filter::Starter>>, Box<Node>)),
     Switch(Vec<(Arc<Predicate>, Box<Node>)>),
-    Outputs(Vec<Box<Output>>),
+    Outputs(Vec<OutputInfo>),
 }
 
 enum IntNode {
@@ -47,7 +82,7 @@ enum IntNode {
 
 impl IntNode {
     fn from(node: Node, filters: &mut Vec<Arc<filter::Starter>>,
-        output_groups: &mut Vec<Vec<Box<Output>>>) -> Self
+        output_groups: &mut Vec<Vec<OutputInfo>>) -> Self
     {
         match node {
             Node::Filters((f, next)) => {
@@ -86,6 +121,9 @@ pub struct PipelineBuilder {
     filter_concurrency: usize,
     inputs: Vec<InputInfo>,
     graph: Option<Node>,
+    output_restart_delay: Duration,
+    dead_letter_queue: Option<Arc<DeadLetterQueue>>,
+    dead_letter_writer: Option<Writer>,
     metrics: Arc<Metrics>,
 }
 
@@ -96,6 +134,9 @@ impl PipelineBuilder {
             in_queue_capacity: 100,
             inputs: Vec::new(),
             graph: None,
+            output_restart_delay: Duration::from_secs(1),
+            dead_letter_queue: None,
+            dead_letter_writer: None,
             metrics,
         }
     }
@@ -115,6 +156,24 @@ impl PipelineBuilder {
         self
     }
 
+    /// Initial delay before restarting a failed output. The delay doubles with each consecutive
+    /// failure up to a minute.
+    pub fn output_restart_delay(&mut self, output_restart_delay: Duration) -> &mut Self {
+        self.output_restart_delay = output_restart_delay;
+        self
+    }
+
+    /// Queue for the events the filters fail on. The filters are identified by their position in
+    /// the graph: `filter-1`, `filter-2` and so on. Without the queue a filter error fails the
+    /// filter graph. The queue `writer` is spawned when the pipeline starts.
+    pub fn dead_letter_queue(&mut self, dead_letter_queue: Arc<DeadLetterQueue>, writer: Writer)
+        -> &mut Self
+    {
+        self.dead_letter_queue = Some(dead_letter_queue);
+        self.dead_letter_writer = Some(writer);
+        self
+    }
+
     fn gen_input_id(&self, name: &str) -> String {
         let mut i = 1;
         for input in &self.inputs {
@@ -139,21 +198,25 @@ impl PipelineBuilder {
     pub fn start(self) {
         assert!(self.graph.is_some());
 
+        if let Some(writer) = self.dead_letter_writer {
+            executor::spawn(writer);
+        }
+
         let (in_queue_tx, in_queue_rx) = mpmc::array::<Event>(self.in_queue_capacity);
 
-        Self::start_inputs(self.inputs, in_queue_tx, self.metrics);
+        Self::start_inputs(self.inputs, in_queue_tx, self.metrics.clone());
 
         let mut filters = Vec::new();
         let mut output_groups = Vec::new();
         let graph = Arc::new(IntNode::from(self.graph.unwrap(), &mut filters, &mut output_groups));
 
         let output_groups = Self::start_output_groups(output_groups, self.filter_concurrency,
-            self.filter_concurrency);
+            self.filter_concurrency, self.output_restart_delay, &self.metrics);
 
         let filter_concurrency = self.filter_concurrency;
 
         for _ in 0..filter_concurrency {
-            executor::spawn(Self::start_filters(&filters)
+            executor::spawn(Self::start_filters(&filters, self.dead_letter_queue.clone())
                 .map_err(|e| error!("{:?}", e))
                 .map(clone!(graph, in_queue_rx, output_groups => move |filters| {
                     let mut filters: Vec<_> = filters.into_iter().map(Some).collect();
@@ -250,7 +313,8 @@ impl PipelineBuilder {
         }
     }
 
-    fn start_filters(starters: &[Arc<filter::Starter>])
+    fn start_filters(starters: &[Arc<filter::Starter>],
+            dead_letter_queue: Option<Arc<DeadLetterQueue>>)
             -> impl Future<Item=Vec<Box<filter::Filter + 'static>>, Error=Error> {
         let futs = starters.iter()
             .map(|s| {
@@ -259,6 +323,17 @@ impl PipelineBuilder {
             })
             .collect::<Vec<_>>();
         future::join_all(futs)
+            .map(move |filters| if let Some(queue) = dead_letter_queue {
+                filters.into_iter()
+                    .enumerate()
+                    .map(|(i, filter)| Box::new(DeadLetterFilter {
+                        filter,
+                        dead_letters: DeadLetters::new(queue.clone(), format!("filter-{}", i + 1)),
+                    }) as Box<filter::Filter>)
+                    .collect()
+            } else {
+                filters
+            })
     }
 
     fn chain_filters(filters: impl IntoIterator<Item=Box<filter::Filter>>) -> FilterChain {
@@ -276,53 +351,463 @@ impl PipelineBuilder {
         })
     }
 
-    fn start_output_groups(output_groups: Vec<Vec<Box<Output>>>, group_queue_capacity: usize,
-        output_queue_capacity: usize) -> Vec<mpsc::Sender<Event>>
+    fn start_output_groups(output_groups: Vec<Vec<OutputInfo>>, group_queue_capacity: usize,
+        output_queue_capacity: usize, restart_delay: Duration, metrics: &Arc<Metrics>)
+        -> Vec<mpsc::Sender<Event>>
     {
         output_groups.into_iter()
-            .map(|o| Self::start_output_group(o, group_queue_capacity, output_queue_capacity))
+            .map(|o| Self::start_output_group(o, group_queue_capacity, output_queue_capacity,
+                restart_delay, metrics))
             .collect()
     }
 
-    fn start_output_group(outputs: Vec<Box<Output>>, group_queue_capacity: usize,
-        output_queue_capacity: usize) -> mpsc::Sender<Event>
+    fn start_output_group(outputs: Vec<OutputInfo>, group_queue_capacity: usize,
+        output_queue_capacity: usize, restart_delay: Duration, metrics: &Arc<Metrics>)
+        -> mpsc::Sender<Event>
     {
-        let mut txs = Vec::new();
-
-        for output in outputs {
-            let (tx, rx) = mpsc::channel::<Event>(output_queue_capacity);
-            executor::spawn(future::lazy(move || {
-                info!("starting output");
-                output.start()
-                    // TODO handle output start failures.
-                    .inspect_err(|e| error!("output start error: {:?}", e))
-                    .map(|o| o.sink)
-                    .and_then(move |output_sink| rx
-                        .infallible()
-                        // TODO don't fail the root future when output sink fails
-                        .forward(output_sink)
-                        .map(|_| {}))
-                    .map_err(|e| error!("output send error: {:?}", e))
-            }));
-            txs.push(tx);
-        }
+        let txs = outputs.into_iter()
+            .map(|o| Self::start_output(o, output_queue_capacity, restart_delay, metrics))
+            .collect::<Vec<_>>();
 
-        // Make broadcasting channel.
-        let txs = Arc::new(txs);
+        // Make broadcasting channel. The senders are kept for the whole lifetime of the group so
+        // an output that doesn't keep up blocks the group instead of growing its queue.
+        let mut txs = txs.into_iter();
+        let first: BoxSink<Event, mpsc::SendError<Event>> = Box::new(txs.next().unwrap());
+        let bcast = txs.fold(first, |r, tx| Box::new(r.fanout(tx)));
         let (bcast_tx, bcast_rx) = mpsc::channel::<Event>(group_queue_capacity);
         executor::spawn(bcast_rx
-            .for_each(clone!(txs => move |event| {
-                let mut futs = FuturesUnordered::new();
-                for tx in &txs[..txs.len() - 1] {
-                    futs.push(tx.clone().send(event.clone()))
+            .forward(bcast
+                .sink_map_err(|e| error!("error sending to one of output queues: {:?}", e)))
+            .map(|_| {}));
+
+        bcast_tx
+    }
+
+    /// Starts the output along with its fallback outputs. Returns the output queue sender.
+    fn start_output(output: OutputInfo, queue_capacity: usize, restart_delay: Duration,
+        metrics: &Arc<Metrics>) -> mpsc::Sender<Event>
+    {
+        let OutputInfo { id, output, failure_policy } = output;
+        let on_failure = match failure_policy {
+            FailurePolicy::Block => OnFailure::Block,
+            FailurePolicy::Drop => OnFailure::Drop,
+            FailurePolicy::Fallback(fallback) => OnFailure::Fallback(
+                Self::start_output(*fallback, queue_capacity, restart_delay, metrics)),
+        };
+        let (tx, rx) = mpsc::channel::<Event>(queue_capacity);
+        executor::spawn(future::lazy(clone!(metrics => move || {
+            OutputTask::new(id, output, rx, on_failure, restart_delay, metrics)
+        })));
+        tx
+    }
+}
+
+/// Sends the events the filter fails on to the dead letter queue instead of failing the stream.
+/// The failed event is taken from the `FailedEvent` details of the error, the errors without
+/// them are only logged.
+struct DeadLetterFilter {
+    filter: Box<filter::Filter>,
+    dead_letters: DeadLetters,
+}
+
+impl filter::Filter for DeadLetterFilter {
+    fn filter(&mut self, event: Event) -> BoxStream<Event, Error> {
+        let dead_letters = self.dead_letters.clone();
+        Box::new(self.filter.filter(event)
+            .then(move |r| Ok(match r {
+                Ok(event) => Some(event),
+                Err(e) => {
+                    let event = e.details()
+                        .and_then(|d| d.downcast_ref::<filter::FailedEvent>())
+                        .map(|d| d.event.clone());
+                    if let Some(event) = event {
+                        dead_letters.push(&e, event);
+                    } else {
+                        error!("filter error without the failed event: {:?}", e);
+                    }
+                    None
+                }
+            }))
+            .filter_map(|v| v))
+    }
+}
+
+const MAX_OUTPUT_RESTART_DELAY: Duration = Duration::from_secs(60);
+
+enum OnFailure {
+    Block,
+    Drop,
+    Fallback(mpsc::Sender<Event>),
+}
+
+enum OutputState {
+    Starting(BoxFuture<Started, Error>),
+    Waiting(Delay),
+    Running(BoxSink<Event, Error>),
+}
+
+/// Forwards the events from the output queue to the output. The output is restarted with
+/// exponential backoff when it fails to start or its sink fails. The events the failed sink has
+/// accepted are lost. While the output isn't running the events are handled according to the
+/// failure policy.
+struct OutputTask {
+    id: String,
+    output: Box<Output>,
+    rx: mpsc::Receiver<Event>,
+    on_failure: OnFailure,
+    state: OutputState,
+    restart_delay: Duration,
+    retry: RetryErrorHandler,
+
+    /// Event received from the queue but not yet accepted by the sink.
+    pending: Option<Event>,
+
+    /// Whether the sink has accepted events since it was last flushed.
+    sent: bool,
+
+    dropped_metric: String,
+    metrics: Arc<Metrics>,
+}
+
+impl OutputTask {
+    fn new(id: String, output: Box<Output>, rx: mpsc::Receiver<Event>, on_failure: OnFailure,
+        restart_delay: Duration, metrics: Arc<Metrics>) -> Self
+    {
+        let dropped_metric = format!("output.{}.dropped", id);
+        metrics.set(dropped_metric.clone(), metric::Value::Counter(0.into()));
+        info!("[{}] starting output", id);
+        Self {
+            state: OutputState::Starting(output.start()),
+            retry: Self::retry_handler(&id, restart_delay),
+            id,
+            output,
+            rx,
+            on_failure,
+            restart_delay,
+            pending: None,
+            sent: false,
+            dropped_metric,
+            metrics,
+        }
+    }
+
+    fn retry_handler(id: &str, restart_delay: Duration) -> RetryErrorHandler {
+        RetryErrorHandler::new(None, restart_delay, MAX_OUTPUT_RESTART_DELAY, id,
+            "running output")
+    }
+
+    fn restart(&mut self, e: Error) {
+        self.state = match self.retry.handle(e) {
+            RetryPolicy::Repeat => OutputState::Starting(self.output.start()),
+            RetryPolicy::WaitRetry(delay) => OutputState::Waiting(
+                Delay::new(Instant::now() + delay)),
+            RetryPolicy::ForwardError(_) => unreachable!(),
+        };
+    }
+
+    /// Forwards the events to the running output. Completes once the queue is exhausted and the
+    /// sink is closed.
+    fn poll_running(&mut self) -> Poll<(), Error> {
+        let sink = if let OutputState::Running(v) = &mut self.state {
+            v
+        } else {
+            unreachable!();
+        };
+        loop {
+            if let Some(event) = self.pending.take() {
+                if let AsyncSink::NotReady(event) = sink.start_send(event)? {
+                    self.pending = Some(event);
+                    return Ok(Async::NotReady);
+                }
+                self.sent = true;
+            }
+            match self.rx.poll().unwrap() {
+                Async::Ready(Some(event)) => self.pending = Some(event),
+                Async::Ready(None) => {
+                    try_ready!(sink.close());
+                    info!("[{}] finished output", self.id);
+                    return Ok(Async::Ready(()));
+                }
+                Async::NotReady => {
+                    try_ready!(sink.poll_complete());
+                    if self.sent {
+                        // The output is healthy again.
+                        self.sent = false;
+                        self.retry = Self::retry_handler(&self.id, self.restart_delay);
+                    }
+                    return Ok(Async::NotReady);
+                }
+            }
+        }
+    }
+
+    /// Handles the events while the output isn't running.
+    fn poll_failed(&mut self) -> Poll<(), ()> {
+        match &mut self.on_failure {
+            OnFailure::Block => {
+                // Nothing is left to deliver once the queue is closed and exhausted.
+                if self.pending.is_none() {
+                    match self.rx.poll().unwrap() {
+                        Async::Ready(Some(event)) => self.pending = Some(event),
+                        Async::Ready(None) => {
+                            warn!("[{}] giving up restarting output: queue is closed", self.id);
+                            return Ok(Async::Ready(()));
+                        }
+                        Async::NotReady => {}
+                    }
+                }
+                Ok(Async::NotReady)
+            }
+            OnFailure::Drop => loop {
+                if self.pending.take().is_some() {
+                    self.metrics.inc(&self.dropped_metric, 1);
+                }
+                match self.rx.poll().unwrap() {
+                    Async::Ready(Some(event)) => self.pending = Some(event),
+                    Async::Ready(None) => return Ok(Async::Ready(())),
+                    Async::NotReady => return Ok(Async::NotReady),
+                }
+            }
+            OnFailure::Fallback(tx) => loop {
+                if let Some(event) = self.pending.take() {
+                    match tx.start_send(event) {
+                        Ok(AsyncSink::Ready) => {}
+                        Ok(AsyncSink::NotReady(event)) => {
+                            self.pending = Some(event);
+                            return Ok(Async::NotReady);
+                        }
+                        Err(e) => {
+                            error!("[{}] fallback output is gone: {:?}", self.id, e);
+                            self.metrics.inc(&self.dropped_metric, 1);
+                        }
+                    }
+                }
+                match self.rx.poll().unwrap() {
+                    Async::Ready(Some(event)) => self.pending = Some(event),
+                    Async::Ready(None) => return Ok(Async::Ready(())),
+                    Async::NotReady => return Ok(Async::NotReady),
                 }
-                futs.push(txs.last().unwrap().clone().send(event));
+            }
+        }
+    }
+}
 
-                futs.for_each(|_| Ok(()))
-                    .map_err(|e| error!("error sending to one of output queues: {:?}", e))
+impl Future for OutputTask {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        loop {
+            let e = match &mut self.state {
+                OutputState::Starting(f) => match f.poll() {
+                    Ok(Async::Ready(started)) => {
+                        info!("[{}] started output", self.id);
+                        self.state = OutputState::Running(started.sink);
+                        continue;
+                    }
+                    Ok(Async::NotReady) => return self.poll_failed(),
+                    Err(e) => e,
+                }
+                OutputState::Waiting(delay) => match delay.poll() {
+                    Ok(Async::Ready(())) => {
+                        info!("[{}] restarting output", self.id);
+                        self.state = OutputState::Starting(self.output.start());
+                        continue;
+                    }
+                    Ok(Async::NotReady) => return self.poll_failed(),
+                    Err(e) => Error::new(ErrorId::Unknown, e.to_string()),
+                }
+                OutputState::Running(_) => match self.poll_running() {
+                    Ok(v) => return Ok(v),
+                    Err(e) => e,
+                }
+            };
+            self.restart(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::stream;
+    use parking_lot::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::component::filter::Filter;
+    use crate::dead_letter;
+    use crate::util::test::TempDir;
+    use crate::value::Value;
+
+    /// Output that fails the first `start_failures` starts. The sink of each start fails after
+    /// accepting `fail_after` events.
+    #[derive(Default)]
+    struct MockOutput {
+        start_failures: usize,
+        fail_after: Option<usize>,
+        starts: Arc<AtomicUsize>,
+        received: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Output for MockOutput {
+        fn start(&self) -> BoxFuture<Started, Error> {
+            if self.starts.fetch_add(1, Ordering::SeqCst) < self.start_failures {
+                return Box::new(future::err(Error::new(ErrorId::Io, "start failed")));
+            }
+            let sink = MockSink {
+                left: self.fail_after,
+                received: self.received.clone(),
+            };
+            Box::new(future::ok(Started {
+                sink: Box::new(sink),
             }))
-        );
+        }
+    }
 
-        bcast_tx
+    struct MockSink {
+        left: Option<usize>,
+        received: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Sink for MockSink {
+        type SinkItem = Event;
+        type SinkError = Error;
+
+        fn start_send(&mut self, event: Event) -> StartSend<Event, Error> {
+            if self.left == Some(0) {
+                return Err(Error::new(ErrorId::Io, "send failed"));
+            }
+            self.left = self.left.map(|v| v - 1);
+            if let Some(Value::String(s)) = event.fields().get("message") {
+                self.received.lock().push(s.clone());
+            }
+            Ok(AsyncSink::Ready)
+        }
+
+        fn poll_complete(&mut self) -> Poll<(), Error> {
+            Ok(Async::Ready(()))
+        }
+    }
+
+    fn events(n: usize) -> Vec<Event> {
+        (0..n)
+            .map(|i| {
+                let mut e = Event::new();
+                e.fields_mut().insert("message".into(), Value::String(i.to_string()));
+                e
+            })
+            .collect()
+    }
+
+    fn messages(v: &[usize]) -> Vec<String> {
+        v.iter().map(|i| i.to_string()).collect()
+    }
+
+    /// Sends the `events` through the started output and waits for all the tasks to finish.
+    fn run(output: OutputInfo, events: Vec<Event>, metrics: Arc<Metrics>) {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.executor().spawn(future::lazy(move || {
+            let tx = PipelineBuilder::start_output(output, 4, Duration::from_millis(1), &metrics);
+            tx.send_all(stream::iter_ok(events))
+                .map(|_| {})
+                .map_err(|e| panic!("{:?}", e))
+        }));
+        rt.shutdown_on_idle().wait().unwrap();
+    }
+
+    #[test]
+    fn restart() {
+        let output = MockOutput {
+            start_failures: 2,
+            fail_after: Some(3),
+            ..Default::default()
+        };
+        let starts = output.starts.clone();
+        let received = output.received.clone();
+        run(OutputInfo::new("test".into(), Box::new(output)), events(10), Default::default());
+        // The events accepted by the failed sink are lost.
+        assert_eq!(*received.lock(), messages(&[0, 1, 2, 4, 5, 6, 8, 9]));
+        assert_eq!(starts.load(Ordering::SeqCst), 5);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn block() {
+        let output = MockOutput {
+            start_failures: usize::max_value(),
+            ..Default::default()
+        };
+        let starts = output.starts.clone();
+        // Finishes once the queue is closed.
+        run(OutputInfo::new("test".into(), Box::new(output)), events(0), Default::default());
+        assert!(starts.load(Ordering::SeqCst) > 0);
+    }
+
+    #[test]
+    fn drop() {
+        let output = MockOutput {
+            start_failures: usize::max_value(),
+            ..Default::default()
+        };
+        let metrics = Arc::new(Metrics::new());
+        run(OutputInfo {
+            id: "test".into(),
+            output: Box::new(output),
+            failure_policy: FailurePolicy::Drop,
+        }, events(10), metrics.clone());
+        assert_eq!(metrics.get("output.test.dropped"),
+            Some(metric::Value::Counter(10.into())));
+    }
+
+    #[test]
+    fn fallback() {
+        let output = MockOutput {
+            start_failures: usize::max_value(),
+            ..Default::default()
+        };
+        let fallback = MockOutput::default();
+        let received = fallback.received.clone();
+        run(OutputInfo {
+            id: "test".into(),
+            output: Box::new(output),
+            failure_policy: FailurePolicy::Fallback(Box::new(
+                OutputInfo::new("fallback".into(), Box::new(fallback)))),
+        }, events(10), Default::default());
+        assert_eq!(*received.lock(), messages(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]));
+    }
+
+    /// Filter that fails on the events with `message` equal to `"1"`.
+    struct FailingFilter;
+
+    impl Filter for FailingFilter {
+        fn filter(&mut self, event: Event) -> BoxStream<Event, Error> {
+            if event.fields().get("message") == Some(&Value::String("1".into())) {
+                Box::new(stream::once(Err(Error::new(ErrorId::Unknown, filter::FailedEvent {
+                    message: "filter failed".into(),
+                    event,
+                }))))
+            } else {
+                Box::new(stream::once(Ok(event)))
+            }
+        }
+    }
+
+    #[test]
+    fn dead_letter_filter() {
+        let dir = TempDir::new("pipeline-dead-letter-filter");
+        let (queue, writer) = dead_letter::test::open(&dir);
+        let mut filter = DeadLetterFilter {
+            filter: Box::new(FailingFilter),
+            dead_letters: DeadLetters::new(queue, "filter-1"),
+        };
+        let passed = events(3).into_iter()
+            .flat_map(|e| filter.filter(e).wait().map(|r| r.unwrap()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        std::mem::drop(filter);
+        let letters = dead_letter::test::read(&dir, writer);
+
+        assert_eq!(passed, vec![events(3)[0].clone(), events(3)[2].clone()]);
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].component_id, "filter-1");
+        assert_eq!(letters[0].event, events(3)[1]);
+    }
+}
@@ -1,2 +1,9 @@
+pub mod csv;
 pub mod error;
 pub mod futures;
+pub mod json;
+pub mod msgpack;
+pub mod sys;
+pub mod template;
+#[cfg(test)]
+pub mod test;
@@ -0,0 +1,166 @@
+use crate::error::*;
+use crate::value::*;
+
+const DELIMITER: &'static str = "delimiter";
+const QUOTE: &'static str = "quote";
+const ESCAPE: &'static str = "escape";
+
+/// CSV flavor.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Dialect {
+    pub delimiter: u8,
+    pub quote: u8,
+
+    /// Character that escapes the following character inside quoted fields. If not set quotes
+    /// are escaped by doubling them.
+    pub escape: Option<u8>,
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: b'"',
+            escape: None,
+        }
+    }
+}
+
+impl Dialect {
+    /// Parses `delimiter`, `quote` and `escape` keys of the `config`.
+    pub fn parse(config: &Spanned<Value>) -> Result<Self> {
+        fn byte(v: &Spanned<Value>) -> Result<u8> {
+            match v.as_str()?.as_bytes() {
+                &[b] if b.is_ascii() => Ok(b),
+                _ => Err(v.new_error("single ASCII character expected")),
+            }
+        }
+
+        let mut r = Self::default();
+        if let Some(v) = config.get_opt(DELIMITER)? {
+            r.delimiter = byte(v)?;
+        }
+        if let Some(v) = config.get_opt(QUOTE)? {
+            r.quote = byte(v)?;
+        }
+        if let Some(v) = config.get_opt(ESCAPE)? {
+            r.escape = Some(byte(v)?);
+        }
+        Ok(r)
+    }
+
+    /// Splits the `record` into unquoted and unescaped fields. Returns `false` if the record
+    /// has an unterminated quoted field, the fields parsed so far are still written to `out`.
+    pub fn split(&self, record: &[u8], out: &mut Vec<Vec<u8>>) -> bool {
+        let mut field = Vec::new();
+        let mut quoted = false;
+        let mut i = 0;
+        while i < record.len() {
+            let b = record[i];
+            i += 1;
+            if quoted {
+                if Some(b) == self.escape && i < record.len() {
+                    field.push(record[i]);
+                    i += 1;
+                } else if b == self.quote {
+                    if self.escape.is_none() && record.get(i) == Some(&self.quote) {
+                        field.push(b);
+                        i += 1;
+                    } else {
+                        quoted = false;
+                    }
+                } else {
+                    field.push(b);
+                }
+            } else if b == self.delimiter {
+                out.push(std::mem::replace(&mut field, Vec::new()));
+            } else if b == self.quote {
+                quoted = true;
+            } else {
+                field.push(b);
+            }
+        }
+        out.push(field);
+        !quoted
+    }
+
+    /// Writes the `fields` as a record to `out`. Fields that contain delimiter, quote, escape or
+    /// line break characters are quoted. The record is not terminated.
+    pub fn join<'a>(&self, fields: impl IntoIterator<Item=&'a [u8]>, out: &mut Vec<u8>) {
+        for (i, field) in fields.into_iter().enumerate() {
+            if i > 0 {
+                out.push(self.delimiter);
+            }
+            let quoted = field.iter().any(|&b| b == self.delimiter || b == self.quote ||
+                Some(b) == self.escape || b == b'\r' || b == b'\n');
+            if !quoted {
+                out.extend_from_slice(field);
+                continue;
+            }
+            out.push(self.quote);
+            for &b in field {
+                if b == self.quote || Some(b) == self.escape {
+                    out.push(self.escape.unwrap_or(self.quote));
+                }
+                out.push(b);
+            }
+            out.push(self.quote);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn split(dialect: Dialect, record: &str) -> (Vec<String>, bool) {
+        let mut fields = Vec::new();
+        let ok = dialect.split(record.as_bytes(), &mut fields);
+        (fields.into_iter().map(|f| String::from_utf8(f).unwrap()).collect(), ok)
+    }
+
+    #[test]
+    fn split_default() {
+        let d = Dialect::default();
+        assert_eq!(split(d, ""), (vec!["".to_owned()], true));
+        assert_eq!(split(d, "a,,b"), (vec!["a".into(), "".into(), "b".into()], true));
+        assert_eq!(split(d, r#""a,""b""
+c",d"#), (vec!["a,\"b\"\nc".into(), "d".into()], true));
+        assert_eq!(split(d, r#"a,"b"#), (vec!["a".into(), "b".into()], false));
+    }
+
+    #[test]
+    fn split_escape() {
+        let d = Dialect {
+            delimiter: b'\t',
+            quote: b'\'',
+            escape: Some(b'\\'),
+        };
+        assert_eq!(split(d, r"'a\'\\'	b"), (vec![r"a'\".into(), "b".into()], true));
+    }
+
+    fn join(dialect: Dialect, fields: &[&str]) -> String {
+        let mut out = Vec::new();
+        dialect.join(fields.iter().map(|f| f.as_bytes()), &mut out);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn join_default() {
+        let d = Dialect::default();
+        assert_eq!(join(d, &[]), "");
+        assert_eq!(join(d, &["a", "", "b c"]), "a,,b c");
+        assert_eq!(join(d, &["a,\"b\"\nc", "d"]), r#""a,""b""
+c",d"#);
+    }
+
+    #[test]
+    fn join_escape() {
+        let d = Dialect {
+            delimiter: b'\t',
+            quote: b'\'',
+            escape: Some(b'\\'),
+        };
+        assert_eq!(join(d, &[r"a'\", "b"]), r"'a\'\\'	b");
+    }
+}
@@ -14,7 +14,7 @@ pub trait Object: 'static + Any + fmt::Debug + fmt::Display + Send {
 impl dyn Object {
     pub fn is<T: Object + 'static>(&self) -> bool {
         let t = TypeId::of::<T>();
-        let boxed = self.type_id();
+        let boxed = Object::type_id(self);
         t == boxed
     }
 
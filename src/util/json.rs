@@ -0,0 +1,55 @@
+use serde_json as json;
+use std::borrow::Cow;
+
+use crate::value::*;
+
+/// Converts JSON value to `Value`. Returns `None` for `null` since it has no `Value`
+/// counterpart, such values are omitted from maps and lists.
+pub fn from_json(value: json::Value) -> Option<Value> {
+    Some(match value {
+        json::Value::Null => return None,
+        json::Value::Bool(v) => Value::Bool(v),
+        json::Value::Number(v) => if let Some(v) = v.as_i64() {
+            Value::Int(v)
+        } else {
+            // Either a float or an integer that doesn't fit `i64`.
+            Value::Float(v.as_f64().unwrap())
+        }
+        json::Value::String(v) => Value::String(v),
+        json::Value::Array(v) => Value::List(v.into_iter()
+            .filter_map(from_json)
+            .map(|v| v.into())
+            .collect()),
+        json::Value::Object(v) => Value::Map(v.into_iter()
+            .filter_map(|(k, v)| from_json(v).map(|v| (k, v.into())))
+            .collect()),
+    })
+}
+
+/// Converts `Value` to JSON value. Non-finite floats become `null`, bytes become arrays of
+/// numbers.
+pub fn to_json(value: &Value) -> json::Value {
+    match value {
+        Value::Bool(v) => json::Value::Bool(*v),
+        Value::Int(v) => json::Value::Number((*v).into()),
+        Value::Float(v) => json::Number::from_f64(*v)
+            .map(json::Value::Number)
+            .unwrap_or(json::Value::Null),
+        Value::String(v) => json::Value::String(v.clone()),
+        Value::Bytes(v) => json::Value::Array(v.iter().map(|&b| b.into()).collect()),
+        Value::List(v) => json::Value::Array(v.iter().map(|v| to_json(v)).collect()),
+        Value::Map(v) => json::Value::Object(v.iter()
+            .map(|(k, v)| (k.clone(), to_json(v)))
+            .collect()),
+    }
+}
+
+/// Converts `Value` to plain text: strings are returned as is, bytes are decoded as UTF-8,
+/// everything else is converted to JSON.
+pub fn to_text(value: &Value) -> Cow<'_, str> {
+    match value {
+        Value::String(v) => Cow::Borrowed(v),
+        Value::Bytes(v) => String::from_utf8_lossy(v),
+        _ => Cow::Owned(to_json(value).to_string()),
+    }
+}
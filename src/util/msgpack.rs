@@ -0,0 +1,62 @@
+use rmpv::Value as MsgPack;
+
+use crate::value::*;
+
+/// Converts MessagePack value to `Value`. Returns `None` for `nil` since it has no `Value`
+/// counterpart, such values are omitted from maps and lists. Non-UTF-8 strings and
+/// extension values become bytes, non-string map keys are converted to strings.
+pub fn from_msgpack(value: MsgPack) -> Option<Value> {
+    Some(match value {
+        MsgPack::Nil => return None,
+        MsgPack::Boolean(v) => Value::Bool(v),
+        MsgPack::Integer(v) => if let Some(v) = v.as_i64() {
+            Value::Int(v)
+        } else {
+            // Integer that doesn't fit `i64`.
+            Value::Float(v.as_f64().unwrap())
+        }
+        MsgPack::F32(v) => Value::Float(v as f64),
+        MsgPack::F64(v) => Value::Float(v),
+        MsgPack::String(v) => if v.is_str() {
+            Value::String(v.into_str().unwrap())
+        } else {
+            Value::Bytes(v.into_bytes())
+        }
+        MsgPack::Binary(v) => Value::Bytes(v),
+        MsgPack::Ext(_, v) => Value::Bytes(v),
+        MsgPack::Array(v) => Value::List(v.into_iter()
+            .filter_map(from_msgpack)
+            .map(|v| v.into())
+            .collect()),
+        MsgPack::Map(v) => Value::Map(v.into_iter()
+            .filter_map(|(k, v)| from_msgpack(v).map(|v| (key_to_string(k), v.into())))
+            .collect()),
+    })
+}
+
+fn key_to_string(key: MsgPack) -> String {
+    match key {
+        MsgPack::String(v) => if v.is_str() {
+            v.into_str().unwrap()
+        } else {
+            String::from_utf8_lossy(&v.into_bytes()).into_owned()
+        }
+        MsgPack::Binary(v) => String::from_utf8_lossy(&v).into_owned(),
+        v => v.to_string(),
+    }
+}
+
+/// Converts `Value` to MessagePack value.
+pub fn to_msgpack(value: &Value) -> MsgPack {
+    match value {
+        Value::Bool(v) => MsgPack::Boolean(*v),
+        Value::Int(v) => MsgPack::from(*v),
+        Value::Float(v) => MsgPack::F64(*v),
+        Value::String(v) => MsgPack::from(v.as_str()),
+        Value::Bytes(v) => MsgPack::Binary(v.clone()),
+        Value::List(v) => MsgPack::Array(v.iter().map(|v| to_msgpack(v)).collect()),
+        Value::Map(v) => MsgPack::Map(v.iter()
+            .map(|(k, v)| (MsgPack::from(k.as_str()), to_msgpack(v)))
+            .collect()),
+    }
+}
@@ -0,0 +1,323 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, TimeZone, Utc};
+use std::borrow::Cow;
+use std::fmt::Write;
+
+use crate::error::*;
+use crate::event::*;
+use crate::util::json::to_text;
+use crate::value::*;
+
+/// Compiled format string with event field placeholders.
+///
+/// Syntax: `%{path|filter|filter:arg}`, `%%` is a literal `%`. The path is a dot-separated list
+/// of map keys and list indices, e.g. `%{http.headers.0}`. Filters are applied left to right:
+///
+/// * `default:<text>` - `<text>` if the field is missing;
+/// * `upper`, `lower` - converts to upper/lower case;
+/// * `json` - escapes the value for embedding into a JSON string;
+/// * `date:<format>` - formats RFC 3339 timestamp string or Unix timestamp number in UTC using
+///   strftime-like `<format>`, e.g. `date:%Y-%m-%d`. Without `<format>` the timestamp is
+///   formatted as RFC 3339.
+///
+/// Missing fields are rendered as empty strings.
+///
+/// Path templates (see `parse_path()`) additionally treat the other `%` sequences as strftime-like
+/// specifiers of the time the path is rendered at, e.g. `/var/log/%{service}/%Y-%m-%d.log`.
+#[derive(Clone, Debug)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, Debug)]
+enum Part {
+    Literal(String),
+
+    /// Literal with strftime-like specifiers.
+    Date(String),
+
+    Field {
+        path: Vec<String>,
+        filters: Vec<Filter>,
+    },
+}
+
+#[derive(Clone, Debug)]
+enum Filter {
+    Default(String),
+    Upper,
+    Lower,
+    Json,
+    Date(Option<String>),
+}
+
+impl Template {
+    /// Parses the template from the string `config` value.
+    pub fn parse(config: &Spanned<Value>) -> Result<Self> {
+        Self::parse_str(config.as_str()?, false)
+            .map_err(|e| config.new_error(format!("invalid template: {}", e)))
+    }
+
+    /// Parses the path template from the string `config` value.
+    pub fn parse_path(config: &Spanned<Value>) -> Result<Self> {
+        Self::parse_str(config.as_str()?, true)
+            .map_err(|e| config.new_error(format!("invalid path template: {}", e)))
+    }
+
+    fn parse_str(s: &str, dates: bool) -> std::result::Result<Self, String> {
+        fn push_literal(parts: &mut Vec<Part>, literal: String, date: bool)
+            -> std::result::Result<(), String>
+        {
+            if date {
+                if StrftimeItems::new(&literal).any(|i| i == Item::Error) {
+                    return Err(format!("invalid date format `{}`", literal));
+                }
+                parts.push(Part::Date(literal));
+            } else if !literal.is_empty() {
+                parts.push(Part::Literal(literal));
+            }
+            Ok(())
+        }
+
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        // Whether the `literal` has strftime specifiers.
+        let mut date = false;
+        let mut rest = s;
+        while let Some(i) = rest.find('%') {
+            literal.push_str(&rest[..i]);
+            rest = &rest[i + 1..];
+            if rest.starts_with('{') {
+                let end = rest.find('}').ok_or_else(||
+                    format!("unterminated placeholder at offset {}", s.len() - rest.len() - 1))?;
+                push_literal(&mut parts, std::mem::replace(&mut literal, String::new()), date)?;
+                date = false;
+                parts.push(Self::parse_field(&rest[1..end])?);
+                rest = &rest[end + 1..];
+            } else if dates && !rest.is_empty() {
+                let len = rest.chars().next().unwrap().len_utf8();
+                literal.push('%');
+                literal.push_str(&rest[..len]);
+                rest = &rest[len..];
+                date = true;
+            } else if rest.starts_with('%') {
+                literal.push('%');
+                rest = &rest[1..];
+            } else {
+                literal.push('%');
+            }
+        }
+        literal.push_str(rest);
+        push_literal(&mut parts, literal, date)?;
+        Ok(Self {
+            parts,
+        })
+    }
+
+    fn parse_field(s: &str) -> std::result::Result<Part, String> {
+        let mut it = s.split('|');
+        let path = it.next().unwrap();
+        if path.is_empty() {
+            return Err(format!("empty field path in `%{{{}}}`", s));
+        }
+        let path = path.split('.').map(|s| s.to_owned()).collect();
+        let mut filters = Vec::new();
+        for f in it {
+            let (name, arg) = match f.find(':') {
+                Some(i) => (&f[..i], Some(&f[i + 1..])),
+                None => (f, None),
+            };
+            filters.push(match (name, arg) {
+                ("default", Some(arg)) => Filter::Default(arg.to_owned()),
+                ("upper", None) => Filter::Upper,
+                ("lower", None) => Filter::Lower,
+                ("json", None) => Filter::Json,
+                ("date", arg) => {
+                    if let Some(arg) = arg {
+                        if StrftimeItems::new(arg).any(|i| i == Item::Error) {
+                            return Err(format!("invalid date format `{}`", arg));
+                        }
+                    }
+                    Filter::Date(arg.map(|s| s.to_owned()))
+                }
+                _ => return Err(format!("unknown filter `{}`", f)),
+            });
+        }
+        Ok(Part::Field {
+            path,
+            filters,
+        })
+    }
+
+    /// Renders the template for the `event` appending the result to `out`.
+    pub fn render(&self, event: &Event, out: &mut String) {
+        self.render_parts(event, None, out);
+    }
+
+    /// Renders the path template for the `event` at `time`. Path separators in the field values
+    /// are replaced with `_` so the fields can't point outside of the directory they're in.
+    pub fn render_path(&self, event: &Event, time: &DateTime<Utc>) -> String {
+        let mut r = String::new();
+        self.render_parts(event, Some(time), &mut r);
+        r
+    }
+
+    fn render_parts(&self, event: &Event, time: Option<&DateTime<Utc>>, out: &mut String) {
+        for part in &self.parts {
+            match part {
+                Part::Literal(s) => out.push_str(s),
+                Part::Date(s) => write!(out, "{}", time.unwrap().format(s)).unwrap(),
+                Part::Field { path, filters } => {
+                    let mut value = lookup(event, path).map(Cow::Borrowed);
+                    for f in filters {
+                        value = f.apply(value);
+                    }
+                    if let Some(v) = value {
+                        let s = to_text(&v);
+                        if time.is_some() {
+                            match s.as_ref() {
+                                "." | ".." => out.push('_'),
+                                s => out.extend(s.chars()
+                                    .map(|c| if c == '/' || c == '\0' { '_' } else { c })),
+                            }
+                        } else {
+                            out.push_str(&s);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn lookup<'a>(event: &'a Event, path: &[String]) -> Option<&'a Value> {
+    let mut r = event.fields().get(&path[0])?;
+    for k in &path[1..] {
+        r = match r {
+            Value::Map(v) => &v.get(k)?.value,
+            Value::List(v) => &v.get(k.parse::<usize>().ok()?)?.value,
+            _ => return None,
+        };
+    }
+    Some(r)
+}
+
+impl Filter {
+    fn apply<'a>(&self, value: Option<Cow<'a, Value>>) -> Option<Cow<'a, Value>> {
+        let value = match (self, value) {
+            (Filter::Default(s), None) => return Some(Cow::Owned(Value::String(s.clone()))),
+            (_, None) => return None,
+            (_, Some(v)) => v,
+        };
+        let s = match self {
+            Filter::Default(_) => return Some(value),
+            Filter::Upper => to_text(&value).to_uppercase(),
+            Filter::Lower => to_text(&value).to_lowercase(),
+            Filter::Json => {
+                let s = serde_json::to_string(&*to_text(&value)).unwrap();
+                // Strip the quotes.
+                s[1..s.len() - 1].to_owned()
+            }
+            Filter::Date(fmt) => {
+                let t = if let Some(t) = timestamp(&value) {
+                    t
+                } else {
+                    return Some(value);
+                };
+                if let Some(fmt) = fmt {
+                    let mut r = String::new();
+                    write!(r, "{}", t.format(fmt)).unwrap();
+                    r
+                } else {
+                    t.to_rfc3339()
+                }
+            }
+        };
+        Some(Cow::Owned(Value::String(s)))
+    }
+}
+
+/// Parses RFC 3339 timestamp string or Unix timestamp number.
+pub fn timestamp(v: &Value) -> Option<DateTime<Utc>> {
+    match v {
+        Value::String(v) => DateTime::parse_from_rfc3339(v).ok().map(|t| t.with_timezone(&Utc)),
+        Value::Int(v) => Utc.timestamp_opt(*v, 0).single(),
+        Value::Float(v) if v.is_finite() => {
+            let secs = v.floor();
+            Utc.timestamp_opt(secs as i64, ((v - secs) * 1e9) as u32).single()
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn render(template: &str, event: &Event) -> String {
+        let mut r = String::new();
+        Template::parse_str(template, false).unwrap().render(event, &mut r);
+        r
+    }
+
+    fn event() -> Event {
+        let mut e = Event::new();
+        let f = e.fields_mut();
+        f.insert("message".into(), Value::String("say \"hi\"".into()));
+        f.insert("level".into(), Value::String("warn".into()));
+        f.insert("timestamp".into(), Value::String("2019-01-02T03:04:05.5+01:00".into()));
+        f.insert("epoch".into(), Value::Int(1546398245));
+        f.insert("n".into(), Value::Float(1.5));
+        f.insert("http".into(), value!({"status" => 200, "hosts" => ["a", "b"]}));
+        e
+    }
+
+    #[test]
+    fn placeholders() {
+        let cases = &[
+            ("", ""),
+            ("text", "text"),
+            ("100%% %d %", "100% %d %"),
+            ("%{timestamp} [%{level|upper}] %{message}",
+                "2019-01-02T03:04:05.5+01:00 [WARN] say \"hi\""),
+            ("%{http.status} %{http.hosts.1} %{http.hosts}", "200 b [\"a\",\"b\"]"),
+            ("%{n}%{missing}%{http.hosts.2}%{http.status.x}|", "1.5|"),
+            ("%{missing|default:-} %{level|default:x|upper}", "- WARN"),
+            ("%{missing|upper|default:X}", "X"),
+            ("{\"m\":\"%{message|json}\"}", r#"{"m":"say \"hi\""}"#),
+            ("%{timestamp|date:%Y-%m-%d %H:%M:%S}", "2019-01-02 02:04:05"),
+            ("%{epoch|date}", "2019-01-02T03:04:05+00:00"),
+            ("%{level|date:%Y}", "warn"),
+        ];
+        for &(template, exp) in cases {
+            assert_eq!(render(template, &event()), exp, "{}", template);
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        for t in &["%{message", "%{}", "%{message|bad}", "%{message|default}",
+            "%{message|upper:x}", "%{timestamp|date:%Q}"]
+        {
+            assert!(Template::parse_str(t, false).is_err(), "{}", t);
+        }
+    }
+
+    #[test]
+    fn path() {
+        let time = Utc.with_ymd_and_hms(2019, 1, 2, 3, 4, 5).unwrap();
+        let mut e = event();
+        e.fields_mut().insert("dir".into(), Value::String("../a/b".into()));
+        e.fields_mut().insert("dot".into(), Value::String("..".into()));
+        let cases = &[
+            ("/var/log/out.log", "/var/log/out.log"),
+            ("/var/log/%{level}/%Y-%m-%d.log", "/var/log/warn/2019-01-02.log"),
+            ("/%{dir}/%{dot}/%{missing}/%%{level}%%H%H", "/.._a_b/_//%{level}%H03"),
+        ];
+        for &(template, exp) in cases {
+            let t = Template::parse_str(template, true).unwrap();
+            assert_eq!(t.render_path(&e, &time), exp, "{}", template);
+        }
+        assert!(Template::parse_str("/%Q/%{level}", true).is_err());
+    }
+}
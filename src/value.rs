@@ -2,6 +2,7 @@ use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt;
 use std::ops::{Deref, DerefMut, Range};
+use std::time::Duration;
 
 use crate::error::*;
 
@@ -96,6 +97,19 @@ impl Spanned<Value> {
         Ok(self.get_opt_string(key)?.map(|s| s.as_str()))
     }
 
+    /// Parses the string value as a human-readable duration like `5s` or `1m 30s`.
+    pub fn as_duration(&self) -> Result<Duration> {
+        humantime::parse_duration(self.as_str()?)
+            .map_err(|e| self.new_error(format!("invalid duration: {}", e)))
+    }
+
+    pub fn get_opt_duration(&self, key: &str) -> Result<Option<Duration>> {
+        Ok(match self.get_opt(key)? {
+            Some(v) => Some(v.as_duration()?),
+            None => None,
+        })
+    }
+
     pub fn get(&self, key: &str) -> Result<&Spanned<Value>> {
         match self.get_opt(key)? {
             Some(v) => Ok(v),
@@ -184,6 +198,9 @@ pub enum Value {
     List(List),
     Map(Map),
     String(String),
+
+    /// Binary data.
+    Bytes(Vec<u8>),
 }
 
 impl Value {
@@ -196,6 +213,7 @@ impl Value {
             List(_) => ValueKind::List,
             Map(_) => ValueKind::Map,
             String(_) => ValueKind::String,
+            Bytes(_) => ValueKind::Bytes,
         }
     }
 
@@ -224,6 +242,7 @@ pub enum ValueKind {
     List,
     Map,
     String,
+    Bytes,
 }
 
 macro_rules! impl_bits {